@@ -1,13 +1,159 @@
 use anyhow::{anyhow, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::Device;
+use crate::vad::SilenceDetector;
+use rubato::{FftFixedIn, Resampler};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Sample rate whisper expects its input in
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// Input chunk size used when resampling
+const RESAMPLE_CHUNK: usize = 1024;
+
+/// Audio kept around detected speech when trimming silence
+const TRIM_PADDING: Duration = Duration::from_millis(100);
+
+/// Most a quiet recording is amplified by normalization (+30 dB), so near-silence
+/// doesn't get blown up into loud hiss
+const MAX_NORMALIZE_GAIN: f32 = 31.6;
+
+/// Analysis window for the spectral-flatness gate (~23ms at 44.1kHz)
+const FLATNESS_FRAME_LEN: usize = 1024;
+
+/// Audio captured by `AudioRecorder`, at the device's sample rate
+pub struct Recording {
+    /// Mono samples, already averaged down from the device's channels
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    /// Channels the device delivered
+    pub channels: u16,
+}
+
+impl Recording {
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.samples.len() as f64 / self.sample_rate as f64)
+    }
+}
+
+/// Optional steps `prepare_for_whisper` applies after downmixing and resampling
+#[derive(Debug, Clone, Default)]
+pub struct Preprocessing {
+    /// Fade the start in over this long, hiding the pop some devices make when opened
+    pub fade_in: Duration,
+    /// Cut leading and trailing silence
+    pub trim_silence: Option<SilenceTrim>,
+    /// Attenuate frames whose spectral flatness exceeds the threshold (threshold, gain)
+    pub music_gate: Option<(f32, f32)>,
+    /// Bring the level to a target in dBFS (mode, target)
+    pub normalize: Option<(Normalize, f32)>,
+    /// Zero-pad clips shorter than this at the end, since whisper rejects very short input
+    pub min_length: Duration,
+}
+
+/// What normalization brings to the target level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalize {
+    /// The loudest sample; a single click can keep the rest quiet
+    Peak,
+    /// The overall loudness, limited so the peak doesn't clip
+    Rms,
+}
+
+/// Window-based speech endpoint detection settings for trimming silence
+///
+/// Speech is found where a window's RMS reaches `open_threshold`; its boundaries are then
+/// widened while the RMS stays above the lower `close_threshold`, so soft word onsets and
+/// decaying endings are kept. Only the edges are trimmed, never gaps inside the speech.
+#[derive(Debug, Clone, Copy)]
+pub struct SilenceTrim {
+    pub window: Duration,
+    pub open_threshold: f32,
+    pub close_threshold: f32,
+}
+
+/// Level of the most recent block of captured audio, both in 0.0-1.0
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InputLevel {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+/// Decides when stream errors are frequent enough to rebuild the audio stream
+///
+/// Keeps the times of recent errors; once `max_errors` fall within `window`,
+/// `record` returns true and the history starts over. `max_errors` of 0 never trips.
+#[derive(Debug)]
+pub struct ErrorRate {
+    max_errors: u32,
+    window: Duration,
+    errors: VecDeque<Instant>,
+}
+
+impl ErrorRate {
+    pub fn new(max_errors: u32, window: Duration) -> Self {
+        Self {
+            max_errors,
+            window,
+            errors: VecDeque::new(),
+        }
+    }
+
+    /// Note an error at `now`, returning true if the limit was reached
+    pub fn record(&mut self, now: Instant) -> bool {
+        if self.max_errors == 0 {
+            return false;
+        }
+        while let Some(&oldest) = self.errors.front() {
+            if now.duration_since(oldest) > self.window {
+                self.errors.pop_front();
+            } else {
+                break;
+            }
+        }
+        self.errors.push_back(now);
+        if self.errors.len() >= self.max_errors as usize {
+            self.errors.clear();
+            return true;
+        }
+        false
+    }
+}
 
 pub struct AudioRecorder {
     stream: Option<cpal::Stream>,
     buffer: Arc<Mutex<Vec<f32>>>,
+    // f32 bits of the peak since it was last read and the latest block's RMS, written by
+    // the stream callback
+    peak: Arc<AtomicU32>,
+    rms: Arc<AtomicU32>,
+    // f32 bits of the silence threshold, and the trailing silence in frames (see SilenceDetector)
+    silence_threshold: Arc<AtomicU32>,
+    silent_frames: Arc<AtomicU64>,
+    // Longest recording kept, and whether the current one reached it
+    max_duration: Option<Duration>,
+    full: Arc<AtomicBool>,
     device: Option<Device>,
+    // Used to find the device again after rebuilding the audio host
+    device_name: Option<String>,
     sample_rate: u32,
+    channels: u16,
+    start_retries: u32,
+    start_backoff: Duration,
+    // Stream errors, shared with the error callback, and whether they call for a rebuild
+    error_rate: Arc<Mutex<ErrorRate>>,
+    needs_rebuild: Arc<AtomicBool>,
 }
 
 /// Returns a list of available input device names
@@ -20,30 +166,169 @@ pub fn list_input_devices() -> Result<Vec<String>> {
     Ok(devices)
 }
 
-/// Gets a device by index from the input devices list
-pub fn get_device_by_index(index: usize) -> Result<Device> {
+/// Returns a list of available output device names
+pub fn list_output_devices() -> Result<Vec<String>> {
+    let host = cpal::default_host();
+    let devices: Vec<String> = host
+        .output_devices()?
+        .filter_map(|d| d.name().ok())
+        .collect();
+    Ok(devices)
+}
+
+/// Finds an output device by name, enumerating the devices afresh
+pub fn find_output_device(name: &str) -> Result<Device> {
     let host = cpal::default_host();
     let device = host
-        .input_devices()?
-        .nth(index)
-        .ok_or_else(|| anyhow!("Device index {} not found", index))?;
+        .output_devices()?
+        .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+        .ok_or_else(|| anyhow!("Output device '{}' not found", name))?;
     Ok(device)
 }
 
+/// Pick a device from `available` using name patterns in order of preference
+/// Patterns match case-insensitively anywhere in the name; the first pattern that
+/// matches any device wins, so "headphones,speakers" prefers headphones when present.
+pub fn select_by_preference<'a>(patterns: &[String], available: &'a [String]) -> Option<&'a str> {
+    patterns.iter().find_map(|pattern| {
+        let pattern = pattern.to_lowercase();
+        available
+            .iter()
+            .find(|name| name.to_lowercase().contains(&pattern))
+            .map(String::as_str)
+    })
+}
+
+/// The host's default input device
+pub fn default_input_device() -> Result<Device> {
+    require_default_input(|| cpal::default_host().default_input_device())
+}
+
+/// The device `lookup` finds, or an error saying there is no default input device
+fn require_default_input(lookup: impl FnOnce() -> Option<Device>) -> Result<Device> {
+    lookup().ok_or_else(|| anyhow!("No default input device found"))
+}
+
+/// Gets an input device by name, which unlike its position survives devices being plugged in
+/// The devices are enumerated afresh on every call.
+/// An exact match wins; otherwise `name` may be any part of one device's name, in any case
+/// (e.g. "usb" for "Microphone (USB Audio)"). No match or several give an error that lists them.
+pub fn get_device_by_name(name: &str) -> Result<Device> {
+    let host = cpal::default_host();
+    let mut devices: Vec<(String, Device)> = host
+        .input_devices()?
+        .filter_map(|d| d.name().ok().map(|n| (n, d)))
+        .collect();
+    if let Some(index) = devices.iter().position(|(n, _)| n == name) {
+        return Ok(devices.swap_remove(index).1);
+    }
+
+    let part = name.to_lowercase();
+    let matches: Vec<usize> = (0..devices.len())
+        .filter(|&i| devices[i].0.to_lowercase().contains(&part))
+        .collect();
+    match matches[..] {
+        [index] => Ok(devices.swap_remove(index).1),
+        [] => Err(anyhow!(
+            "Input device '{}' not found. Available: {}",
+            name,
+            quoted_names(devices.iter().map(|(n, _)| n))
+        )),
+        _ => Err(anyhow!(
+            "'{}' matches several input devices: {}",
+            name,
+            quoted_names(matches.iter().map(|&i| &devices[i].0))
+        )),
+    }
+}
+
+fn quoted_names<'a>(names: impl Iterator<Item = &'a String>) -> String {
+    let names: Vec<String> = names.map(|n| format!("'{}'", n)).collect();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
 impl AudioRecorder {
     pub fn new() -> Self {
         Self {
             stream: None,
             buffer: Arc::new(Mutex::new(Vec::new())),
+            peak: Arc::new(AtomicU32::new(0)),
+            rms: Arc::new(AtomicU32::new(0)),
+            silence_threshold: Arc::new(AtomicU32::new(0.01f32.to_bits())),
+            silent_frames: Arc::new(AtomicU64::new(0)),
+            max_duration: None,
+            full: Arc::new(AtomicBool::new(false)),
             device: None,
+            device_name: None,
             sample_rate: 44100,
+            channels: 1,
+            start_retries: 0,
+            start_backoff: Duration::ZERO,
+            error_rate: Arc::new(Mutex::new(ErrorRate::new(0, Duration::ZERO))),
+            needs_rebuild: Arc::new(AtomicBool::new(false)),
         }
     }
 
     pub fn set_device(&mut self, device: Device) {
+        self.device_name = device.name().ok();
         self.device = Some(device);
     }
 
+    /// Rebuild the stream once `max_errors` stream errors happen within `window`
+    /// (see `recover_if_needed`); 0 disables this
+    pub fn set_error_policy(&mut self, max_errors: u32, window: Duration) {
+        if let Ok(mut error_rate) = self.error_rate.lock() {
+            *error_rate = ErrorRate::new(max_errors, window);
+        }
+    }
+
+    /// Retry opening the input stream up to `retries` extra times when `start` fails,
+    /// waiting `backoff` before the first retry and doubling it after each one
+    pub fn set_retry_policy(&mut self, retries: u32, backoff: Duration) {
+        self.start_retries = retries;
+        self.start_backoff = backoff;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Stop keeping audio once a recording is `max` long, None = no limit
+    /// Takes effect from the next recording on.
+    pub fn set_max_duration(&mut self, max: Option<Duration>) {
+        self.max_duration = max;
+    }
+
+    /// Whether the current recording reached the maximum duration and is dropping audio
+    pub fn is_full(&self) -> bool {
+        self.full.load(Ordering::Relaxed)
+    }
+
+    /// Peak since the previous call and RMS of the most recently captured block
+    /// (zero when not recording)
+    pub fn input_level(&self) -> InputLevel {
+        InputLevel {
+            peak: f32::from_bits(self.peak.swap(0, Ordering::Relaxed)),
+            rms: f32::from_bits(self.rms.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Input RMS (0-1) below which the microphone counts as silent for `silence_duration`
+    pub fn set_silence_threshold(&mut self, threshold: f32) {
+        self.silence_threshold.store(threshold.to_bits(), Ordering::Relaxed);
+    }
+
+    /// How long the input has been silent since speech was last heard in this recording
+    /// Zero while recording hasn't picked up any speech yet, so a recording is never
+    /// ended before the user starts talking.
+    pub fn silence_duration(&self) -> Duration {
+        let frames = self.silent_frames.load(Ordering::Relaxed);
+        Duration::from_secs_f64(frames as f64 / self.sample_rate.max(1) as f64)
+    }
 
     pub fn start(&mut self) -> Result<()> {
         // Stop any existing stream first
@@ -52,26 +337,80 @@ impl AudioRecorder {
             std::thread::sleep(std::time::Duration::from_millis(50));
         }
 
-        let device = self.device.as_ref()
-            .ok_or_else(|| anyhow!("No input device selected"))?;
-
-        let config: cpal::StreamConfig = device.default_input_config()?.into();
-        self.sample_rate = config.sample_rate.0;
-
         // Clear buffer before starting new recording
         {
             let mut lock = self.buffer.lock().map_err(|_| anyhow!("Failed to lock buffer"))?;
             lock.clear();
         }
+        self.full.store(false, Ordering::Relaxed);
+
+        // Some USB interfaces aren't ready right away, so retry with backoff
+        let (stream, sample_rate, channels) =
+            retry_with_backoff(self.start_retries, self.start_backoff, || {
+                self.open_stream()
+            })?;
+
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        self.stream = Some(stream);
+
+        Ok(())
+    }
+
+    /// Build and start an input stream on the selected device,
+    /// returning it with its sample rate and channel count
+    fn open_stream(&self) -> Result<(cpal::Stream, u32, u16)> {
+        let device = self.device.as_ref()
+            .ok_or_else(|| anyhow!("No input device selected"))?;
+
+        let config: cpal::StreamConfig = device.default_input_config()?.into();
+        let sample_rate = config.sample_rate.0;
+        let channels = config.channels;
 
         let buffer_clone = self.buffer.clone();
-        let err_fn = |err| eprintln!("An error occurred on stream: {}", err);
+        let peak_clone = self.peak.clone();
+        let rms_clone = self.rms.clone();
+        let silence_threshold = self.silence_threshold.clone();
+        let silent_frames = self.silent_frames.clone();
+        silent_frames.store(0, Ordering::Relaxed);
+        // The buffer holds mono samples, so the cap doesn't depend on the channel count
+        let max_samples = self.max_duration.map_or(usize::MAX, |max| {
+            (max.as_secs_f64() * sample_rate as f64) as usize
+        });
+        let full = self.full.clone();
+        let threshold = f32::from_bits(silence_threshold.load(Ordering::Relaxed));
+        let mut silence = SilenceDetector::new(threshold);
+        let error_rate = self.error_rate.clone();
+        let needs_rebuild = self.needs_rebuild.clone();
+        let err_fn = move |err| {
+            warn!("An error occurred on stream: {}", err);
+            if let Ok(mut error_rate) = error_rate.lock() {
+                if error_rate.record(Instant::now()) {
+                    needs_rebuild.store(true, Ordering::Relaxed);
+                }
+            }
+        };
 
         let stream = device.build_input_stream(
             &config,
             move |data: &[f32], _: &_| {
+                // Levels are published lock-free so readers never stall the callback
+                let level = block_level(data);
+                // Non-negative floats order like their bits, so this keeps the highest peak
+                peak_clone.fetch_max(level.peak.to_bits(), Ordering::Relaxed);
+                rms_clone.store(level.rms.to_bits(), Ordering::Relaxed);
+                silence.set_threshold(f32::from_bits(silence_threshold.load(Ordering::Relaxed)));
+                silence.update(data, channels, sample_rate);
+                silent_frames.store(silence.silent_frames(), Ordering::Relaxed);
+
                 if let Ok(mut lock) = buffer_clone.lock() {
-                    lock.extend_from_slice(data);
+                    if lock.len() < max_samples {
+                        extend_mono(&mut lock, data, channels);
+                        if lock.len() >= max_samples {
+                            lock.truncate(max_samples);
+                            full.store(true, Ordering::Relaxed);
+                        }
+                    }
                 }
             },
             err_fn,
@@ -79,12 +418,49 @@ impl AudioRecorder {
         )?;
 
         stream.play()?;
-        self.stream = Some(stream);
+        Ok((stream, sample_rate, channels))
+    }
 
-        Ok(())
+    /// Tear down and rebuild the audio stream if stream errors passed the configured rate
+    ///
+    /// The device is looked up again by name on a fresh host, so a driver that reset or
+    /// a device that was re-plugged is picked up. A recording in progress keeps going on
+    /// the new stream. Returns true if a rebuild happened.
+    pub fn recover_if_needed(&mut self) -> Result<bool> {
+        if !self.needs_rebuild.swap(false, Ordering::Relaxed) {
+            return Ok(false);
+        }
+
+        warn!("Too many audio stream errors, rebuilding the audio stream...");
+        let was_recording = self.stream.take().is_some();
+        std::thread::sleep(Duration::from_millis(50));
+
+        if let Some(name) = &self.device_name {
+            self.device = Some(get_device_by_name(name)?);
+        }
+
+        if was_recording {
+            let (stream, sample_rate, channels) = self.open_stream()?;
+            if sample_rate != self.sample_rate {
+                // Samples at different rates can't share one buffer
+                warn!("Audio format changed after the rebuild, earlier audio of this recording was dropped.");
+                if let Ok(mut lock) = self.buffer.lock() {
+                    lock.clear();
+                }
+                self.sample_rate = sample_rate;
+            }
+            self.channels = channels;
+            self.stream = Some(stream);
+        }
+
+        info!(
+            "Audio stream rebuilt on '{}'.",
+            self.device_name.as_deref().unwrap_or("unknown device")
+        );
+        Ok(true)
     }
 
-    pub fn stop(&mut self) -> Result<Vec<f32>> {
+    pub fn stop(&mut self) -> Result<Recording> {
         // Stop the stream first
         if let Some(stream) = self.stream.take() {
             drop(stream);
@@ -92,29 +468,583 @@ impl AudioRecorder {
 
         // Small delay to ensure stream callback has finished
         std::thread::sleep(std::time::Duration::from_millis(50));
+        self.peak.store(0, Ordering::Relaxed);
+        self.rms.store(0, Ordering::Relaxed);
 
         // Get the data and clear the buffer
         let mut lock = self.buffer.lock().map_err(|_| anyhow!("Failed to lock buffer"))?;
         let data = std::mem::take(&mut *lock); // Takes the data and replaces with empty Vec
-        Ok(data)
+        Ok(Recording {
+            samples: data,
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+        })
+    }
+}
+
+/// Call `open` until it succeeds, at most `retries` extra times, waiting `backoff` before
+/// the first retry and doubling it after each one
+fn retry_with_backoff<T>(
+    retries: u32,
+    backoff: Duration,
+    mut open: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        match open() {
+            Ok(opened) => return Ok(opened),
+            Err(e) if attempt < retries => {
+                let delay = backoff * 2u32.pow(attempt.min(5));
+                warn!(
+                    "Failed to open input stream ({}), retrying in {}ms...",
+                    e,
+                    delay.as_millis()
+                );
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Write mono samples as a 16-bit PCM WAV file
+pub fn save_to_file(audio_data: &[f32], sample_rate: u32, path: &Path) -> Result<()> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    // Simple conversion from f32 (-1.0 to 1.0) to i16
+    for &sample in audio_data {
+        let amplitude = i16::MAX as f32;
+        let val = (sample * amplitude).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        writer.write_sample(val)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Read a 16-bit mono WAV (as written by `save_to_file`) back into f32 samples
+pub fn load_samples(path: &Path) -> Result<Vec<f32>> {
+    let mut reader = hound::WavReader::open(path)?;
+    let samples = reader
+        .samples::<i16>()
+        .map(|sample| sample.map(|sample| sample as f32 / i16::MAX as f32))
+        .collect::<Result<Vec<f32>, _>>()?;
+    Ok(samples)
+}
+
+/// Split `samples` into consecutive (start, end) ranges of at most `chunk_length`
+/// Each cut is placed at the quietest spot in the last quarter of its chunk, so words
+/// are rarely split between chunks.
+pub fn chunk_bounds(samples: &[f32], sample_rate: u32, chunk_length: Duration) -> Vec<(usize, usize)> {
+    let chunk_len = ((chunk_length.as_secs_f64() * sample_rate as f64) as usize).max(1);
+    let window = ((0.1 * sample_rate as f64) as usize).max(1);
+    let mut bounds = Vec::new();
+    let mut start = 0;
+    while samples.len() - start > chunk_len {
+        let latest = start + chunk_len;
+        let earliest = latest - chunk_len / 4;
+        let cut = (earliest..latest.saturating_sub(window).max(earliest + 1))
+            .step_by((window / 2).max(1))
+            .min_by(|&a, &b| {
+                let level = |at: usize| block_level(&samples[at..(at + window).min(latest)]).rms;
+                level(a).total_cmp(&level(b))
+            })
+            .map_or(latest, |quietest| (quietest + window / 2).min(latest));
+        bounds.push((start, cut));
+        start = cut;
+    }
+    bounds.push((start, samples.len()));
+    bounds
+}
+
+/// Turn a recording into the 16 kHz mono samples whisper wants
+/// Pipeline: resample to 16 kHz -> optional steps from `preprocessing` -> pad to minimum length
+pub fn prepare_samples(recording: &Recording, preprocessing: &Preprocessing) -> Result<Vec<f32>> {
+    let mut samples = resample(&recording.samples, recording.sample_rate, WHISPER_SAMPLE_RATE)?;
+    // Before trimming, so the opening transient isn't taken for speech
+    fade_in(&mut samples, WHISPER_SAMPLE_RATE, preprocessing.fade_in);
+
+    if let Some(trim) = &preprocessing.trim_silence {
+        samples = trim_silence(&samples, WHISPER_SAMPLE_RATE, trim).to_vec();
+    }
+
+    if let Some((threshold, gain)) = preprocessing.music_gate {
+        attenuate_flat_frames(&mut samples, threshold, gain);
+    }
+    match preprocessing.normalize {
+        Some((Normalize::Peak, target_dbfs)) => normalize_peak(&mut samples, target_dbfs),
+        Some((Normalize::Rms, target_dbfs)) => normalize_rms(&mut samples, target_dbfs),
+        None => {}
+    }
+    pad_to_length(&mut samples, WHISPER_SAMPLE_RATE, preprocessing.min_length);
+    Ok(samples)
+}
+
+/// The part of `samples` from the first to the last speech, keeping TRIM_PADDING of audio
+/// on either side so word onsets and endings aren't clipped
+/// Without any speech (see `speech_bounds`) the samples are returned untouched.
+pub fn trim_silence<'a>(samples: &'a [f32], sample_rate: u32, trim: &SilenceTrim) -> &'a [f32] {
+    match speech_bounds(samples, sample_rate, trim) {
+        Some((start, end)) => {
+            let padding = (TRIM_PADDING.as_secs_f64() * sample_rate as f64) as usize;
+            &samples[start.saturating_sub(padding)..(end + padding).min(samples.len())]
+        }
+        None => samples,
+    }
+}
+
+/// Scale `samples` so the loudest one is at `target_dbfs` (e.g. -3.0)
+/// Silent input is left alone, and the gain is capped at MAX_NORMALIZE_GAIN.
+pub fn normalize_peak(samples: &mut [f32], target_dbfs: f32) {
+    let peak = block_level(samples).peak;
+    apply_gain(samples, dbfs_to_amplitude(target_dbfs) / peak);
+}
+
+/// Scale `samples` so their RMS is at `target_dbfs` (e.g. -20.0), never past clipping
+/// Silent input is left alone, and the gain is capped at MAX_NORMALIZE_GAIN.
+pub fn normalize_rms(samples: &mut [f32], target_dbfs: f32) {
+    let level = block_level(samples);
+    let gain = (dbfs_to_amplitude(target_dbfs) / level.rms).min(1.0 / level.peak);
+    apply_gain(samples, gain);
+}
+
+/// Multiply by `gain` (at most MAX_NORMALIZE_GAIN); infinite/NaN gains from silence do nothing
+fn apply_gain(samples: &mut [f32], gain: f32) {
+    if !gain.is_finite() || gain <= 0.0 {
+        return;
+    }
+    let gain = gain.min(MAX_NORMALIZE_GAIN);
+    for sample in samples.iter_mut() {
+        *sample *= gain;
+    }
+}
+
+fn dbfs_to_amplitude(dbfs: f32) -> f32 {
+    10f32.powf(dbfs / 20.0)
+}
+
+/// Ramp the gain linearly from 0 to 1 over the first `length` of `samples`
+/// Later samples are left unchanged; a zero `length` does nothing.
+pub fn fade_in(samples: &mut [f32], sample_rate: u32, length: Duration) {
+    let ramp = (length.as_secs_f64() * sample_rate as f64) as usize;
+    for (i, sample) in samples.iter_mut().take(ramp).enumerate() {
+        *sample *= i as f32 / ramp as f32;
+    }
+}
+
+/// Append silence until `samples` lasts at least `min_length`
+pub fn pad_to_length(samples: &mut Vec<f32>, sample_rate: u32, min_length: Duration) {
+    let min_samples = (min_length.as_secs_f64() * sample_rate as f64).ceil() as usize;
+    if samples.len() < min_samples {
+        samples.resize(min_samples, 0.0);
+    }
+}
+
+/// Write a recording to `path` as a canonical 16 kHz mono 16-bit WAV, so whisper
+/// never has to convert it, and verify the written header before handing it over
+pub fn prepare_for_whisper(
+    recording: &Recording,
+    preprocessing: &Preprocessing,
+    path: &Path,
+) -> Result<PathBuf> {
+    let samples = prepare_samples(recording, preprocessing)?;
+    save_to_file(&samples, WHISPER_SAMPLE_RATE, path)?;
+
+    let spec = hound::WavReader::open(path)?.spec();
+    if spec.channels != 1 || spec.sample_rate != WHISPER_SAMPLE_RATE || spec.bits_per_sample != 16 {
+        return Err(anyhow!(
+            "Prepared WAV has unexpected format ({} channels, {} Hz, {} bits)",
+            spec.channels,
+            spec.sample_rate,
+            spec.bits_per_sample
+        ));
+    }
+    Ok(path.to_path_buf())
+}
+
+/// Append an input block to `buffer` as mono, averaging each frame of interleaved channels
+/// Mono input is copied as-is.
+pub fn extend_mono(buffer: &mut Vec<f32>, data: &[f32], channels: u16) {
+    if channels <= 1 {
+        buffer.extend_from_slice(data);
+        return;
+    }
+    buffer.extend(
+        data.chunks_exact(channels as usize)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+    );
+}
+
+/// Resample mono audio, returning exactly `len * to_rate / from_rate` samples
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Result<Vec<f32>> {
+    if from_rate == to_rate || samples.is_empty() {
+        return Ok(samples.to_vec());
+    }
+
+    let mut resampler =
+        FftFixedIn::<f32>::new(from_rate as usize, to_rate as usize, RESAMPLE_CHUNK, 2, 1)?;
+    let expected_len = (samples.len() as u64 * to_rate as u64 / from_rate as u64) as usize;
+    let delay = resampler.output_delay();
+    let mut output = Vec::with_capacity(expected_len + delay);
+
+    let mut position = 0;
+    while samples.len() - position >= resampler.input_frames_next() {
+        let frames = resampler.input_frames_next();
+        let input = [&samples[position..position + frames]];
+        let chunk = resampler.process(&input[..], None)?;
+        output.extend_from_slice(&chunk[0]);
+        position += frames;
+    }
+    if position < samples.len() {
+        let input = [&samples[position..]];
+        let chunk = resampler.process_partial(Some(&input[..]), None)?;
+        output.extend_from_slice(&chunk[0]);
+    }
+
+    // Flush the filter delay so the end of the clip isn't cut off
+    while output.len() < expected_len + delay {
+        let chunk = resampler.process_partial::<&[f32]>(None, None)?;
+        if chunk[0].is_empty() {
+            break;
+        }
+        output.extend_from_slice(&chunk[0]);
+    }
+
+    output.drain(..delay.min(output.len()));
+    // Short clips may not fill the filter, pad them so the length is always exact
+    output.resize(expected_len, 0.0);
+    Ok(output)
+}
+
+/// Peak and RMS level of a block of samples
+pub fn block_level(samples: &[f32]) -> InputLevel {
+    if samples.is_empty() {
+        return InputLevel::default();
+    }
+    let peak = samples.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+    let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+    InputLevel { peak, rms }
+}
+
+/// Sample range `start..end` holding speech, found with half-overlapping RMS windows
+/// Returns None when no window reaches the open threshold.
+pub fn speech_bounds(samples: &[f32], sample_rate: u32, trim: &SilenceTrim) -> Option<(usize, usize)> {
+    let window_len = ((trim.window.as_secs_f64() * sample_rate as f64) as usize).max(1);
+    let hop = (window_len / 2).max(1);
+
+    let levels: Vec<f32> = (0..samples.len())
+        .step_by(hop)
+        .map(|start| block_level(&samples[start..(start + window_len).min(samples.len())]).rms)
+        .collect();
+
+    let first = levels.iter().position(|&rms| rms >= trim.open_threshold)?;
+    let last = levels.iter().rposition(|&rms| rms >= trim.open_threshold)?;
+
+    // Hysteresis: extend outwards while the level stays above the close threshold
+    let mut start_window = first;
+    while start_window > 0 && levels[start_window - 1] >= trim.close_threshold {
+        start_window -= 1;
+    }
+    let mut end_window = last;
+    while end_window + 1 < levels.len() && levels[end_window + 1] >= trim.close_threshold {
+        end_window += 1;
+    }
+
+    let start = start_window * hop;
+    let end = (end_window * hop + window_len).min(samples.len());
+    Some((start, end))
+}
+
+/// `frame_flatness` of one frame, planning an FFT for its length
+#[cfg(test)]
+fn spectral_flatness(frame: &[f32]) -> f32 {
+    if frame.is_empty() {
+        return 0.0;
+    }
+    let mut planner = FftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(frame.len());
+    frame_flatness(fft.as_ref(), frame)
+}
+
+/// Attenuate frames whose spectral flatness exceeds `threshold` by multiplying them by `gain`
+/// Broadband noise and dense background music score high, so whisper hears less of them
+/// and is less tempted to emit `[MÚSICA]`-style artifacts. A trailing partial frame is left as-is.
+pub fn attenuate_flat_frames(samples: &mut [f32], threshold: f32, gain: f32) {
+    let mut planner = FftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(FLATNESS_FRAME_LEN);
+
+    for frame in samples.chunks_exact_mut(FLATNESS_FRAME_LEN) {
+        if frame_flatness(fft.as_ref(), frame) > threshold {
+            for sample in frame.iter_mut() {
+                *sample *= gain;
+            }
+        }
+    }
+}
+
+/// Spectral flatness of a frame: geometric mean / arithmetic mean of its power spectrum
+/// Close to 0 for tonal content (a sine, a held note), close to 1 for noise-like content.
+/// `fft` must be planned for the frame's length.
+fn frame_flatness(fft: &dyn Fft<f32>, frame: &[f32]) -> f32 {
+    let len = frame.len();
+    // Hann window to keep spectral leakage from flattening tonal frames
+    let mut buffer: Vec<Complex<f32>> = frame
+        .iter()
+        .enumerate()
+        .map(|(i, &sample)| {
+            let window = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / len as f32).cos();
+            Complex::new(sample * window, 0.0)
+        })
+        .collect();
+    fft.process(&mut buffer);
+
+    // Only the first half of a real signal's spectrum is unique
+    let bins = &buffer[..len / 2 + 1];
+    let power: Vec<f32> = bins.iter().map(|c| c.norm_sqr() + 1e-12).collect();
+    let count = power.len() as f32;
+    let log_mean = power.iter().map(|p| p.ln()).sum::<f32>() / count;
+    let mean = power.iter().sum::<f32>() / count;
+    log_mean.exp() / mean
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `len` samples of a sine at `frequency` Hz
+    fn tone(frequency: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                0.5 * (2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin()
+            })
+            .collect()
+    }
+
+    /// `len` samples of repeatable white noise in -0.5..0.5
+    fn noise(len: usize) -> Vec<f32> {
+        let mut state: u32 = 0x1234_5678;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1 << 24) as f32 - 0.5
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tone_is_less_flat_than_noise() {
+        let tonal = spectral_flatness(&tone(440.0, 44100, FLATNESS_FRAME_LEN));
+        let noisy = spectral_flatness(&noise(FLATNESS_FRAME_LEN));
+        assert!(tonal < 0.05, "tone flatness {}", tonal);
+        assert!(noisy > 0.3, "noise flatness {}", noisy);
+    }
+
+    #[test]
+    fn flat_frames_are_attenuated_and_tonal_ones_kept() {
+        let mut samples = tone(440.0, 44100, FLATNESS_FRAME_LEN);
+        samples.extend(noise(FLATNESS_FRAME_LEN));
+        let original = samples.clone();
+        attenuate_flat_frames(&mut samples, 0.3, 0.1);
+        assert_eq!(
+            samples[..FLATNESS_FRAME_LEN],
+            original[..FLATNESS_FRAME_LEN]
+        );
+        let last = samples.len() - 1;
+        assert!((samples[last] - original[last] * 0.1).abs() < 1e-6);
     }
 
-    pub fn save_to_file(&self, audio_data: &[f32], filename: &str) -> Result<()> {
-        let spec = hound::WavSpec {
+    #[test]
+    fn stereo_48k_recording_comes_out_as_16k_mono_wav() {
+        // One second of a tone on the left channel, silence on the right
+        let left = tone(440.0, 48000, 48000);
+        let interleaved: Vec<f32> = left.iter().flat_map(|&sample| [sample, 0.0]).collect();
+        let mut samples = Vec::new();
+        extend_mono(&mut samples, &interleaved, 2);
+        assert_eq!(samples.len(), 48000);
+        assert!((samples[100] - left[100] / 2.0).abs() < 1e-6);
+
+        let recording = Recording {
+            samples,
+            sample_rate: 48000,
+            channels: 2,
+        };
+        let path = std::env::temp_dir().join(format!("prepare_test_{}.wav", std::process::id()));
+        let prepared = prepare_for_whisper(&recording, &Preprocessing::default(), &path).unwrap();
+        let reader = hound::WavReader::open(&prepared).unwrap();
+        let spec = reader.spec();
+        let len = reader.len();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(spec.channels, 1);
+        assert_eq!(spec.sample_rate, WHISPER_SAMPLE_RATE);
+        assert_eq!(spec.bits_per_sample, 16);
+        assert_eq!(spec.sample_format, hound::SampleFormat::Int);
+        assert_eq!(len, WHISPER_SAMPLE_RATE);
+    }
+
+    #[test]
+    fn resample_returns_the_exact_length() {
+        let samples = tone(440.0, 48000, 4800);
+        assert_eq!(resample(&samples, 48000, 16000).unwrap().len(), 1600);
+        assert_eq!(resample(&samples, 16000, 16000).unwrap(), samples);
+    }
+
+    #[test]
+    fn short_clip_is_padded_to_the_minimum_length() {
+        // 50 ms at 44.1 kHz
+        let recording = Recording {
+            samples: tone(440.0, 44100, 2205),
+            sample_rate: 44100,
             channels: 1,
-            sample_rate: self.sample_rate,
-            bits_per_sample: 16,
-            sample_format: hound::SampleFormat::Int,
         };
+        let unpadded = prepare_samples(&recording, &Preprocessing::default()).unwrap();
+        assert_eq!(unpadded.len(), 800);
 
-        let mut writer = hound::WavWriter::create(filename, spec)?;
-        // Simple conversion from f32 (-1.0 to 1.0) to i16
-        for &sample in audio_data {
-            let amplitude = i16::MAX as f32;
-            let val = (sample * amplitude).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
-            writer.write_sample(val)?;
-        }
-        writer.finalize()?;
-        Ok(())
+        let preprocessing = Preprocessing {
+            min_length: Duration::from_millis(1000),
+            ..Preprocessing::default()
+        };
+        let padded = prepare_samples(&recording, &preprocessing).unwrap();
+        assert_eq!(padded.len(), WHISPER_SAMPLE_RATE as usize);
+        assert_eq!(padded[..800], unpadded[..]);
+        assert!(padded[800..].iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn silence_around_speech_is_trimmed_down_to_the_padding() {
+        let second = WHISPER_SAMPLE_RATE as usize;
+        let mut samples = vec![0.0; second];
+        samples.extend(tone(440.0, WHISPER_SAMPLE_RATE, second));
+        samples.extend(vec![0.0; second]);
+        let trim = SilenceTrim {
+            window: Duration::from_millis(20),
+            open_threshold: 0.05,
+            close_threshold: 0.02,
+        };
+
+        // The 20 ms windows overlapping the tone by half count as speech
+        let (start, end) = speech_bounds(&samples, WHISPER_SAMPLE_RATE, &trim).unwrap();
+        assert_eq!((start, end), (second - 160, 2 * second + 160));
+        let padding = second / 10;
+        let trimmed = trim_silence(&samples, WHISPER_SAMPLE_RATE, &trim);
+        assert_eq!(trimmed.len(), end - start + 2 * padding);
+        assert_eq!(
+            trimmed[padding + 160..][..second],
+            samples[second..2 * second]
+        );
+
+        let silence = vec![0.0; second];
+        assert_eq!(
+            trim_silence(&silence, WHISPER_SAMPLE_RATE, &trim).len(),
+            second
+        );
+    }
+
+    #[test]
+    fn brief_dip_is_not_taken_for_the_end_of_speech() {
+        let ms = |count: usize| count * WHISPER_SAMPLE_RATE as usize / 1000;
+        let speech = || tone(440.0, WHISPER_SAMPLE_RATE, ms(300));
+        let mut samples = vec![0.0; ms(200)];
+        samples.extend(speech());
+        samples.extend(vec![0.0; ms(30)]);
+        samples.extend(speech());
+        // A soft tail between the close and open thresholds, e.g. a trailing consonant
+        samples.extend(speech()[..ms(100)].iter().map(|s| s * 0.1));
+        samples.extend(vec![0.0; ms(200)]);
+        let mut trim = SilenceTrim {
+            window: Duration::from_millis(20),
+            open_threshold: 0.05,
+            close_threshold: 0.02,
+        };
+
+        let bounds = speech_bounds(&samples, WHISPER_SAMPLE_RATE, &trim);
+        assert_eq!(bounds, Some((ms(200) - ms(10), ms(930) + ms(10))));
+
+        // Without hysteresis the tail is cut, but the dip still isn't the end
+        trim.close_threshold = trim.open_threshold;
+        let bounds = speech_bounds(&samples, WHISPER_SAMPLE_RATE, &trim);
+        assert_eq!(bounds, Some((ms(200) - ms(10), ms(830) + ms(10))));
+    }
+
+    #[test]
+    fn errors_within_the_window_trigger_a_rebuild() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut errors = ErrorRate::new(3, Duration::from_secs(1));
+        assert!(!errors.record(at(0)));
+        assert!(!errors.record(at(400)));
+        // The first two have left the window by now
+        assert!(!errors.record(at(1500)));
+        assert!(!errors.record(at(1600)));
+        assert!(errors.record(at(1700)));
+        // The history starts over after a rebuild
+        assert!(!errors.record(at(1800)));
+
+        let mut never = ErrorRate::new(0, Duration::from_secs(1));
+        assert!((0..10).all(|ms| !never.record(at(ms))));
+    }
+
+    #[test]
+    fn missing_default_input_device_is_a_clear_error() {
+        let Err(error) = require_default_input(|| None) else {
+            panic!("expected an error without a default input device");
+        };
+        assert_eq!(error.to_string(), "No default input device found");
+    }
+
+    #[test]
+    fn stream_opens_on_the_second_attempt() {
+        let mut attempts = 0;
+        let opened = retry_with_backoff(2, Duration::from_millis(1), || {
+            attempts += 1;
+            if attempts == 1 {
+                Err(anyhow!("device not ready"))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(opened.unwrap(), 2);
+
+        let failed: Result<()> = retry_with_backoff(1, Duration::ZERO, || Err(anyhow!("gone")));
+        assert!(failed.is_err());
+    }
+
+    #[test]
+    fn fade_in_ramps_the_start_and_leaves_the_rest() {
+        let mut samples = vec![1.0; 8];
+        fade_in(&mut samples, 1000, Duration::from_millis(4));
+        assert_eq!(samples, vec![0.0, 0.25, 0.5, 0.75, 1.0, 1.0, 1.0, 1.0]);
+
+        let mut untouched = vec![1.0; 8];
+        fade_in(&mut untouched, 1000, Duration::ZERO);
+        assert_eq!(untouched, vec![1.0; 8]);
+    }
+
+    #[test]
+    fn first_connected_preference_picks_the_output_device() {
+        let patterns = vec!["headphones".to_string(), "speakers".to_string()];
+        let docked = vec![
+            "Speakers (Realtek Audio)".to_string(),
+            "Headphones (WH-1000XM4)".to_string(),
+        ];
+        assert_eq!(
+            select_by_preference(&patterns, &docked),
+            Some("Headphones (WH-1000XM4)")
+        );
+
+        let unplugged = vec!["Speakers (Realtek Audio)".to_string()];
+        assert_eq!(
+            select_by_preference(&patterns, &unplugged),
+            Some("Speakers (Realtek Audio)")
+        );
+
+        let hdmi = vec!["HDMI Output".to_string()];
+        assert_eq!(select_by_preference(&patterns, &hdmi), None);
+        assert_eq!(select_by_preference(&[], &docked), None);
     }
 }
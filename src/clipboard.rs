@@ -1,12 +1,74 @@
+use crate::config::OutputMode;
 use anyhow::Result;
-use arboard::Clipboard;
+use arboard::{Clipboard, ImageData};
 use enigo::{Enigo, Key, Settings, Direction, Keyboard}; 
+use rand::Rng;
 use std::thread;
 use std::time::Duration;
+use tracing::warn;
 
 pub struct ClipboardManager {
     clipboard: Clipboard,
     enigo: Enigo,
+    // Pause between typed characters, plus up to `type_jitter` of random extra
+    type_delay: Duration,
+    type_jitter: Duration,
+    // Without a per-character delay, type this many characters at a time (0 = all at once)
+    type_chunk_chars: usize,
+    type_chunk_delay: Duration,
+    // Modifiers followed by the key that pastes, e.g. [Control, 'v']
+    paste_keys: Vec<Key>,
+    // Put back the previous clipboard contents this long after pasting, None = never
+    restore_delay: Option<Duration>,
+    // Add pasted text to the end of the clipboard text after this separator, None = replace it
+    append_separator: Option<String>,
+    // When appending, still send the paste keystroke
+    append_paste: bool,
+}
+
+/// The clipboard operations pasting relies on, so they can be stubbed in tests
+pub trait ClipboardAccess {
+    fn get_text(&mut self) -> Result<String, arboard::Error>;
+    fn set_text(&mut self, text: String) -> Result<(), arboard::Error>;
+    fn get_image(&mut self) -> Result<ImageData<'static>, arboard::Error>;
+    fn set_image(&mut self, image: ImageData<'static>) -> Result<(), arboard::Error>;
+}
+
+impl ClipboardAccess for Clipboard {
+    fn get_text(&mut self) -> Result<String, arboard::Error> {
+        Clipboard::get_text(self)
+    }
+
+    fn set_text(&mut self, text: String) -> Result<(), arboard::Error> {
+        Clipboard::set_text(self, text)
+    }
+
+    fn get_image(&mut self) -> Result<ImageData<'static>, arboard::Error> {
+        Clipboard::get_image(self)
+    }
+
+    fn set_image(&mut self, image: ImageData<'static>) -> Result<(), arboard::Error> {
+        Clipboard::set_image(self, image)
+    }
+}
+
+/// How `deliver` gets text to the focused window
+#[derive(Debug, PartialEq, Eq)]
+enum Delivery {
+    /// Through the clipboard and the paste keys, restoring the clipboard if configured
+    Paste,
+    /// As keystrokes, leaving the clipboard alone
+    Type,
+    /// As keystrokes, then left on the clipboard for pasting elsewhere (never restored)
+    TypeAndKeep,
+}
+
+/// What the clipboard held before a paste replaced it
+enum ClipboardContent {
+    Text(String),
+    Image(ImageData<'static>),
+    /// Nothing, or something that can't be read back (e.g. copied files)
+    Other,
 }
 
 impl ClipboardManager {
@@ -14,35 +76,477 @@ impl ClipboardManager {
         let clipboard = Clipboard::new().map_err(|e| anyhow::anyhow!("Failed to init clipboard: {}", e))?;
         // Enigo 0.2.x constructor takes Settings
         let enigo = Enigo::new(&Settings::default()).map_err(|e| anyhow::anyhow!("Failed to init enigo: {:?}", e))?;
-        Ok(Self { clipboard, enigo })
+        Ok(Self {
+            clipboard,
+            enigo,
+            type_delay: Duration::ZERO,
+            type_jitter: Duration::ZERO,
+            type_chunk_chars: 0,
+            type_chunk_delay: Duration::ZERO,
+            paste_keys: vec![Key::Control, Key::Unicode('v')],
+            restore_delay: None,
+            append_separator: None,
+            append_paste: true,
+        })
+    }
+
+    /// Type one character at a time with `delay` (+ random `jitter`) between them,
+    /// for remote desktops and games that drop fast synthetic input
+    pub fn set_typing_delay(&mut self, delay: Duration, jitter: Duration) {
+        self.type_delay = delay;
+        self.type_jitter = jitter;
+    }
+
+    /// Type `chunk_chars` characters at a time with `delay` between chunks (0 = all at once),
+    /// so long text doesn't flood apps that fall behind on input
+    pub fn set_typing_chunks(&mut self, chunk_chars: usize, delay: Duration) {
+        self.type_chunk_chars = chunk_chars;
+        self.type_chunk_delay = delay;
+    }
+
+    /// Keys used to paste, as parsed by `parse_key_combo`
+    pub fn set_paste_keys(&mut self, keys: Vec<Key>) {
+        self.paste_keys = keys;
+    }
+
+    /// Put back what was on the clipboard `delay` after each paste (None = leave the text)
+    /// Slow apps may read the clipboard late, so the delay must outlast their paste.
+    pub fn set_restore_clipboard(&mut self, delay: Option<Duration>) {
+        self.restore_delay = delay;
+    }
+
+    /// Collect pasted text on the clipboard, joined with `separator`, instead of replacing it
+    /// (None = replace). Without `paste` the text is only added and the keystroke is skipped.
+    pub fn set_append(&mut self, separator: Option<String>, paste: bool) {
+        self.append_separator = separator;
+        self.append_paste = paste;
+    }
+
+    /// Deliver text to the focused window using the configured mode
+    /// In `Type` mode the clipboard is left alone unless `keep_clipboard` is set, in which
+    /// case it is deliberately left holding the text (and must not be restored afterwards).
+    pub fn deliver(&mut self, text: &str, mode: OutputMode, keep_clipboard: bool) -> Result<()> {
+        match delivery(mode, keep_clipboard) {
+            Delivery::Paste => self.paste_text(text),
+            Delivery::Type => self.type_text(text),
+            Delivery::TypeAndKeep => {
+                self.type_text(text)?;
+                self.set_text(text)
+            }
+        }
+    }
+
+    /// Current clipboard text, None if it holds no text
+    pub fn get_text(&mut self) -> Option<String> {
+        self.clipboard.get_text().ok()
+    }
+
+    /// Put text on the clipboard without pasting it
+    pub fn set_text(&mut self, text: &str) -> Result<()> {
+        self.clipboard.set_text(text.to_owned()).map_err(|e| anyhow::anyhow!("Failed to set clipboard: {}", e))
+    }
+
+    /// Type text as keystrokes, for apps where pasting doesn't work
+    /// Characters enigo can't type are logged and skipped rather than dropped silently.
+    pub fn type_text(&mut self, text: &str) -> Result<()> {
+        if self.type_delay.is_zero() && self.type_jitter.is_zero() {
+            let chunks = text_chunks(text, self.type_chunk_chars);
+            for (i, chunk) in chunks.into_iter().enumerate() {
+                if i > 0 {
+                    thread::sleep(self.type_chunk_delay);
+                }
+                if let Err(e) = self.enigo.text(chunk) {
+                    warn!(
+                        "Could not type '{}' ({:?}){}",
+                        chunk,
+                        e,
+                        describe_non_ascii(chunk)
+                    );
+                }
+            }
+            return Ok(());
+        }
+
+        let mut buf = [0u8; 4];
+        for (i, c) in text.chars().enumerate() {
+            if i > 0 {
+                thread::sleep(keystroke_delay(self.type_delay, self.type_jitter));
+            }
+            // Make sure no modifier is left held from the previous character
+            for modifier in [Key::Shift, Key::Control, Key::Alt] {
+                self.enigo.key(modifier, Direction::Release).map_err(|e| anyhow::anyhow!("Enigo error: {:?}", e))?;
+            }
+            if let Err(e) = self.enigo.text(c.encode_utf8(&mut buf)) {
+                warn!("Could not type '{}' (U+{:04X}): {:?}", c, c as u32, e);
+            }
+        }
+        Ok(())
     }
 
     pub fn paste_text(&mut self, text: &str) -> Result<()> {
+        if let Some(separator) = self.append_separator.clone() {
+            return self.append_text(text, &separator);
+        }
+        let original = self
+            .restore_delay
+            .map(|delay| (content(&mut self.clipboard), delay));
+
         // 1. Set text to clipboard
-        self.clipboard.set_text(text.to_owned()).map_err(|e| anyhow::anyhow!("Failed to set clipboard: {}", e))?;
-        
-        // 2. Simulate CTRL+V
-        thread::sleep(Duration::from_millis(100));
+        self.set_text(text)?;
         
-        // Press Control
-        self.enigo.key(Key::Control, Direction::Press).map_err(|e| anyhow::anyhow!("Enigo error: {:?}", e))?;
-        // Click V (Unicode) - Note: Key::Layout('v') in older vers, 0.2 uses different variants usually.
-        // Assuming Key::Unicode('v') or Key::V exists. In modern Enigo 0.2, standard keys are often enum variants like Key::V.
-        // Let's try Key::V first, if not available we'll try Unicode.
-        // Actually, for 'v' it's often Key::V or use text("v") but text sends strings.
-        // Let's try Key::Unicode('v') as it is common for chars.
-        // UPDATE: Error log said Key::Layout not found.
-        // Common 0.2 API: Key::V might exist.
-        // Let's use `text("v")`? No, that types V. We need to hold CTRL.
-        // Let's check common keys. Key::Control exists. Key::V might not.
-        // But Key::Unicode('v') usually exists.
-        
-        // However, to be safe against API changes, let's use what we know exists or try Key::Unicode.
-        self.enigo.key(Key::Unicode('v'), Direction::Click).map_err(|e| anyhow::anyhow!("Enigo error: {:?}", e))?;
-        
-        // Release Control
-        self.enigo.key(Key::Control, Direction::Release).map_err(|e| anyhow::anyhow!("Enigo error: {:?}", e))?;
+        // 2. Simulate the paste keystroke (Ctrl+V unless configured otherwise)
+        self.send_paste_keys()?;
 
+        // 3. Put the previous contents back once the target app has read the clipboard
+        if let Some((original, delay)) = original {
+            restore_later(original, text.to_owned(), delay);
+        }
         Ok(())
     }
+
+    /// Add text to the end of the clipboard text and paste the result if configured
+    /// An empty clipboard or one holding no text (e.g. an image) is started over with `text`.
+    /// Nothing is restored afterwards, since the clipboard is meant to collect the text.
+    fn append_text(&mut self, text: &str, separator: &str) -> Result<()> {
+        let combined = match self.get_text() {
+            Some(current) if !current.is_empty() => format!("{}{}{}", current, separator, text),
+            _ => text.to_owned(),
+        };
+        self.set_text(&combined)?;
+        if self.append_paste {
+            self.send_paste_keys()?;
+        }
+        Ok(())
+    }
+
+    /// Simulate the paste keystroke after giving the clipboard a moment to settle
+    fn send_paste_keys(&mut self) -> Result<()> {
+        thread::sleep(Duration::from_millis(100));
+        for (key, direction) in key_sequence(&self.paste_keys) {
+            self.enigo.key(key, direction).map_err(|e| anyhow::anyhow!("Enigo error: {:?}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// OUTPUT_MODE and KEEP_CLIPBOARD combined; keeping only matters when typing
+fn delivery(mode: OutputMode, keep_clipboard: bool) -> Delivery {
+    match (mode, keep_clipboard) {
+        (OutputMode::Paste, _) => Delivery::Paste,
+        (OutputMode::Type, false) => Delivery::Type,
+        (OutputMode::Type, true) => Delivery::TypeAndKeep,
+    }
+}
+
+/// What `clipboard` holds now, as far as it can be put back
+fn content(clipboard: &mut impl ClipboardAccess) -> ClipboardContent {
+    if let Ok(text) = clipboard.get_text() {
+        return ClipboardContent::Text(text);
+    }
+    match clipboard.get_image() {
+        Ok(image) => ClipboardContent::Image(image),
+        Err(_) => ClipboardContent::Other,
+    }
+}
+
+/// Text just copied from the selection, "" when nothing (or no text) was copied
+/// Other errors are real clipboard problems, e.g. another app holding it open.
+pub fn selected_text(clipboard: &mut impl ClipboardAccess) -> Result<String> {
+    match clipboard.get_text() {
+        Ok(text) => Ok(text),
+        Err(arboard::Error::ContentNotAvailable) => Ok(String::new()),
+        Err(e) => Err(anyhow::anyhow!("Failed to read the clipboard: {}", e)),
+    }
+}
+
+/// Put `original` back on the clipboard after `delay`, on another thread (see `restore`)
+fn restore_later(original: ClipboardContent, pasted: String, delay: Duration) {
+    if matches!(original, ClipboardContent::Other) {
+        return;
+    }
+    thread::spawn(move || {
+        thread::sleep(delay);
+        let mut clipboard = match Clipboard::new() {
+            Ok(clipboard) => clipboard,
+            Err(e) => {
+                warn!("Failed to restore the clipboard: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = restore(&mut clipboard, original, &pasted) {
+            warn!("Failed to restore the clipboard: {}", e);
+        }
+    });
+}
+
+/// Put `original` back on `clipboard`
+/// Skipped if the clipboard no longer holds `pasted` (something else was copied meanwhile)
+/// or if the original can't be put back, so the clipboard is never emptied.
+fn restore(
+    clipboard: &mut impl ClipboardAccess,
+    original: ClipboardContent,
+    pasted: &str,
+) -> Result<(), arboard::Error> {
+    if clipboard.get_text().ok().as_deref() != Some(pasted) {
+        return Ok(());
+    }
+    match original {
+        ClipboardContent::Text(text) => clipboard.set_text(text),
+        ClipboardContent::Image(image) => clipboard.set_image(image),
+        ClipboardContent::Other => Ok(()),
+    }
+}
+
+/// `text` split into pieces of at most `chars` characters (the whole text for 0)
+fn text_chunks(text: &str, chars: usize) -> Vec<&str> {
+    if chars == 0 {
+        return vec![text];
+    }
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    for (count, (index, _)) in text.char_indices().enumerate() {
+        if count > 0 && count % chars == 0 {
+            chunks.push(&text[start..index]);
+            start = index;
+        }
+    }
+    chunks.push(&text[start..]);
+    chunks
+}
+
+/// ", which has non-ASCII characters: 'é' (U+00E9), ..." for a typing warning, or ""
+/// Those are the characters most likely to have no key mapping.
+fn describe_non_ascii(text: &str) -> String {
+    let mut seen: Vec<char> = Vec::new();
+    for c in text.chars().filter(|c| !c.is_ascii()) {
+        if !seen.contains(&c) {
+            seen.push(c);
+        }
+    }
+    if seen.is_empty() {
+        return String::new();
+    }
+    let listed: Vec<String> = seen
+        .iter()
+        .map(|c| format!("'{}' (U+{:04X})", c, *c as u32))
+        .collect();
+    format!(", which has non-ASCII characters: {}", listed.join(", "))
+}
+
+/// Parse a combo like "ctrl+v", "shift+insert" or "ctrl+shift+v" into keys,
+/// modifiers first and the key to click last
+pub fn parse_key_combo(combo: &str) -> Result<Vec<Key>> {
+    let keys = combo
+        .split('+')
+        .map(|name| parse_key(name.trim()))
+        .collect::<Result<Vec<Key>>>()?;
+    if keys.is_empty() {
+        return Err(anyhow::anyhow!("Empty key combo"));
+    }
+    Ok(keys)
+}
+
+fn parse_key(name: &str) -> Result<Key> {
+    let key = match name.to_lowercase().as_str() {
+        "ctrl" | "control" => Key::Control,
+        "shift" => Key::Shift,
+        "alt" => Key::Alt,
+        "meta" | "win" | "cmd" => Key::Meta,
+        #[cfg(not(target_os = "macos"))]
+        "insert" | "ins" => Key::Insert,
+        other => {
+            let mut chars = other.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Unicode(c),
+                _ => return Err(anyhow::anyhow!("Unknown key '{}'", name)),
+            }
+        }
+    };
+    Ok(key)
+}
+
+/// Press the modifiers in order, click the last key, then release the modifiers in reverse
+fn key_sequence(keys: &[Key]) -> Vec<(Key, Direction)> {
+    let Some((&key, modifiers)) = keys.split_last() else {
+        return Vec::new();
+    };
+    let mut sequence: Vec<(Key, Direction)> = modifiers.iter().map(|&m| (m, Direction::Press)).collect();
+    sequence.push((key, Direction::Click));
+    sequence.extend(modifiers.iter().rev().map(|&m| (m, Direction::Release)));
+    sequence
+}
+
+/// Pause before the next typed character: `delay` plus a random share of `jitter`
+fn keystroke_delay(delay: Duration, jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        return delay;
+    }
+    delay + rand::thread_rng().gen_range(Duration::ZERO..=jitter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    /// In-memory clipboard holding text, an image or nothing
+    #[derive(Default)]
+    struct StubClipboard {
+        text: Option<String>,
+        image: Option<ImageData<'static>>,
+    }
+
+    impl ClipboardAccess for StubClipboard {
+        fn get_text(&mut self) -> Result<String, arboard::Error> {
+            self.text.clone().ok_or(arboard::Error::ContentNotAvailable)
+        }
+
+        fn set_text(&mut self, text: String) -> Result<(), arboard::Error> {
+            self.text = Some(text);
+            self.image = None;
+            Ok(())
+        }
+
+        fn get_image(&mut self) -> Result<ImageData<'static>, arboard::Error> {
+            self.image
+                .clone()
+                .ok_or(arboard::Error::ContentNotAvailable)
+        }
+
+        fn set_image(&mut self, image: ImageData<'static>) -> Result<(), arboard::Error> {
+            self.image = Some(image);
+            self.text = None;
+            Ok(())
+        }
+    }
+
+    fn image() -> ImageData<'static> {
+        ImageData {
+            width: 1,
+            height: 1,
+            bytes: Cow::Owned(vec![255, 0, 0, 255]),
+        }
+    }
+
+    #[test]
+    fn typed_text_is_kept_on_the_clipboard_only_when_asked() {
+        assert_eq!(delivery(OutputMode::Type, false), Delivery::Type);
+        assert_eq!(delivery(OutputMode::Type, true), Delivery::TypeAndKeep);
+        assert_eq!(delivery(OutputMode::Paste, false), Delivery::Paste);
+        assert_eq!(delivery(OutputMode::Paste, true), Delivery::Paste);
+    }
+
+    #[test]
+    fn paste_combo_presses_modifiers_around_the_key() {
+        let keys = parse_key_combo("Ctrl + Shift + V").unwrap();
+        assert_eq!(keys, vec![Key::Control, Key::Shift, Key::Unicode('v')]);
+        assert_eq!(
+            key_sequence(&keys),
+            vec![
+                (Key::Control, Direction::Press),
+                (Key::Shift, Direction::Press),
+                (Key::Unicode('v'), Direction::Click),
+                (Key::Shift, Direction::Release),
+                (Key::Control, Direction::Release),
+            ]
+        );
+        #[cfg(not(target_os = "macos"))]
+        assert_eq!(
+            parse_key_combo("shift+insert").unwrap(),
+            vec![Key::Shift, Key::Insert]
+        );
+        assert!(parse_key_combo("ctrl+paste").is_err());
+        assert!(parse_key_combo("ctrl+").is_err());
+    }
+
+    #[test]
+    fn keystroke_delay_stays_within_the_jitter() {
+        let delay = Duration::from_millis(15);
+        let jitter = Duration::from_millis(10);
+        for _ in 0..100 {
+            let pause = keystroke_delay(delay, jitter);
+            assert!(pause >= delay && pause <= delay + jitter, "{:?}", pause);
+        }
+        assert_eq!(keystroke_delay(delay, Duration::ZERO), delay);
+    }
+
+    #[test]
+    fn copied_text_is_restored_after_the_paste() {
+        let mut clipboard = StubClipboard {
+            text: Some("copied".to_string()),
+            ..Default::default()
+        };
+        let original = content(&mut clipboard);
+        clipboard.set_text("dictated".to_string()).unwrap();
+
+        restore(&mut clipboard, original, "dictated").unwrap();
+        assert_eq!(clipboard.text.as_deref(), Some("copied"));
+    }
+
+    #[test]
+    fn copied_image_is_restored_instead_of_lost() {
+        let mut clipboard = StubClipboard {
+            image: Some(image()),
+            ..Default::default()
+        };
+        let original = content(&mut clipboard);
+        assert!(matches!(original, ClipboardContent::Image(_)));
+        clipboard.set_text("dictated".to_string()).unwrap();
+
+        restore(&mut clipboard, original, "dictated").unwrap();
+        assert_eq!(clipboard.text, None);
+        assert_eq!(clipboard.image.unwrap().bytes, image().bytes);
+    }
+
+    #[test]
+    fn clipboard_is_left_alone_when_something_else_was_copied() {
+        let mut clipboard = StubClipboard {
+            text: Some("copied".to_string()),
+            ..Default::default()
+        };
+        let original = content(&mut clipboard);
+        clipboard.set_text("copied meanwhile".to_string()).unwrap();
+
+        restore(&mut clipboard, original, "dictated").unwrap();
+        assert_eq!(clipboard.text.as_deref(), Some("copied meanwhile"));
+    }
+
+    #[test]
+    fn empty_clipboard_is_not_restored() {
+        let mut clipboard = StubClipboard::default();
+        assert!(matches!(content(&mut clipboard), ClipboardContent::Other));
+    }
+
+    /// Clipboard that another app is holding open
+    struct BusyClipboard;
+
+    impl ClipboardAccess for BusyClipboard {
+        fn get_text(&mut self) -> Result<String, arboard::Error> {
+            Err(arboard::Error::ClipboardOccupied)
+        }
+
+        fn set_text(&mut self, _: String) -> Result<(), arboard::Error> {
+            Err(arboard::Error::ClipboardOccupied)
+        }
+
+        fn get_image(&mut self) -> Result<ImageData<'static>, arboard::Error> {
+            Err(arboard::Error::ClipboardOccupied)
+        }
+
+        fn set_image(&mut self, _: ImageData<'static>) -> Result<(), arboard::Error> {
+            Err(arboard::Error::ClipboardOccupied)
+        }
+    }
+
+    #[test]
+    fn empty_selection_is_told_apart_from_a_clipboard_error() {
+        let mut copied = StubClipboard {
+            text: Some("selected".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(selected_text(&mut copied).unwrap(), "selected");
+        assert_eq!(selected_text(&mut StubClipboard::default()).unwrap(), "");
+        let error = selected_text(&mut BusyClipboard).unwrap_err().to_string();
+        assert!(error.starts_with("Failed to read the clipboard"));
+    }
 }
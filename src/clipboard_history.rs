@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+
+/// Ring of the most recent clipboard texts, newest first
+pub struct ClipboardHistory {
+    capacity: usize,
+    entries: VecDeque<String>,
+}
+
+impl ClipboardHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Remember `text` as the newest entry, dropping the oldest when full
+    /// Empty text and repeats of the newest entry are ignored.
+    pub fn push(&mut self, text: &str) {
+        if self.capacity == 0 || text.trim().is_empty() {
+            return;
+        }
+        if self.entries.front().is_some_and(|newest| newest == text) {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_back();
+        }
+        self.entries.push_front(text.to_string());
+    }
+
+    /// Entry `copies_ago` copies back (0 = the current clipboard)
+    pub fn get(&self, copies_ago: usize) -> Option<&str> {
+        self.entries.get(copies_ago).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_retrieved_by_copies_ago() {
+        let mut history = ClipboardHistory::new(3);
+        for text in ["one", "two", "two", "  ", "three", "four"] {
+            history.push(text);
+        }
+        assert_eq!(history.get(0), Some("four"));
+        assert_eq!(history.get(1), Some("three"));
+        assert_eq!(history.get(2), Some("two"));
+        // "one" was dropped to make room
+        assert_eq!(history.get(3), None);
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut history = ClipboardHistory::new(0);
+        history.push("one");
+        assert_eq!(history.get(0), None);
+    }
+}
@@ -0,0 +1,285 @@
+use std::cmp::Reverse;
+
+/// Spoken symbols for code dictation, as (phrase, replacement)
+/// Replacements are inserted without spaces around them unless they contain their own
+/// (binary operators do), so "foo open paren bar close paren" becomes "foo(bar)".
+const DEFAULT_SYMBOLS: &[(&str, &str)] = &[
+    ("open paren", "("),
+    ("close paren", ")"),
+    ("open bracket", "["),
+    ("close bracket", "]"),
+    ("open brace", "{"),
+    ("close brace", "}"),
+    ("open curly", "{"),
+    ("close curly", "}"),
+    ("less than", " < "),
+    ("greater than", " > "),
+    ("open angle", "<"),
+    ("close angle", ">"),
+    ("semicolon", ";"),
+    ("double colon", "::"),
+    ("colon", ":"),
+    ("comma", ", "),
+    ("dot", "."),
+    ("double equals", " == "),
+    ("triple equals", " === "),
+    ("not equals", " != "),
+    ("plus equals", " += "),
+    ("minus equals", " -= "),
+    ("equals", " = "),
+    ("plus plus", "++"),
+    ("plus", " + "),
+    ("minus", " - "),
+    ("times", " * "),
+    ("star", "*"),
+    ("slash", "/"),
+    ("backslash", "\\"),
+    ("fat arrow", " => "),
+    ("arrow", " -> "),
+    ("and and", " && "),
+    ("or or", " || "),
+    ("ampersand", "&"),
+    ("pipe", "|"),
+    ("bang", "!"),
+    ("question mark", "?"),
+    ("underscore", "_"),
+    ("dash", "-"),
+    ("double quote", "\""),
+    ("single quote", "'"),
+    ("quote", "\""),
+    ("backtick", "`"),
+    ("hash", "#"),
+    ("at sign", "@"),
+    ("dollar", "$"),
+    ("percent", "%"),
+    ("caret", "^"),
+    ("tilde", "~"),
+    ("new line", "\n"),
+    ("tab", "\t"),
+];
+
+/// Identifier styles that can be spoken before a run of words
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Casing {
+    /// fooBar
+    Camel,
+    /// FooBar
+    Pascal,
+    /// foo_bar
+    Snake,
+    /// foo-bar
+    Kebab,
+    /// FOO_BAR
+    Constant,
+}
+
+const CASINGS: &[(&str, Casing)] = &[
+    ("camel case", Casing::Camel),
+    ("pascal case", Casing::Pascal),
+    ("snake case", Casing::Snake),
+    ("kebab case", Casing::Kebab),
+    ("constant case", Casing::Constant),
+];
+
+enum Piece {
+    Word(String),
+    Symbol(String),
+}
+
+/// Turn a code dictation into code: spoken symbols become symbols and casing commands
+/// ("camel case foo bar") join the following words into one identifier
+///
+/// `custom` entries (phrase, replacement) are tried before the defaults. Longer phrases
+/// always win over shorter ones, so "double equals" isn't read as "double" "=".
+/// A casing command takes every following word up to the next symbol or command.
+/// Words are lowercased and lose the punctuation whisper adds, since in code mode every
+/// symbol is meant to be spoken.
+pub fn apply(text: &str, custom: &[(String, String)]) -> String {
+    let mut symbols: Vec<(Vec<&str>, &str)> = custom
+        .iter()
+        .map(|(phrase, replacement)| (phrase.as_str(), replacement.as_str()))
+        .chain(DEFAULT_SYMBOLS.iter().copied())
+        .map(|(phrase, replacement)| (phrase.split_whitespace().collect(), replacement))
+        .collect();
+    // Stable sort: among phrases of the same length, custom ones stay first
+    symbols.sort_by_key(|symbol| Reverse(symbol.0.len()));
+
+    let words: Vec<String> = text.split_whitespace().map(normalize).collect();
+    let mut pieces = Vec::new();
+    let mut i = 0;
+
+    while i < words.len() {
+        if let Some((length, casing)) = match_casing(&words[i..]) {
+            i += length;
+            let start = i;
+            while i < words.len()
+                && match_casing(&words[i..]).is_none()
+                && match_symbol(&symbols, &words[i..]).is_none()
+            {
+                i += 1;
+            }
+            if i > start {
+                pieces.push(Piece::Word(apply_casing(&words[start..i], casing)));
+            }
+        } else if let Some((length, replacement)) = match_symbol(&symbols, &words[i..]) {
+            pieces.push(Piece::Symbol(replacement.to_string()));
+            i += length;
+        } else {
+            if !words[i].is_empty() {
+                pieces.push(Piece::Word(words[i].clone()));
+            }
+            i += 1;
+        }
+    }
+
+    join(&pieces)
+}
+
+/// Lowercase a word and strip the punctuation whisper adds around it
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric() && c != '_')
+        .to_lowercase()
+}
+
+fn match_casing(words: &[String]) -> Option<(usize, Casing)> {
+    CASINGS.iter().find_map(|(phrase, casing)| {
+        let length = phrase_matches(phrase.split_whitespace(), words)?;
+        Some((length, *casing))
+    })
+}
+
+fn match_symbol<'a>(
+    symbols: &[(Vec<&str>, &'a str)],
+    words: &[String],
+) -> Option<(usize, &'a str)> {
+    symbols.iter().find_map(|(phrase, replacement)| {
+        let length = phrase_matches(phrase.iter().copied(), words)?;
+        Some((length, *replacement))
+    })
+}
+
+/// Number of words matched if `words` starts with `phrase`
+fn phrase_matches<'p>(phrase: impl Iterator<Item = &'p str>, words: &[String]) -> Option<usize> {
+    let mut length = 0;
+    for expected in phrase {
+        if words.get(length)? != expected {
+            return None;
+        }
+        length += 1;
+    }
+    (length > 0).then_some(length)
+}
+
+fn apply_casing(words: &[String], casing: Casing) -> String {
+    match casing {
+        Casing::Camel => words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                if i == 0 {
+                    word.clone()
+                } else {
+                    capitalize(word)
+                }
+            })
+            .collect(),
+        Casing::Pascal => words.iter().map(|word| capitalize(word)).collect(),
+        Casing::Snake => words.join("_"),
+        Casing::Kebab => words.join("-"),
+        Casing::Constant => words.join("_").to_uppercase(),
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Words are separated by one space; symbols bring their own spacing
+fn join(pieces: &[Piece]) -> String {
+    let mut result = String::new();
+    let mut previous_was_word = false;
+    for piece in pieces {
+        match piece {
+            Piece::Word(word) => {
+                if previous_was_word {
+                    result.push(' ');
+                }
+                result.push_str(word);
+                previous_was_word = true;
+            }
+            Piece::Symbol(symbol) => {
+                // Avoid doubled spaces when spaced operators meet
+                if symbol.starts_with(' ') && result.ends_with(' ') {
+                    result.push_str(&symbol[1..]);
+                } else {
+                    result.push_str(symbol);
+                }
+                previous_was_word = false;
+            }
+        }
+    }
+    result
+        .split('\n')
+        .map(|line| line.trim_matches(' '))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spoken_symbols_become_code() {
+        assert_eq!(
+            apply("foo open paren bar close paren semicolon", &[]),
+            "foo(bar);"
+        );
+        assert_eq!(apply("Count plus equals one.", &[]), "count += one");
+        assert_eq!(
+            apply("x less than y new line return x semicolon", &[]),
+            "x < y\nreturn x;"
+        );
+    }
+
+    #[test]
+    fn multi_word_commands_win_over_single_words() {
+        assert_eq!(
+            apply("If x double equals y, and and z not equals none.", &[]),
+            "if x == y && z != none"
+        );
+    }
+
+    #[test]
+    fn casing_commands_join_the_following_words() {
+        assert_eq!(
+            apply("camel case user name equals x open paren close paren", &[]),
+            "userName = x()"
+        );
+        assert_eq!(
+            apply("pascal case http client double colon new", &[]),
+            "HttpClient::new"
+        );
+        assert_eq!(
+            apply("snake case max retries comma kebab case line height", &[]),
+            "max_retries, line-height"
+        );
+        assert_eq!(apply("constant case default port", &[]), "DEFAULT_PORT");
+        assert_eq!(apply("camel case", &[]), "");
+    }
+
+    #[test]
+    fn custom_symbols_are_tried_before_the_defaults() {
+        let custom = vec![
+            ("arrow".to_string(), " => ".to_string()),
+            ("left shift".to_string(), " << ".to_string()),
+        ];
+        assert_eq!(apply("x arrow y", &[]), "x -> y");
+        assert_eq!(apply("x arrow y", &custom), "x => y");
+        assert_eq!(apply("a left shift two", &custom), "a << two");
+    }
+}
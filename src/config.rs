@@ -0,0 +1,1061 @@
+use crate::audio::{Normalize, Preprocessing, SilenceTrim};
+use crate::dataset::RetentionPolicy;
+use crate::hotkeys::Hotkeys;
+use crate::logging::LogRotation;
+use crate::narrate;
+use crate::toml_config::{self, TOML_CONFIG_FILE};
+use crate::transcribe::{Prompts, TranscriberBackend};
+use anyhow::{anyhow, Context, Result};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::warn;
+
+/// Name of the shared configuration file in the working directory
+pub const CONFIG_FILE: &str = "tts_config.txt";
+
+/// Where REMEMBER_PROFILE keeps the name of the last profile in use
+const LAST_PROFILE_FILE: &str = "last_profile.txt";
+
+/// Where REMEMBER_MICROPHONE keeps the name of the microphone picked at the prompt
+const LAST_MICROPHONE_FILE: &str = "last_microphone.txt";
+
+/// The config file in use: `config.toml` if it exists, otherwise `tts_config.txt`
+pub fn config_file() -> &'static str {
+    if Path::new(TOML_CONFIG_FILE).exists() {
+        TOML_CONFIG_FILE
+    } else {
+        CONFIG_FILE
+    }
+}
+
+/// Read `KEY=VALUE` pairs from the config file, in file order
+/// `config.toml` is preferred and must be valid; without it `tts_config.txt` is read.
+/// Blank lines and lines starting with '#' are skipped; a missing file yields no pairs
+pub fn read_config_file() -> Result<Vec<(String, String)>> {
+    let toml_path = env::current_dir()?.join(TOML_CONFIG_FILE);
+    if toml_path.exists() {
+        return toml_config::read_pairs(&toml_path);
+    }
+
+    let config_path = env::current_dir()?.join(CONFIG_FILE);
+    if !config_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = std::fs::read_to_string(&config_path)?;
+    let mut pairs = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            pairs.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    Ok(pairs)
+}
+
+/// Check a setting the way loading the config would apply it, for `config.toml`
+/// Returns false if no part of the tool reads the key; an invalid value is an error.
+pub fn check_setting(key: &str, value: &str) -> Result<bool> {
+    if let Some(action) = key.strip_prefix("HOTKEY.") {
+        Hotkeys::default().set(action, value)?;
+        return Ok(true);
+    }
+    if AppConfig::default().try_apply(key, value)? {
+        return Ok(true);
+    }
+    narrate::check_setting(key, value)
+}
+
+/// What to do when whisper succeeds but hears no speech
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoSpeechAction {
+    /// Print a short "no speech detected" notice
+    Report,
+    /// Do nothing, as if the recording never happened
+    Ignore,
+}
+
+/// What to do when F9 is pressed while a transcription is still being processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusyPolicy {
+    /// Ignore the press with a "busy" message
+    Reject,
+    /// Start recording as soon as processing finishes, if F9 is still held
+    Queue,
+}
+
+/// How F9 controls recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordMode {
+    /// Record while F9 is held
+    Hold,
+    /// First press starts recording, the next press stops it
+    Toggle,
+}
+
+/// How a transcription is put into the focused window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Set the clipboard and send Ctrl+V
+    Paste,
+    /// Type the text as keystrokes, leaving the clipboard untouched
+    Type,
+}
+
+/// Profile that plain (unprefixed) options belong to
+const DEFAULT_PROFILE: &str = "default";
+
+/// Named set of text post-processing options
+///
+/// Options given as plain keys (e.g. `AUTO_PUNCTUATE=true`) belong to the "default"
+/// profile; other profiles are declared with `PROFILE.<name>.<OPTION>=value` and
+/// picked with `PROFILE=<name>`.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    /// Capitalize sentences and add a final period when missing
+    pub auto_punctuate: bool,
+    /// Turn spoken words like "comma" / "nova linha" into punctuation and line breaks
+    pub spoken_commands: bool,
+    /// Word(s) that make the next spoken command count anywhere, not only at the end
+    /// (e.g. "command" for "hello command comma world"), None = commands only at the end
+    pub command_prefix: Option<String>,
+    /// Language of this profile's dictation (e.g. "en", "pt"), None = detect from the text
+    pub language: Option<String>,
+    /// Treat dictation as code: spoken symbols and casing commands, no prose clean-ups
+    pub code_mode: bool,
+    /// Extra code-mode symbols as (lowercase phrase, replacement), from CODE_SYMBOL.<phrase>
+    pub code_symbols: Vec<(String, String)>,
+}
+
+impl Profile {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            auto_punctuate: false,
+            spoken_commands: false,
+            command_prefix: None,
+            language: None,
+            code_mode: false,
+            code_symbols: Vec::new(),
+        }
+    }
+
+    /// Apply a profile option, returning false if the key isn't a profile option
+    /// An invalid value is an error and leaves the option unchanged.
+    fn apply(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "AUTO_PUNCTUATE" => set_bool(&mut self.auto_punctuate, key, value)?,
+            "SPOKEN_COMMANDS" => set_bool(&mut self.spoken_commands, key, value)?,
+            "COMMAND_PREFIX" => {
+                self.command_prefix = Some(value.trim().to_lowercase()).filter(|p| !p.is_empty())
+            }
+            "CODE_MODE" => set_bool(&mut self.code_mode, key, value)?,
+            "LANGUAGE" => {
+                self.language = match value.to_lowercase().as_str() {
+                    "" | "auto" => None,
+                    code => Some(code.to_string()),
+                }
+            }
+            _ => match key.strip_prefix("CODE_SYMBOL.") {
+                // Underscores stand for spaces: CODE_SYMBOL.fat_arrow==>
+                Some(phrase) if !phrase.is_empty() => {
+                    let phrase = phrase.replace('_', " ").to_lowercase();
+                    self.code_symbols.retain(|(existing, _)| *existing != phrase);
+                    self.code_symbols.push((phrase, value.to_string()));
+                }
+                _ => return Ok(false),
+            },
+        }
+        Ok(true)
+    }
+}
+
+/// Settings for recording, transcription and the hotkey loop
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub whisper_model: PathBuf,
+    /// Run whisper-cli per recording or keep the model loaded in-process
+    pub whisper_backend: TranscriberBackend,
+    /// Let the library backend use the GPU when built with one (WHISPER_GPU=auto), false = CPU only
+    pub whisper_gpu: bool,
+    /// Memory-map the model file for the library backend instead of having whisper read it
+    pub whisper_mmap: bool,
+    /// Lighter model used while running on battery, None = always `whisper_model`
+    pub whisper_model_battery: Option<PathBuf>,
+    /// Other models to switch to with the next-model key (WHISPER_MODEL.<name>)
+    pub whisper_models: Vec<(String, PathBuf)>,
+    /// Local directory holding a copy of the model for faster loads (None = use in place)
+    pub model_cache_dir: Option<PathBuf>,
+    /// Local folder for temporary audio instead of the system temp folder (TEMP_DIR)
+    pub temp_dir: Option<PathBuf>,
+    pub on_no_speech: NoSpeechAction,
+    pub on_busy: BusyPolicy,
+    pub record_mode: RecordMode,
+    /// In toggle mode, stop recording after this much silence, 0 = never
+    pub toggle_silence_stop_ms: u64,
+    /// In toggle mode, ignore a second press this soon after the first (accidental double tap)
+    pub toggle_debounce_ms: u64,
+    /// Input RMS (0-1) below which the microphone counts as silent
+    pub silence_threshold: f32,
+    /// Minimum time between two F10 presses for the second to count
+    pub speak_debounce_ms: u64,
+    /// A second F10 press within this window skips to the next queued utterance, 0 = off
+    pub double_tap_ms: u64,
+    /// Heavy operations (whisper, Piper) allowed to run at once, 0 = no limit
+    pub max_heavy_ops: usize,
+    /// Ignore hotkeys for this long after pasting/typing, so our own keystrokes can't trigger them
+    pub hotkey_guard_ms: u64,
+    /// Cut leading/trailing silence before transcription
+    pub trim_silence: bool,
+    /// Shortest clip passed to whisper; shorter ones are padded with silence
+    pub min_clip_ms: u64,
+    /// Fade-in at the start of each recording against the opening pop, 0 = off
+    pub start_fade_ms: u64,
+    /// Recordings longer than this (seconds) are transcribed in chunks, 0 = never
+    pub chunk_after_s: u64,
+    /// Longest chunk (seconds) for chunked transcription
+    pub chunk_length_s: u64,
+    /// Spoken phrase that ends a dictation and is removed from its end (END_COMMAND)
+    pub end_command: Option<String>,
+    /// Reject transcriptions faster than this many words per second as hallucinations, 0 = off
+    pub max_words_per_second: f32,
+    /// RMS analysis window used to find speech boundaries
+    pub trim_window_ms: u64,
+    /// RMS (0-1) a window needs to count as speech
+    pub trim_open_threshold: f32,
+    /// RMS (0-1) above which audio next to speech is kept
+    pub trim_close_threshold: f32,
+    /// Attenuate noise/music-like frames before transcription
+    pub music_gate: bool,
+    /// Spectral flatness (0-1) above which a frame is attenuated
+    pub music_gate_flatness: f32,
+    /// Gain applied to attenuated frames
+    pub music_gate_gain: f32,
+    /// Level normalization before transcription, None = off
+    pub normalize: Option<Normalize>,
+    /// Target peak level in dBFS for `Normalize::Peak`
+    pub normalize_peak_dbfs: f32,
+    /// Target RMS level in dBFS for `Normalize::Rms`
+    pub normalize_rms_dbfs: f32,
+    /// Microphone to use without asking: "default" or a device name (None = prompt)
+    pub device: Option<String>,
+    /// Wait this long after selecting the microphone before first use
+    pub audio_start_delay_ms: u64,
+    /// Extra attempts to open the input stream when recording fails to start
+    pub audio_start_retries: u32,
+    /// Delay before the first retry, doubled for each further retry
+    pub audio_start_backoff_ms: u64,
+    /// Refuse to write temp audio when less than this many MB are free, 0 = don't check
+    pub min_free_disk_mb: u64,
+    pub output_mode: OutputMode,
+    /// Keystroke that pastes, e.g. "ctrl+v", "shift+insert", "ctrl+shift+v"
+    pub paste_keys: String,
+    /// In type mode, also leave the text on the clipboard for pasting elsewhere
+    pub keep_clipboard: bool,
+    /// In paste mode, put back what was on the clipboard before the paste
+    pub restore_clipboard: bool,
+    /// How long after pasting the clipboard is put back
+    pub restore_clipboard_delay_ms: u64,
+    /// In paste mode, add the text to the clipboard text after this separator, None = replace it
+    pub append_clipboard: Option<String>,
+    /// When appending, still send the paste keystroke
+    pub append_clipboard_paste: bool,
+    /// Continue the previous paste (with a space) if a dictation follows within this, 0 = off
+    pub merge_window_ms: u64,
+    /// In type mode, pause between characters (0 = type the whole text at once)
+    pub type_char_delay_ms: u64,
+    /// Random extra pause (0 up to this) added to each character's delay
+    pub type_jitter_ms: u64,
+    /// In type mode without a character delay, type this many characters at a time, 0 = all
+    pub type_chunk_chars: usize,
+    /// Pause between those chunks
+    pub type_chunk_delay_ms: u64,
+    /// Rebuild the audio stream after this many stream errors within the window, 0 = never
+    pub audio_error_limit: u32,
+    pub audio_error_window_ms: u64,
+    /// Show transcriptions in the console and wait for F7 (paste) / F8 (skip) / F9 (re-record)
+    pub preview: bool,
+    /// Number of recent clipboard texts kept for Ctrl+Shift+1-9 read-back, 0 = disabled
+    pub clipboard_history: usize,
+    /// Read each transcription aloud with Piper (it is still pasted)
+    pub speak_transcription: bool,
+    /// Process names (e.g. "Teams.exe") that mute TTS while in the foreground (Windows)
+    pub mute_tts_apps: Vec<String>,
+    /// Process names (e.g. "KeePassXC.exe") that dictation is never pasted into (Windows)
+    pub block_dictation_apps: Vec<String>,
+    /// Refocus the window that was active when F9 was pressed before pasting
+    pub restore_focus: bool,
+    /// Directory to save each dictation's audio and text into, None = disabled
+    pub dataset_dir: Option<PathBuf>,
+    /// Also save skipped and no-speech dictations to the dataset
+    pub dataset_include_rejected: bool,
+    /// Delete dataset entries older than this many days, 0 = keep forever
+    pub dataset_max_age_days: u64,
+    /// Keep at most this many dataset entries, 0 = no limit
+    pub dataset_max_entries: usize,
+    /// Run from a tray icon with the console hidden (Windows)
+    pub tray: bool,
+    /// Address to send JSON events (e.g. mic level) to over UDP, None = disabled
+    pub events_udp_target: Option<String>,
+    /// Local port for a websocket server that streams JSON events, 0 = disabled
+    pub events_websocket_port: u16,
+    /// Languages the next-language hotkey cycles through, after auto-detection
+    pub languages: Vec<String>,
+    /// Have whisper translate dictation to English instead of transcribing it
+    pub translate: bool,
+    /// Local port for the `POST /transcribe` HTTP server, 0 = disabled
+    pub http_port: u16,
+    /// Folder for rotating log files, None = console only
+    pub log_dir: Option<PathBuf>,
+    pub log_rotation: LogRotation,
+    /// Log files kept in `log_dir`, 0 = all
+    pub log_max_files: usize,
+    /// How often to publish the mic level while recording
+    pub level_interval_ms: u64,
+    /// Redraw a peak meter on the console while recording
+    pub level_meter: bool,
+    /// Recordings are cut off (and stopped) at this length, 0 = no limit
+    pub max_recording_seconds: u64,
+    /// Extra whisper-cli arguments, appended after the ones the tool manages
+    pub whisper_extra_args: Vec<String>,
+    /// CPU threads for whisper (WHISPER_THREADS), None = one per logical CPU
+    pub whisper_threads: Option<usize>,
+    /// whisper-cli runs longer than this are killed, 0 = no limit
+    pub whisper_timeout_seconds: u64,
+    /// Initial prompts for whisper, general and per forced language
+    pub whisper_prompts: Prompts,
+    /// Punctuation-restoring command (program and arguments), empty = none
+    pub punctuator_command: Vec<String>,
+    /// Punctuation-restoring ONNX model, used instead of the command when set
+    pub punctuator_model: Option<PathBuf>,
+    /// The model's tokenizer.json, None = next to the model
+    pub punctuator_tokenizer: Option<PathBuf>,
+    /// Names of the model's output classes, in order
+    pub punctuator_labels: Vec<String>,
+    /// Remove whisper log lines, timestamps and special tokens from its output
+    pub strip_whisper_markup: bool,
+    /// Desktop notifications for finished and failed transcriptions
+    pub notifications: bool,
+    /// Print extra diagnostics (e.g. the full whisper command)
+    pub verbose: bool,
+    /// Show prompts and spoken text in VERBOSE logs instead of redacting them
+    pub log_sensitive: bool,
+    /// Copy the last recording's audio next to the diagnostics report
+    pub diagnostics_include_audio: bool,
+    /// Pin the language after this many consecutive identical detections, 0 = never
+    pub pin_language_after: u32,
+    /// All known profiles; the first one is always "default"
+    pub profiles: Vec<Profile>,
+    /// Name of the profile in use
+    pub active_profile: String,
+    /// Start in the profile used last time instead of the one set with PROFILE
+    pub remember_profile: bool,
+    /// Skip the microphone prompt when the one picked last time is connected
+    pub remember_microphone: bool,
+    /// Keys bound to each action (HOTKEY.<action>=<key>)
+    pub hotkeys: Hotkeys,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            whisper_model: PathBuf::from("ggml-large-v3-turbo.bin"), // Best model with CUDA acceleration
+            whisper_backend: TranscriberBackend::Cli,
+            whisper_gpu: true,
+            whisper_mmap: true,
+            whisper_model_battery: None,
+            whisper_models: Vec::new(),
+            model_cache_dir: None,
+            temp_dir: None,
+            on_no_speech: NoSpeechAction::Report,
+            on_busy: BusyPolicy::Reject,
+            record_mode: RecordMode::Hold,
+            toggle_silence_stop_ms: 0,
+            toggle_debounce_ms: 200,
+            silence_threshold: 0.01,
+            speak_debounce_ms: 300,
+            double_tap_ms: 0,
+            hotkey_guard_ms: 200,
+            max_heavy_ops: 1,
+            trim_silence: false,
+            min_clip_ms: 0,
+            start_fade_ms: 10,
+            chunk_after_s: 120,
+            chunk_length_s: 60,
+            end_command: None,
+            max_words_per_second: 8.0,
+            trim_window_ms: 10,
+            trim_open_threshold: 0.02,
+            trim_close_threshold: 0.01,
+            music_gate: false,
+            music_gate_flatness: 0.5,
+            music_gate_gain: 0.1,
+            normalize: None,
+            normalize_peak_dbfs: -3.0,
+            normalize_rms_dbfs: -20.0,
+            device: None,
+            audio_start_delay_ms: 0,
+            audio_start_retries: 3,
+            audio_start_backoff_ms: 200,
+            min_free_disk_mb: 50,
+            audio_error_limit: 5,
+            audio_error_window_ms: 10000,
+            output_mode: OutputMode::Paste,
+            paste_keys: "ctrl+v".to_string(),
+            keep_clipboard: false,
+            restore_clipboard: true,
+            restore_clipboard_delay_ms: 300,
+            append_clipboard: None,
+            append_clipboard_paste: true,
+            merge_window_ms: 0,
+            type_char_delay_ms: 0,
+            type_jitter_ms: 0,
+            type_chunk_chars: 0,
+            type_chunk_delay_ms: 50,
+            preview: false,
+            clipboard_history: 0,
+            speak_transcription: false,
+            mute_tts_apps: Vec::new(),
+            block_dictation_apps: Vec::new(),
+            restore_focus: true,
+            dataset_dir: None,
+            dataset_include_rejected: false,
+            dataset_max_age_days: 0,
+            dataset_max_entries: 0,
+            tray: false,
+            events_udp_target: None,
+            events_websocket_port: 0,
+            languages: Vec::new(),
+            translate: false,
+            http_port: 0,
+            log_dir: None,
+            log_rotation: LogRotation::Daily,
+            log_max_files: 7,
+            level_interval_ms: 100,
+            level_meter: true,
+            max_recording_seconds: 300,
+            pin_language_after: 0,
+            whisper_extra_args: Vec::new(),
+            whisper_threads: None,
+            whisper_timeout_seconds: 600,
+            whisper_prompts: Prompts::default(),
+            punctuator_command: Vec::new(),
+            punctuator_model: None,
+            punctuator_tokenizer: None,
+            punctuator_labels: split_list("O,COMMA,PERIOD,QUESTION"),
+            strip_whisper_markup: true,
+            notifications: true,
+            verbose: false,
+            log_sensitive: false,
+            diagnostics_include_audio: false,
+            profiles: vec![Profile::new(DEFAULT_PROFILE)],
+            active_profile: DEFAULT_PROFILE.to_string(),
+            remember_profile: false,
+            remember_microphone: true,
+            hotkeys: Hotkeys::default(),
+        }
+    }
+}
+
+impl AppConfig {
+    /// Load settings from the config file, keeping defaults for anything unset
+    /// Keys that belong to other components (e.g. PIPER_*) are ignored here.
+    /// Unlike other settings, an invalid hotkey is an error rather than a warning.
+    pub fn load() -> Result<Self> {
+        let mut config = Self::default();
+        for (key, value) in read_config_file()? {
+            match key.strip_prefix("HOTKEY.") {
+                Some(action) => config
+                    .hotkeys
+                    .set(action, &value)
+                    .with_context(|| format!("Invalid {} in {}", key, config_file()))?,
+                None => config.apply(&key, &value),
+            }
+        }
+
+        if !config.profiles.iter().any(|p| p.name == config.active_profile) {
+            warn!(
+                "Profile '{}' is not defined in {}, using '{}'",
+                config.active_profile,
+                config_file(),
+                DEFAULT_PROFILE
+            );
+            config.active_profile = DEFAULT_PROFILE.to_string();
+        }
+        Ok(config)
+    }
+
+    /// Take over the settings of a freshly loaded config, keeping those only used at startup
+    /// Returns the keys of startup-only settings that changed and need a restart.
+    pub fn apply_reload(&mut self, mut reloaded: AppConfig) -> Vec<&'static str> {
+        let mut restart_needed = Vec::new();
+        if reloaded.device != self.device {
+            restart_needed.push("DEVICE");
+        }
+        if reloaded.whisper_model != self.whisper_model {
+            restart_needed.push("WHISPER_MODEL");
+        }
+        if reloaded.whisper_backend != self.whisper_backend {
+            restart_needed.push("WHISPER_BACKEND");
+        }
+        if reloaded.whisper_gpu != self.whisper_gpu {
+            restart_needed.push("WHISPER_GPU");
+        }
+        if reloaded.whisper_mmap != self.whisper_mmap {
+            restart_needed.push("WHISPER_MMAP");
+        }
+        if reloaded.whisper_model_battery != self.whisper_model_battery {
+            restart_needed.push("WHISPER_MODEL_BATTERY");
+        }
+        if reloaded.whisper_models != self.whisper_models {
+            restart_needed.push("WHISPER_MODEL.<name>");
+        }
+        if reloaded.model_cache_dir != self.model_cache_dir {
+            restart_needed.push("MODEL_CACHE_DIR");
+        }
+        if reloaded.temp_dir != self.temp_dir {
+            restart_needed.push("TEMP_DIR");
+        }
+        if reloaded.events_udp_target != self.events_udp_target {
+            restart_needed.push("EVENTS_UDP");
+        }
+        if reloaded.events_websocket_port != self.events_websocket_port {
+            restart_needed.push("EVENTS_WEBSOCKET_PORT");
+        }
+        if reloaded.http_port != self.http_port {
+            restart_needed.push("HTTP_PORT");
+        }
+        if reloaded.log_dir != self.log_dir
+            || reloaded.log_rotation != self.log_rotation
+            || reloaded.log_max_files != self.log_max_files
+        {
+            restart_needed.push("LOG_DIR/LOG_ROTATION/LOG_MAX_FILES");
+        }
+
+        std::mem::swap(&mut reloaded.device, &mut self.device);
+        std::mem::swap(&mut reloaded.whisper_model, &mut self.whisper_model);
+        std::mem::swap(&mut reloaded.whisper_backend, &mut self.whisper_backend);
+        std::mem::swap(&mut reloaded.whisper_gpu, &mut self.whisper_gpu);
+        std::mem::swap(&mut reloaded.whisper_mmap, &mut self.whisper_mmap);
+        std::mem::swap(
+            &mut reloaded.whisper_model_battery,
+            &mut self.whisper_model_battery,
+        );
+        std::mem::swap(&mut reloaded.whisper_models, &mut self.whisper_models);
+        std::mem::swap(&mut reloaded.model_cache_dir, &mut self.model_cache_dir);
+        std::mem::swap(&mut reloaded.temp_dir, &mut self.temp_dir);
+        std::mem::swap(&mut reloaded.events_udp_target, &mut self.events_udp_target);
+        std::mem::swap(
+            &mut reloaded.events_websocket_port,
+            &mut self.events_websocket_port,
+        );
+        std::mem::swap(&mut reloaded.http_port, &mut self.http_port);
+        std::mem::swap(&mut reloaded.log_dir, &mut self.log_dir);
+        std::mem::swap(&mut reloaded.log_rotation, &mut self.log_rotation);
+        std::mem::swap(&mut reloaded.log_max_files, &mut self.log_max_files);
+        *self = reloaded;
+        restart_needed
+    }
+
+    /// Retention limits for the dataset directory
+    pub fn dataset_retention(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            max_age: (self.dataset_max_age_days > 0)
+                .then(|| Duration::from_secs(self.dataset_max_age_days * 24 * 60 * 60)),
+            max_count: (self.dataset_max_entries > 0).then_some(self.dataset_max_entries),
+        }
+    }
+
+    /// Audio preprocessing steps to apply before transcription
+    pub fn preprocessing(&self) -> Preprocessing {
+        Preprocessing {
+            fade_in: Duration::from_millis(self.start_fade_ms),
+            trim_silence: self.trim_silence.then(|| SilenceTrim {
+                window: Duration::from_millis(self.trim_window_ms),
+                open_threshold: self.trim_open_threshold,
+                close_threshold: self.trim_close_threshold,
+            }),
+            music_gate: self
+                .music_gate
+                .then_some((self.music_gate_flatness, self.music_gate_gain)),
+            normalize: self.normalize.map(|mode| match mode {
+                Normalize::Peak => (mode, self.normalize_peak_dbfs),
+                Normalize::Rms => (mode, self.normalize_rms_dbfs),
+            }),
+            min_length: Duration::from_millis(self.min_clip_ms),
+        }
+    }
+
+    /// Switch to the profile saved by `save_last_profile` (REMEMBER_PROFILE), if it still exists
+    pub fn restore_last_profile(&mut self) {
+        if !self.remember_profile {
+            return;
+        }
+        if let Ok(name) = std::fs::read_to_string(LAST_PROFILE_FILE) {
+            self.switch_to_saved_profile(name.trim());
+        }
+    }
+
+    /// Switch to the saved profile `name`, unless it has been removed from the config since
+    fn switch_to_saved_profile(&mut self, name: &str) {
+        if name.is_empty() || name == self.active_profile {
+            return;
+        }
+        if self.profiles.iter().any(|p| p.name == name) {
+            self.active_profile = name.to_string();
+        } else {
+            warn!(
+                "Last used profile '{}' is no longer defined, using '{}'",
+                name, self.active_profile
+            );
+        }
+    }
+
+    /// Remember the profile in use for the next start (REMEMBER_PROFILE)
+    pub fn save_last_profile(&self) {
+        if !self.remember_profile {
+            return;
+        }
+        if let Err(e) = std::fs::write(LAST_PROFILE_FILE, &self.active_profile) {
+            warn!("Failed to save the last used profile: {}", e);
+        }
+    }
+
+    /// The microphone saved by `save_last_microphone` (REMEMBER_MICROPHONE), if any
+    pub fn last_microphone(&self) -> Option<String> {
+        if !self.remember_microphone {
+            return None;
+        }
+        let name = std::fs::read_to_string(LAST_MICROPHONE_FILE).ok()?;
+        let name = name.trim();
+        (!name.is_empty()).then(|| name.to_string())
+    }
+
+    /// Remember the microphone picked at the prompt for the next start (REMEMBER_MICROPHONE)
+    pub fn save_last_microphone(&self, name: &str) {
+        if !self.remember_microphone {
+            return;
+        }
+        if let Err(e) = std::fs::write(LAST_MICROPHONE_FILE, name) {
+            warn!("Failed to save the last used microphone: {}", e);
+        }
+    }
+
+    /// The profile currently in use
+    pub fn profile(&self) -> &Profile {
+        self.profiles
+            .iter()
+            .find(|p| p.name == self.active_profile)
+            .unwrap_or(&self.profiles[0])
+    }
+
+    /// Force the next language from LANGUAGES on the active profile, going back to
+    /// auto-detection after the last one; returns the forced language (None = auto)
+    /// A language that isn't in the list moves on to the first one. Reloading undoes it.
+    pub fn next_language(&mut self) -> Option<String> {
+        let profile = self.profile();
+        let position = profile.language.as_deref().and_then(|current| {
+            self.languages
+                .iter()
+                .position(|code| code.eq_ignore_ascii_case(current))
+        });
+        let next = match position {
+            Some(index) => self.languages.get(index + 1).cloned(),
+            None => self.languages.first().cloned(),
+        };
+        let name = profile.name.clone();
+        self.profile_mut(&name).language = next.clone();
+        next
+    }
+
+    fn profile_mut(&mut self, name: &str) -> &mut Profile {
+        let index = match self.profiles.iter().position(|p| p.name == name) {
+            Some(index) => index,
+            None => {
+                self.profiles.push(Profile::new(name));
+                self.profiles.len() - 1
+            }
+        };
+        &mut self.profiles[index]
+    }
+
+    /// Apply a setting, warning about an invalid value (the current one is kept)
+    fn apply(&mut self, key: &str, value: &str) {
+        if let Err(e) = self.try_apply(key, value) {
+            warn!("{} in {}, ignoring it", e, config_file());
+        }
+    }
+
+    /// Apply a setting, returning false if the key isn't one of these settings
+    /// (e.g. PIPER_*, which the narrator reads); an invalid value is an error
+    fn try_apply(&mut self, key: &str, value: &str) -> Result<bool> {
+        if let Some((name, option)) = key
+            .strip_prefix("PROFILE.")
+            .and_then(|rest| rest.split_once('.'))
+        {
+            if !self.profile_mut(name).apply(option, value)? {
+                return Err(anyhow!("Unknown profile option '{}'", key));
+            }
+            return Ok(true);
+        }
+        if self.profile_mut(DEFAULT_PROFILE).apply(key, value)? {
+            return Ok(true);
+        }
+
+        match key {
+            "PROFILE" => self.active_profile = value.to_string(),
+            "REMEMBER_PROFILE" => set_bool(&mut self.remember_profile, key, value)?,
+            "REMEMBER_MICROPHONE" => set_bool(&mut self.remember_microphone, key, value)?,
+            "WHISPER_MODEL" => self.whisper_model = PathBuf::from(value),
+            "WHISPER_BACKEND" => match value.to_lowercase().as_str() {
+                "cli" => self.whisper_backend = TranscriberBackend::Cli,
+                "library" => self.whisper_backend = TranscriberBackend::Library,
+                _ => return Err(invalid(key, value)),
+            },
+            "WHISPER_GPU" => match value.to_lowercase().as_str() {
+                "auto" => self.whisper_gpu = true,
+                "cpu" => self.whisper_gpu = false,
+                _ => return Err(invalid(key, value)),
+            },
+            "WHISPER_MMAP" => set_bool(&mut self.whisper_mmap, key, value)?,
+            "WHISPER_MODEL_BATTERY" => {
+                self.whisper_model_battery = (!value.is_empty()).then(|| PathBuf::from(value))
+            }
+            "MODEL_CACHE_DIR" => self.model_cache_dir = Some(PathBuf::from(value)),
+            "TEMP_DIR" => self.temp_dir = Some(PathBuf::from(value)),
+            "ON_NO_SPEECH" => match value.to_lowercase().as_str() {
+                "report" => self.on_no_speech = NoSpeechAction::Report,
+                "ignore" => self.on_no_speech = NoSpeechAction::Ignore,
+                _ => return Err(invalid(key, value)),
+            },
+            "ON_BUSY" => match value.to_lowercase().as_str() {
+                "reject" => self.on_busy = BusyPolicy::Reject,
+                "queue" => self.on_busy = BusyPolicy::Queue,
+                _ => return Err(invalid(key, value)),
+            },
+            "RECORD_MODE" => match value.to_lowercase().as_str() {
+                "hold" => self.record_mode = RecordMode::Hold,
+                "toggle" => self.record_mode = RecordMode::Toggle,
+                _ => return Err(invalid(key, value)),
+            },
+            "TOGGLE_SILENCE_STOP_MS" => set_parsed(&mut self.toggle_silence_stop_ms, key, value)?,
+            "TOGGLE_DEBOUNCE_MS" => set_parsed(&mut self.toggle_debounce_ms, key, value)?,
+            "SILENCE_THRESHOLD" => set_parsed(&mut self.silence_threshold, key, value)?,
+            "SPEAK_DEBOUNCE_MS" => set_parsed(&mut self.speak_debounce_ms, key, value)?,
+            "DOUBLE_TAP_MS" => set_parsed(&mut self.double_tap_ms, key, value)?,
+            "HOTKEY_GUARD_MS" => set_parsed(&mut self.hotkey_guard_ms, key, value)?,
+            "MAX_HEAVY_OPS" => set_parsed(&mut self.max_heavy_ops, key, value)?,
+            "TRIM_SILENCE" => set_bool(&mut self.trim_silence, key, value)?,
+            "MIN_CLIP_MS" => set_parsed(&mut self.min_clip_ms, key, value)?,
+            "START_FADE_MS" => set_parsed(&mut self.start_fade_ms, key, value)?,
+            "CHUNK_AFTER_S" => set_parsed(&mut self.chunk_after_s, key, value)?,
+            "CHUNK_LENGTH_S" => set_parsed(&mut self.chunk_length_s, key, value)?,
+            "END_COMMAND" => self.end_command = (!value.is_empty()).then(|| value.to_string()),
+            "MAX_WORDS_PER_SECOND" => set_parsed(&mut self.max_words_per_second, key, value)?,
+            "TRIM_WINDOW_MS" => set_parsed(&mut self.trim_window_ms, key, value)?,
+            "TRIM_OPEN_THRESHOLD" => set_parsed(&mut self.trim_open_threshold, key, value)?,
+            "TRIM_CLOSE_THRESHOLD" => set_parsed(&mut self.trim_close_threshold, key, value)?,
+            "MUSIC_GATE" => set_bool(&mut self.music_gate, key, value)?,
+            "MUSIC_GATE_FLATNESS" => set_parsed(&mut self.music_gate_flatness, key, value)?,
+            "MUSIC_GATE_GAIN" => set_parsed(&mut self.music_gate_gain, key, value)?,
+            "NORMALIZE" => match value.to_lowercase().as_str() {
+                "off" => self.normalize = None,
+                "peak" => self.normalize = Some(Normalize::Peak),
+                "rms" => self.normalize = Some(Normalize::Rms),
+                _ => return Err(invalid(key, value)),
+            },
+            "NORMALIZE_PEAK_DBFS" => set_parsed(&mut self.normalize_peak_dbfs, key, value)?,
+            "NORMALIZE_RMS_DBFS" => set_parsed(&mut self.normalize_rms_dbfs, key, value)?,
+            "DEVICE" => {
+                self.device = match value {
+                    "" => None,
+                    v if v.eq_ignore_ascii_case("default") => Some("default".to_string()),
+                    v => Some(v.to_string()),
+                }
+            }
+            "AUDIO_START_DELAY_MS" => set_parsed(&mut self.audio_start_delay_ms, key, value)?,
+            "AUDIO_START_RETRIES" => set_parsed(&mut self.audio_start_retries, key, value)?,
+            "AUDIO_START_BACKOFF_MS" => set_parsed(&mut self.audio_start_backoff_ms, key, value)?,
+            "AUDIO_ERROR_LIMIT" => set_parsed(&mut self.audio_error_limit, key, value)?,
+            "AUDIO_ERROR_WINDOW_MS" => set_parsed(&mut self.audio_error_window_ms, key, value)?,
+            "MIN_FREE_DISK_MB" => set_parsed(&mut self.min_free_disk_mb, key, value)?,
+            "OUTPUT_MODE" => match value.to_lowercase().as_str() {
+                "paste" => self.output_mode = OutputMode::Paste,
+                "type" => self.output_mode = OutputMode::Type,
+                _ => return Err(invalid(key, value)),
+            },
+            "PASTE_KEYS" => self.paste_keys = value.to_string(),
+            "KEEP_CLIPBOARD" => set_bool(&mut self.keep_clipboard, key, value)?,
+            "RESTORE_CLIPBOARD" => set_bool(&mut self.restore_clipboard, key, value)?,
+            "RESTORE_CLIPBOARD_DELAY_MS" => {
+                set_parsed(&mut self.restore_clipboard_delay_ms, key, value)?
+            }
+            "APPEND_CLIPBOARD" => self.append_clipboard = parse_separator(value),
+            "APPEND_CLIPBOARD_PASTE" => set_bool(&mut self.append_clipboard_paste, key, value)?,
+            "MERGE_WINDOW_MS" => set_parsed(&mut self.merge_window_ms, key, value)?,
+            "TYPE_CHAR_DELAY_MS" => set_parsed(&mut self.type_char_delay_ms, key, value)?,
+            "TYPE_JITTER_MS" => set_parsed(&mut self.type_jitter_ms, key, value)?,
+            "TYPE_CHUNK_CHARS" => set_parsed(&mut self.type_chunk_chars, key, value)?,
+            "TYPE_CHUNK_DELAY_MS" => set_parsed(&mut self.type_chunk_delay_ms, key, value)?,
+            "PREVIEW" => set_bool(&mut self.preview, key, value)?,
+            "CLIPBOARD_HISTORY" => set_parsed(&mut self.clipboard_history, key, value)?,
+            "SPEAK_TRANSCRIPTION" => set_bool(&mut self.speak_transcription, key, value)?,
+            "MUTE_TTS_APPS" => self.mute_tts_apps = split_list(value),
+            "BLOCK_DICTATION_APPS" => self.block_dictation_apps = split_list(value),
+            "RESTORE_FOCUS" => set_bool(&mut self.restore_focus, key, value)?,
+            "DATASET_DIR" => self.dataset_dir = Some(PathBuf::from(value)),
+            "DATASET_INCLUDE_REJECTED" => set_bool(&mut self.dataset_include_rejected, key, value)?,
+            "DATASET_MAX_AGE_DAYS" => set_parsed(&mut self.dataset_max_age_days, key, value)?,
+            "DATASET_MAX_ENTRIES" => set_parsed(&mut self.dataset_max_entries, key, value)?,
+            "TRAY" => set_bool(&mut self.tray, key, value)?,
+            "EVENTS_UDP" => self.events_udp_target = Some(value.to_string()),
+            "EVENTS_WEBSOCKET_PORT" => set_parsed(&mut self.events_websocket_port, key, value)?,
+            "LANGUAGES" => {
+                self.languages = split_list(value)
+                    .into_iter()
+                    .map(|code| code.to_lowercase())
+                    .filter(|code| code != "auto")
+                    .collect()
+            }
+            "TRANSLATE" => set_bool(&mut self.translate, key, value)?,
+            "HTTP_PORT" => set_parsed(&mut self.http_port, key, value)?,
+            "LOG_DIR" => self.log_dir = (!value.is_empty()).then(|| PathBuf::from(value)),
+            "LOG_ROTATION" => match value.to_lowercase().as_str() {
+                "daily" => self.log_rotation = LogRotation::Daily,
+                "hourly" => self.log_rotation = LogRotation::Hourly,
+                "never" => self.log_rotation = LogRotation::Never,
+                _ => return Err(invalid(key, value)),
+            },
+            "LOG_MAX_FILES" => set_parsed(&mut self.log_max_files, key, value)?,
+            "LEVEL_INTERVAL_MS" => set_parsed(&mut self.level_interval_ms, key, value)?,
+            "LEVEL_METER" => set_bool(&mut self.level_meter, key, value)?,
+            "NOTIFICATIONS" => set_bool(&mut self.notifications, key, value)?,
+            "MAX_RECORDING_SECONDS" => set_parsed(&mut self.max_recording_seconds, key, value)?,
+            "PIN_LANGUAGE_AFTER" => set_parsed(&mut self.pin_language_after, key, value)?,
+            "WHISPER_TIMEOUT_SECONDS" => set_parsed(&mut self.whisper_timeout_seconds, key, value)?,
+            "WHISPER_THREADS" => {
+                self.whisper_threads = match value.parse() {
+                    Ok(0) => None,
+                    Ok(threads) => Some(threads),
+                    Err(_) if value.is_empty() || value.eq_ignore_ascii_case("auto") => None,
+                    Err(_) => {
+                        self.whisper_threads = None;
+                        return Err(invalid(key, value));
+                    }
+                }
+            }
+            "WHISPER_EXTRA_ARGS" => match split_args(value) {
+                Ok(args) => self.whisper_extra_args = args,
+                Err(e) => return Err(anyhow!("Invalid {} ({})", key, e)),
+            },
+            "PUNCTUATOR_COMMAND" => match split_args(value) {
+                Ok(args) => self.punctuator_command = args,
+                Err(e) => return Err(anyhow!("Invalid {} ({})", key, e)),
+            },
+            "PUNCTUATOR_MODEL" => {
+                self.punctuator_model = (!value.is_empty()).then(|| PathBuf::from(value))
+            }
+            "PUNCTUATOR_TOKENIZER" => {
+                self.punctuator_tokenizer = (!value.is_empty()).then(|| PathBuf::from(value))
+            }
+            "PUNCTUATOR_LABELS" => self.punctuator_labels = split_list(value),
+            "WHISPER_PROMPT" => {
+                self.whisper_prompts.default = (!value.is_empty()).then(|| value.to_string())
+            }
+            "STRIP_WHISPER_MARKUP" => set_bool(&mut self.strip_whisper_markup, key, value)?,
+            "VERBOSE" => set_bool(&mut self.verbose, key, value)?,
+            "LOG_SENSITIVE" => set_bool(&mut self.log_sensitive, key, value)?,
+            "DIAGNOSTICS_INCLUDE_AUDIO" => {
+                set_bool(&mut self.diagnostics_include_audio, key, value)?
+            }
+            _ => {
+                if let Some(language) = key.strip_prefix("WHISPER_PROMPT.") {
+                    let language = language.to_lowercase();
+                    let prompts = &mut self.whisper_prompts.by_language;
+                    prompts.retain(|(code, _)| *code != language);
+                    if !value.is_empty() {
+                        prompts.push((language, value.to_string()));
+                    }
+                } else if let Some(name) = key.strip_prefix("WHISPER_MODEL.") {
+                    self.whisper_models.retain(|(existing, _)| existing != name);
+                    if !value.is_empty() {
+                        self.whisper_models
+                            .push((name.to_string(), PathBuf::from(value)));
+                    }
+                } else {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Split a command-line style list of arguments
+/// Arguments are separated by whitespace; single or double quotes keep spaces inside one
+/// argument (e.g. `--prompt "Hello there" -bs 5`). Unbalanced quotes are an error.
+pub fn split_args(value: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+
+    for c in value.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            None if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if let Some(q) = quote {
+        return Err(anyhow::anyhow!("unclosed {} quote", q));
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+/// Comma-separated items, trimmed, without empty ones
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Separator for APPEND_CLIPBOARD: "space", "newline", "none" or literal text with
+/// `\n`/`\t` escapes (values are trimmed, so spaces must be named). Off/empty = replace
+fn parse_separator(value: &str) -> Option<String> {
+    let separator = match value.to_lowercase().as_str() {
+        "" | "false" | "no" | "off" | "0" => return None,
+        "true" | "yes" | "on" | "1" | "space" => " ".to_string(),
+        "newline" => "\n".to_string(),
+        "none" => String::new(),
+        _ => value.replace("\\n", "\n").replace("\\t", "\t"),
+    };
+    Some(separator)
+}
+
+/// Parse `value` into `target`, keeping the current value on failure
+pub fn set_parsed<T: std::str::FromStr>(target: &mut T, key: &str, value: &str) -> Result<()> {
+    *target = value.parse().map_err(|_| invalid(key, value))?;
+    Ok(())
+}
+
+/// Parse a boolean (true/false, yes/no, on/off, 1/0) into `target`
+pub fn set_bool(target: &mut bool, key: &str, value: &str) -> Result<()> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => *target = true,
+        "false" | "no" | "off" | "0" => *target = false,
+        _ => return Err(invalid(key, value)),
+    }
+    Ok(())
+}
+
+/// Error for a config value that could not be parsed
+pub fn invalid(key: &str, value: &str) -> anyhow::Error {
+    anyhow!("Invalid value '{}' for {}", value, key)
+}
+
+/// Warn about a config value that could not be parsed (the default is kept)
+pub fn warn_invalid(key: &str, value: &str) {
+    warn!(
+        "Invalid value '{}' for {} in {}, using default",
+        value,
+        key,
+        config_file()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hotkeys::Action;
+
+    #[test]
+    fn saved_profile_is_restored_only_while_it_exists() {
+        let mut config = AppConfig::default();
+        config.profile_mut("work");
+
+        config.switch_to_saved_profile("removed");
+        assert_eq!(config.active_profile, DEFAULT_PROFILE);
+
+        config.switch_to_saved_profile("work");
+        assert_eq!(config.active_profile, "work");
+    }
+
+    #[test]
+    fn args_are_split_on_whitespace_outside_quotes() {
+        assert_eq!(
+            split_args(r#"  --prompt "Hello there" -bs 5 --x 'a "b"' "" "#).unwrap(),
+            vec!["--prompt", "Hello there", "-bs", "5", "--x", "a \"b\"", ""]
+        );
+        assert!(split_args("").unwrap().is_empty());
+        assert!(split_args("--prompt 'unclosed").is_err());
+    }
+
+    #[test]
+    fn device_default_skips_the_microphone_choice() {
+        let mut config = AppConfig::default();
+        config.try_apply("DEVICE", "Default").unwrap();
+        assert_eq!(config.device.as_deref(), Some("default"));
+        config.try_apply("DEVICE", "USB Audio").unwrap();
+        assert_eq!(config.device.as_deref(), Some("USB Audio"));
+        config.try_apply("DEVICE", "").unwrap();
+        assert_eq!(config.device, None);
+    }
+
+    #[test]
+    fn reload_applies_live_settings_and_reports_startup_ones() {
+        let mut live = AppConfig::default();
+        let mut reloaded = AppConfig::default();
+        reloaded.hotkeys.set("record", "F7").unwrap();
+        reloaded.try_apply("DOUBLE_TAP_MS", "300").unwrap();
+        reloaded.try_apply("AUTO_PUNCTUATE", "false").unwrap();
+        reloaded.try_apply("DEVICE", "USB Audio").unwrap();
+        reloaded.try_apply("WHISPER_MODEL", "other.bin").unwrap();
+
+        let restart_needed = live.apply_reload(reloaded);
+        assert_eq!(restart_needed, vec!["DEVICE", "WHISPER_MODEL"]);
+        assert_eq!(live.hotkeys.label(Action::Record), "F7");
+        assert_eq!(live.double_tap_ms, 300);
+        assert!(!live.profile().auto_punctuate);
+        // Startup-only settings keep their running values until a restart
+        assert_eq!(live.device, None);
+        assert_eq!(live.whisper_model, AppConfig::default().whisper_model);
+    }
+
+    #[test]
+    fn music_gate_is_off_until_enabled() {
+        let mut config = AppConfig::default();
+        assert_eq!(config.preprocessing().music_gate, None);
+
+        config.try_apply("MUSIC_GATE", "on").unwrap();
+        config.try_apply("MUSIC_GATE_FLATNESS", "0.4").unwrap();
+        config.try_apply("MUSIC_GATE_GAIN", "0.2").unwrap();
+        assert_eq!(config.preprocessing().music_gate, Some((0.4, 0.2)));
+
+        assert!(config.try_apply("MUSIC_GATE_GAIN", "quiet").is_err());
+    }
+}
@@ -0,0 +1,383 @@
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of the JSONL manifest inside the dataset directory
+pub const MANIFEST_FILE: &str = "manifest.jsonl";
+
+/// What happened to a dictation, stored with its dataset entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryStatus {
+    /// Transcribed and pasted
+    Accepted,
+    /// Transcribed but skipped or re-recorded from the preview
+    Skipped,
+    /// Whisper heard no speech
+    NoSpeech,
+    /// Rejected as a likely hallucination (implausible word rate)
+    Hallucination,
+}
+
+impl EntryStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            EntryStatus::Accepted => "accepted",
+            EntryStatus::Skipped => "skipped",
+            EntryStatus::NoSpeech => "no_speech",
+            EntryStatus::Hallucination => "hallucination",
+        }
+    }
+}
+
+/// Limits on how many dataset entries are kept; oldest entries are deleted first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Delete entries older than this
+    pub max_age: Option<Duration>,
+    /// Keep at most this many entries
+    pub max_count: Option<usize>,
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some() || self.max_count.is_some()
+    }
+}
+
+/// Pick the ids to delete from `(id, created)` entries under `policy`
+pub fn select_expired(
+    entries: &[(String, SystemTime)],
+    policy: &RetentionPolicy,
+    now: SystemTime,
+) -> Vec<String> {
+    let mut entries: Vec<&(String, SystemTime)> = entries.iter().collect();
+    // Newest first, so everything past max_count is the oldest
+    entries.sort_by_key(|entry| Reverse(entry.1));
+
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(index, (_, created))| {
+            let too_many = policy.max_count.is_some_and(|max| *index >= max);
+            let too_old = policy.max_age.is_some_and(|max_age| {
+                now.duration_since(*created).unwrap_or(Duration::ZERO) > max_age
+            });
+            too_many || too_old
+        })
+        .map(|(_, (id, _))| id.clone())
+        .collect()
+}
+
+/// Creation time encoded in an id made by `next_id` ("<unix millis>" or "<unix millis>_<n>")
+fn id_created(id: &str) -> Option<SystemTime> {
+    let (millis, suffix) = match id.split_once('_') {
+        Some((millis, suffix)) => (millis, Some(suffix)),
+        None => (id, None),
+    };
+    if suffix.is_some_and(|s| s.is_empty() || !s.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+    if millis.is_empty() || !millis.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(UNIX_EPOCH + Duration::from_millis(millis.parse().ok()?))
+}
+
+/// Sample rate and length of the WAV file at `path`, from its header
+fn wav_length(path: &Path) -> Result<(u32, Duration)> {
+    let reader = hound::WavReader::open(path)
+        .with_context(|| format!("Failed to read '{}'", path.display()))?;
+    let sample_rate = reader.spec().sample_rate;
+    let frames = reader.duration();
+    Ok((
+        sample_rate,
+        Duration::from_secs_f64(frames as f64 / sample_rate.max(1) as f64),
+    ))
+}
+
+/// Saves each dictation as `{id}.wav` + `{id}.txt` and a row in `manifest.jsonl`
+///
+/// Files are written under a temporary name and renamed into place, and the manifest
+/// row is appended last, so a row only ever points at complete files.
+pub struct DatasetExporter {
+    dir: PathBuf,
+    include_rejected: bool,
+}
+
+impl DatasetExporter {
+    pub fn new(dir: PathBuf, include_rejected: bool) -> Self {
+        Self {
+            dir,
+            include_rejected,
+        }
+    }
+
+    /// Store the prepared (16 kHz mono) WAV at `audio_path` together with its transcription
+    /// Entries that weren't accepted are dropped unless rejected results are included.
+    /// The manifest's duration is that of the WAV as written, after trimming and padding.
+    pub fn export(&self, audio_path: &Path, text: &str, status: EntryStatus) -> Result<()> {
+        if status != EntryStatus::Accepted && !self.include_rejected {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create dataset dir '{}'", self.dir.display()))?;
+
+        let id = self.next_id();
+        let wav_name = format!("{}.wav", id);
+        let txt_name = format!("{}.txt", id);
+        let (sample_rate, duration) = wav_length(audio_path)?;
+
+        let partial_wav = self.dir.join(format!("{}.partial", wav_name));
+        fs::copy(audio_path, &partial_wav)?;
+        fs::rename(&partial_wav, self.dir.join(&wav_name))?;
+
+        let partial_txt = self.dir.join(format!("{}.partial", txt_name));
+        fs::write(&partial_txt, text)?;
+        fs::rename(&partial_txt, self.dir.join(&txt_name))?;
+
+        let row = json!({
+            "id": id,
+            "audio": wav_name,
+            "text": text,
+            "duration": duration.as_secs_f64(),
+            "sample_rate": sample_rate,
+            "status": status.as_str(),
+        });
+        let mut manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(MANIFEST_FILE))?;
+        // One write per row so a crash can't leave half a line behind another row
+        manifest.write_all(format!("{}\n", row).as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Delete the oldest entries beyond `policy`, returning how many were removed
+    /// Only `.wav`/`.txt` files named like the exporter's own ids are ever touched,
+    /// and their manifest rows are dropped too.
+    pub fn enforce_retention(&self, policy: &RetentionPolicy) -> Result<usize> {
+        if !policy.is_enabled() || !self.dir.exists() {
+            return Ok(0);
+        }
+
+        let mut ids = HashSet::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let is_entry_file = matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("wav") | Some("txt")
+            );
+            if !is_entry_file {
+                continue;
+            }
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                if id_created(stem).is_some() {
+                    ids.insert(stem.to_string());
+                }
+            }
+        }
+        let entries: Vec<(String, SystemTime)> = ids
+            .into_iter()
+            .filter_map(|id| id_created(&id).map(|created| (id, created)))
+            .collect();
+
+        let expired = select_expired(&entries, policy, SystemTime::now());
+        if expired.is_empty() {
+            return Ok(0);
+        }
+        for id in &expired {
+            for ext in ["wav", "txt"] {
+                let path = self.dir.join(format!("{}.{}", id, ext));
+                if path.exists() {
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+        self.drop_manifest_rows(&expired)?;
+        Ok(expired.len())
+    }
+
+    /// Rewrite the manifest without the rows of the given ids
+    fn drop_manifest_rows(&self, ids: &[String]) -> Result<()> {
+        let manifest_path = self.dir.join(MANIFEST_FILE);
+        let Ok(content) = fs::read_to_string(&manifest_path) else {
+            return Ok(());
+        };
+        let removed: HashSet<&str> = ids.iter().map(String::as_str).collect();
+        let kept: String = content
+            .lines()
+            .filter(|line| {
+                let id = serde_json::from_str::<serde_json::Value>(line)
+                    .ok()
+                    .and_then(|row| row["id"].as_str().map(str::to_string));
+                !id.is_some_and(|id| removed.contains(id.as_str()))
+            })
+            .map(|line| format!("{}\n", line))
+            .collect();
+
+        let partial_path = self.dir.join(format!("{}.partial", MANIFEST_FILE));
+        fs::write(&partial_path, kept)?;
+        fs::rename(&partial_path, &manifest_path)?;
+        Ok(())
+    }
+
+    /// Timestamp-based id that doesn't collide with an existing entry
+    fn next_id(&self) -> String {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let mut id = format!("{}", millis);
+        let mut suffix = 1;
+        while self.dir.join(format!("{}.wav", id)).exists() {
+            id = format!("{}_{}", millis, suffix);
+            suffix += 1;
+        }
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory for one test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("dataset_test_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Write `samples` silent samples as a 16 kHz mono WAV
+    fn write_wav(path: &Path, samples: usize) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for _ in 0..samples {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    fn manifest_rows(dir: &Path) -> Vec<serde_json::Value> {
+        fs::read_to_string(dir.join(MANIFEST_FILE))
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn manifest_duration_is_that_of_the_exported_wav() {
+        let dir = test_dir("duration");
+        let audio_path = dir.join("input.wav");
+        // 1.25 s, as prepared (the raw capture may have been longer before trimming)
+        write_wav(&audio_path, 20000);
+
+        let exporter = DatasetExporter::new(dir.join("dataset"), false);
+        exporter
+            .export(&audio_path, "Hello.", EntryStatus::Accepted)
+            .unwrap();
+
+        let rows = manifest_rows(&dir.join("dataset"));
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["duration"].as_f64(), Some(1.25));
+        assert_eq!(rows[0]["sample_rate"].as_u64(), Some(16000));
+        assert_eq!(rows[0]["text"], "Hello.");
+        assert_eq!(rows[0]["status"], "accepted");
+    }
+
+    #[test]
+    fn rejected_entries_are_only_kept_when_included() {
+        let dir = test_dir("rejected");
+        let audio_path = dir.join("input.wav");
+        write_wav(&audio_path, 1600);
+
+        DatasetExporter::new(dir.join("without"), false)
+            .export(&audio_path, "", EntryStatus::NoSpeech)
+            .unwrap();
+        DatasetExporter::new(dir.join("with"), true)
+            .export(&audio_path, "", EntryStatus::NoSpeech)
+            .unwrap();
+
+        let without = manifest_rows(&dir.join("without"));
+        let with = manifest_rows(&dir.join("with"));
+        let _ = fs::remove_dir_all(&dir);
+        assert!(without.is_empty());
+        assert_eq!(with.len(), 1);
+        assert_eq!(with[0]["status"], "no_speech");
+    }
+
+    #[test]
+    fn oldest_entries_past_the_limits_are_selected() {
+        let now = UNIX_EPOCH + Duration::from_secs(100 * 24 * 60 * 60);
+        let days_ago = |days: u64| now - Duration::from_secs(days * 24 * 60 * 60);
+        let entries = vec![
+            ("b".to_string(), days_ago(5)),
+            ("a".to_string(), days_ago(40)),
+            ("d".to_string(), days_ago(1)),
+            ("c".to_string(), days_ago(10)),
+        ];
+        let expired = |max_age_days: Option<u64>, max_count| {
+            let policy = RetentionPolicy {
+                max_age: max_age_days.map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+                max_count,
+            };
+            let mut ids = select_expired(&entries, &policy, now);
+            ids.sort();
+            ids
+        };
+
+        assert!(expired(None, None).is_empty());
+        assert_eq!(expired(Some(30), None), vec!["a"]);
+        assert_eq!(expired(None, Some(2)), vec!["a", "c"]);
+        assert_eq!(expired(Some(7), Some(3)), vec!["a", "c"]);
+        assert_eq!(expired(None, Some(0)), vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn retention_only_deletes_the_exporters_own_files() {
+        let dir = test_dir("retention");
+        for name in ["1000.wav", "1000.txt", "2000_1.wav", "notes.txt"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        fs::write(
+            dir.join(MANIFEST_FILE),
+            "{\"id\":\"1000\"}\n{\"id\":\"2000_1\"}\n",
+        )
+        .unwrap();
+
+        let exporter = DatasetExporter::new(dir.clone(), false);
+        let policy = RetentionPolicy {
+            max_age: None,
+            max_count: Some(1),
+        };
+        let removed = exporter.enforce_retention(&policy).unwrap();
+        let rows = manifest_rows(&dir);
+        let left: HashSet<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(removed, 1);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["id"], "2000_1");
+        let expected = ["2000_1.wav", "notes.txt", MANIFEST_FILE];
+        assert_eq!(left, expected.iter().map(|name| name.to_string()).collect());
+    }
+}
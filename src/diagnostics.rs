@@ -0,0 +1,255 @@
+//! Diagnostics bundle for bug reports: settings, devices, tool versions and recent activity
+
+use crate::audio::{block_level, Recording};
+use anyhow::Result;
+use cpal::traits::{DeviceTrait, HostTrait};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Summary of a recording, kept instead of the audio itself
+#[derive(Debug, Clone)]
+pub struct RecordingStats {
+    pub duration: Duration,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub peak: f32,
+    pub rms: f32,
+}
+
+impl RecordingStats {
+    pub fn of(recording: &Recording) -> Self {
+        let level = block_level(&recording.samples);
+        Self {
+            duration: recording.duration(),
+            sample_rate: recording.sample_rate,
+            channels: recording.channels,
+            peak: level.peak,
+            rms: level.rms,
+        }
+    }
+}
+
+/// The last few activity lines, oldest first, each stamped with the time since startup
+pub struct LogTail {
+    lines: VecDeque<String>,
+    capacity: usize,
+    started: Instant,
+}
+
+impl LogTail {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn push(&mut self, line: impl std::fmt::Display) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        let elapsed = self.started.elapsed().as_secs_f32();
+        self.lines
+            .push_back(format!("[{:>9.1}s] {}", elapsed, line));
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().map(String::as_str)
+    }
+}
+
+/// Everything that goes into a diagnostics report, gathered by the caller
+pub struct Report<'a> {
+    /// Effective settings, already formatted
+    pub settings: Vec<(&'a str, String)>,
+    pub input_devices: Vec<String>,
+    pub output_devices: Vec<String>,
+    /// (tool name, version output or the error running it)
+    pub versions: Vec<(&'a str, String)>,
+    pub recent: Vec<&'a str>,
+    pub last_recording: Option<&'a RecordingStats>,
+}
+
+/// Lay the report out as plain text, one `== Section ==` heading per part
+pub fn assemble(report: &Report) -> String {
+    let mut text = String::new();
+    let mut section = |title: &str, lines: Vec<String>| {
+        text.push_str(&format!("== {} ==\n", title));
+        if lines.is_empty() {
+            text.push_str("(none)\n");
+        }
+        for line in lines {
+            text.push_str(&line);
+            text.push('\n');
+        }
+        text.push('\n');
+    };
+
+    section(
+        "Settings",
+        report
+            .settings
+            .iter()
+            .map(|(name, value)| format!("[{}]\n{}", name, value))
+            .collect(),
+    );
+    section("Input devices", report.input_devices.clone());
+    section("Output devices", report.output_devices.clone());
+    section(
+        "Versions",
+        report
+            .versions
+            .iter()
+            .map(|(tool, version)| format!("{}: {}", tool, version))
+            .collect(),
+    );
+    section(
+        "Recent activity",
+        report.recent.iter().map(|line| line.to_string()).collect(),
+    );
+    section(
+        "Last recording",
+        report
+            .last_recording
+            .map(|stats| {
+                vec![format!(
+                    "{:.2}s, {} Hz, {} channel(s), peak {:.3}, RMS {:.3}",
+                    stats.duration.as_secs_f32(),
+                    stats.sample_rate,
+                    stats.channels,
+                    stats.peak,
+                    stats.rms
+                )]
+            })
+            .unwrap_or_default(),
+    );
+    text
+}
+
+/// Audio devices with their default configuration, (inputs, outputs)
+pub fn describe_devices() -> (Vec<String>, Vec<String>) {
+    let host = cpal::default_host();
+    let inputs = match host.input_devices() {
+        Ok(devices) => devices
+            .map(|device| {
+                let name = device.name().unwrap_or_else(|_| "unknown".to_string());
+                match device.default_input_config() {
+                    Ok(config) => format!("{} ({:?})", name, config),
+                    Err(e) => format!("{} (no default config: {})", name, e),
+                }
+            })
+            .collect(),
+        Err(e) => vec![format!("Failed to list input devices: {}", e)],
+    };
+    let outputs = match host.output_devices() {
+        Ok(devices) => devices
+            .map(|device| {
+                let name = device.name().unwrap_or_else(|_| "unknown".to_string());
+                match device.default_output_config() {
+                    Ok(config) => format!("{} ({:?})", name, config),
+                    Err(e) => format!("{} (no default config: {})", name, e),
+                }
+            })
+            .collect(),
+        Err(e) => vec![format!("Failed to list output devices: {}", e)],
+    };
+    (inputs, outputs)
+}
+
+/// First line `program --version` prints, or why it couldn't be run
+pub fn tool_version(program: &Path) -> String {
+    match Command::new(program).arg("--version").output() {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            stdout
+                .lines()
+                .chain(stderr.lines())
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .map(str::to_string)
+                .unwrap_or_else(|| {
+                    format!("no version output (exit code {:?})", output.status.code())
+                })
+        }
+        Err(e) => format!("failed to run '{}': {}", program.display(), e),
+    }
+}
+
+/// Write `contents` to a new `diagnostics_<unix time>.txt` in `dir`
+/// With `audio` set, that file is copied alongside as `diagnostics_<unix time>.wav`.
+pub fn write_report(dir: &Path, contents: &str, audio: Option<&Path>) -> Result<PathBuf> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = dir.join(format!("diagnostics_{}.txt", stamp));
+    std::fs::write(&path, contents)?;
+    if let Some(audio) = audio.filter(|audio| audio.exists()) {
+        std::fs::copy(audio, dir.join(format!("diagnostics_{}.wav", stamp)))?;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_has_every_section_in_order() {
+        let stats = RecordingStats {
+            duration: Duration::from_millis(1500),
+            sample_rate: 48000,
+            channels: 1,
+            peak: 0.5,
+            rms: 0.125,
+        };
+        let report = Report {
+            settings: vec![("App", "record_mode: Hold".to_string())],
+            input_devices: vec!["USB Microphone".to_string()],
+            output_devices: Vec::new(),
+            versions: vec![("piper", "1.2.0".to_string())],
+            recent: vec!["[      1.0s] Recording started"],
+            last_recording: Some(&stats),
+        };
+        let text = assemble(&report);
+
+        let headings: Vec<&str> = text
+            .lines()
+            .filter(|line| line.starts_with("== "))
+            .collect();
+        assert_eq!(
+            headings,
+            vec![
+                "== Settings ==",
+                "== Input devices ==",
+                "== Output devices ==",
+                "== Versions ==",
+                "== Recent activity ==",
+                "== Last recording ==",
+            ]
+        );
+        assert!(text.contains("[App]\nrecord_mode: Hold\n"));
+        assert!(text.contains("== Output devices ==\n(none)\n"));
+        assert!(text.contains("piper: 1.2.0\n"));
+        assert!(text.contains("1.50s, 48000 Hz, 1 channel(s), peak 0.500, RMS 0.125"));
+    }
+
+    #[test]
+    fn log_tail_keeps_the_newest_lines() {
+        let mut tail = LogTail::new(2);
+        tail.push("first");
+        tail.push("second");
+        tail.push("third");
+        let lines: Vec<&str> = tail.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("] second"));
+        assert!(lines[1].ends_with("] third"));
+    }
+}
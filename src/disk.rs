@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Context, Result};
+use std::env;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Fail early if the disk `path` will be written to has less than `min_free_mb` available
+/// A `min_free_mb` of 0 disables the check.
+pub fn ensure_free_space(path: &Path, min_free_mb: u64) -> Result<()> {
+    if min_free_mb == 0 {
+        return Ok(());
+    }
+
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let available = fs2::available_space(dir)
+        .with_context(|| format!("Failed to query free disk space for '{}'", dir.display()))?;
+
+    check_free_space(available, min_free_mb)
+        .with_context(|| format!("Cannot write '{}'", path.display()))
+}
+
+/// Folder for temporary audio (recordings, TTS output): `configured` (TEMP_DIR) or the
+/// system temp folder, never the working directory
+/// Keeps the WAVs out of OneDrive/network folders, where syncing can lock them mid-run.
+/// A relative `configured` path is taken as relative to the system temp folder.
+pub fn temp_dir(configured: Option<&Path>) -> PathBuf {
+    let system = env::temp_dir();
+    let Some(configured) = configured else {
+        return system;
+    };
+    let dir = system.join(configured);
+    match std::fs::create_dir_all(&dir) {
+        Ok(()) => dir,
+        Err(e) => {
+            warn!(
+                "Can't use TEMP_DIR '{}' ({}), using '{}'",
+                dir.display(),
+                e,
+                system.display()
+            );
+            system
+        }
+    }
+}
+
+/// Compare the available bytes against the configured minimum
+fn check_free_space(available_bytes: u64, min_free_mb: u64) -> Result<()> {
+    if available_bytes < min_free_mb.saturating_mul(BYTES_PER_MB) {
+        return Err(anyhow!(
+            "Insufficient disk space: {} MB free, at least {} MB required (MIN_FREE_DISK_MB)",
+            available_bytes / BYTES_PER_MB,
+            min_free_mb
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_dir_is_relative_to_the_system_temp_folder() {
+        let system = env::temp_dir();
+        assert_eq!(temp_dir(None), system);
+
+        let name = format!("temp_dir_test_{}", std::process::id());
+        let dir = temp_dir(Some(Path::new(&name)));
+        assert_eq!(dir, system.join(&name));
+        assert!(dir.is_dir());
+        let _ = std::fs::remove_dir(&dir);
+    }
+
+    #[test]
+    fn unusable_temp_dir_falls_back_to_the_system_one() {
+        let blocker = env::temp_dir().join(format!("temp_dir_file_{}", std::process::id()));
+        std::fs::write(&blocker, b"not a folder").unwrap();
+        let dir = temp_dir(Some(&blocker.join("audio")));
+        let _ = std::fs::remove_file(&blocker);
+        assert_eq!(dir, env::temp_dir());
+    }
+}
@@ -0,0 +1,253 @@
+use anyhow::Result;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use std::net::{TcpListener, UdpSocket};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::warn;
+
+/// Something external listeners (overlays, meters) may want to know about
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// Current microphone level while recording, both in 0.0-1.0
+    Level { peak: f32, rms: f32 },
+    /// A finished transcription, after post-processing
+    Transcription {
+        text: String,
+        language: Option<String>,
+    },
+    /// Text about to be read aloud
+    Speech { text: String },
+    /// TTS playback reached a word (TTS_WORD_EVENTS); `index` counts the whitespace-separated
+    /// words of the last `Speech` text, `offset` is the estimated start within its paragraph
+    Word {
+        index: usize,
+        word: String,
+        offset: Duration,
+    },
+}
+
+impl Event {
+    /// JSON form sent to listeners, one object per message, e.g.
+    /// `{"type":"level","peak":0.42,"rms":0.08}`
+    pub fn to_json(&self) -> String {
+        match self {
+            Event::Level { peak, rms } => {
+                json!({ "type": "level", "peak": peak, "rms": rms }).to_string()
+            }
+            Event::Transcription { text, language } => {
+                json!({ "type": "transcription", "text": text, "language": language }).to_string()
+            }
+            Event::Speech { text } => json!({ "type": "speech", "text": text }).to_string(),
+            Event::Word {
+                index,
+                word,
+                offset,
+            } => json!({
+                "type": "word",
+                "index": index,
+                "word": word,
+                "offset_ms": offset.as_millis() as u64,
+            })
+            .to_string(),
+        }
+    }
+}
+
+/// Fan-out channel for `Event`s
+/// Publishing never blocks and is a no-op when nobody is listening.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(256);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+/// Send every published event as a JSON datagram to `target` (e.g. "127.0.0.1:9876")
+pub fn spawn_udp_forwarder(bus: &EventBus, target: &str) -> Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect(target)?;
+    let mut receiver = bus.subscribe();
+
+    std::thread::spawn(move || loop {
+        match receiver.blocking_recv() {
+            Ok(event) => {
+                // Nobody listening on the other end is fine, the overlay may start later
+                let _ = socket.send(event.to_json().as_bytes());
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    });
+    Ok(())
+}
+
+/// Serve every published event as a JSON text message to websocket clients on `port`
+/// Only local connections are accepted (127.0.0.1); clients get events from the moment
+/// they connect, and anything they send is ignored.
+pub fn spawn_websocket_server(bus: &EventBus, port: u16) -> Result<()> {
+    // Bind here so a port already in use is reported at startup
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    listener.set_nonblocking(true)?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let bus = bus.clone();
+
+    std::thread::spawn(move || {
+        runtime.block_on(async move {
+            let listener = match tokio::net::TcpListener::from_std(listener) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    warn!("Websocket event server stopped: {}", e);
+                    return;
+                }
+            };
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        tokio::spawn(serve_websocket_client(stream, bus.subscribe()));
+                    }
+                    Err(e) => warn!("Failed to accept a websocket client: {}", e),
+                }
+            }
+        })
+    });
+    Ok(())
+}
+
+/// Forward events to one websocket client until it disconnects
+async fn serve_websocket_client(stream: TcpStream, mut receiver: broadcast::Receiver<Event>) {
+    let Ok(socket) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    let (mut outgoing, mut incoming) = socket.split();
+    loop {
+        tokio::select! {
+            message = incoming.next() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            event = receiver.recv() => match event {
+                Ok(event) => {
+                    if outgoing.send(Message::Text(event.to_json())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+}
+
+/// Rate limiter that fires at most once per interval
+pub struct Ticker {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl Ticker {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    /// Returns true if at least one interval has passed since the last time it returned true
+    pub fn tick(&mut self) -> bool {
+        let now = Instant::now();
+        match self.last {
+            Some(last) if now.duration_since(last) < self.interval => false,
+            _ => {
+                self.last = Some(now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_messages_are_forwarded_at_the_ticker_rate() {
+        let bus = EventBus::new();
+        let meter = UdpSocket::bind("127.0.0.1:0").unwrap();
+        meter
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        spawn_udp_forwarder(&bus, &meter.local_addr().unwrap().to_string()).unwrap();
+
+        // A level source fed every 5ms, throttled to one message per 50ms
+        let mut ticker = Ticker::new(Duration::from_millis(50));
+        let start = Instant::now();
+        let mut published = 0;
+        while start.elapsed() < Duration::from_millis(250) {
+            if ticker.tick() {
+                bus.publish(Event::Level {
+                    peak: 0.5,
+                    rms: 0.25,
+                });
+                published += 1;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!((2..=6).contains(&published), "{} messages", published);
+
+        let mut buf = [0; 256];
+        for _ in 0..published {
+            let len = meter.recv(&mut buf).unwrap();
+            let message: serde_json::Value = serde_json::from_slice(&buf[..len]).unwrap();
+            assert_eq!(
+                message,
+                json!({ "type": "level", "peak": 0.5, "rms": 0.25 })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn websocket_client_receives_published_events_as_json() {
+        let bus = EventBus::new();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let receiver = bus.subscribe();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            serve_websocket_client(stream, receiver).await;
+        });
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}", address))
+            .await
+            .unwrap();
+        bus.publish(Event::Speech {
+            text: "Hello".to_string(),
+        });
+        let Some(Ok(Message::Text(text))) = client.next().await else {
+            panic!("expected a text message");
+        };
+        let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(event, json!({ "type": "speech", "text": "Hello" }));
+
+        // Closing the connection ends the client's task
+        client.close(None).await.unwrap();
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+//! Foreground window helpers
+//! Only implemented on Windows; elsewhere nothing is captured and pasting behaves as before.
+
+/// Handle of a top-level window
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowHandle(isize);
+
+/// The window that currently has keyboard focus, if it can be determined
+#[cfg(target_os = "windows")]
+pub fn foreground_window() -> Option<WindowHandle> {
+    use windows_sys::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+    let hwnd = unsafe { GetForegroundWindow() };
+    if hwnd == 0 {
+        None
+    } else {
+        Some(WindowHandle(hwnd))
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn foreground_window() -> Option<WindowHandle> {
+    None
+}
+
+/// Bring `window` back to the foreground, returning false if Windows refused
+#[cfg(target_os = "windows")]
+pub fn restore_foreground(window: WindowHandle) -> bool {
+    use std::time::Duration;
+    use windows_sys::Win32::UI::WindowsAndMessaging::SetForegroundWindow;
+
+    if foreground_window() == Some(window) {
+        return true;
+    }
+    let restored = unsafe { SetForegroundWindow(window.0) } != 0;
+    if restored {
+        // Give the window a moment to actually receive focus before keys are sent
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    restored
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn restore_foreground(_window: WindowHandle) -> bool {
+    false
+}
+
+/// Executable name (e.g. "Teams.exe") of the process owning the foreground window
+#[cfg(target_os = "windows")]
+pub fn foreground_process_name() -> Option<String> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::GetWindowThreadProcessId;
+
+    let window = foreground_window()?;
+    let mut process_id = 0u32;
+    unsafe { GetWindowThreadProcessId(window.0, &mut process_id) };
+    if process_id == 0 {
+        return None;
+    }
+
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, process_id) };
+    if process == 0 {
+        return None;
+    }
+    let mut buffer = [0u16; 1024];
+    let mut length = buffer.len() as u32;
+    let ok = unsafe {
+        QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            buffer.as_mut_ptr(),
+            &mut length,
+        )
+    };
+    unsafe { CloseHandle(process) };
+    if ok == 0 {
+        return None;
+    }
+
+    let path = String::from_utf16_lossy(&buffer[..length as usize]);
+    path.rsplit(['\\', '/']).next().map(str::to_string)
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn foreground_process_name() -> Option<String> {
+    None
+}
+
+/// Whether `process` is one of `apps`, ignoring case and an optional ".exe"
+fn is_listed_app(process: &str, apps: &[String]) -> bool {
+    let strip = |name: &str| {
+        let name = name.trim().to_lowercase();
+        name.strip_suffix(".exe")
+            .map(str::to_string)
+            .unwrap_or(name)
+    };
+    let process = strip(process);
+    apps.iter().any(|app| strip(app) == process)
+}
+
+/// `foreground` (the foreground process name) if it is one of `apps`
+pub fn listed_foreground_app(foreground: Option<String>, apps: &[String]) -> Option<String> {
+    foreground.filter(|app| is_listed_app(app, apps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apps(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn call_app_in_the_foreground_mutes_tts() {
+        let mute = apps(&["Teams", "zoom.exe"]);
+        let muting = |process: &str| listed_foreground_app(Some(process.to_string()), &mute);
+        assert_eq!(muting("ms-teams.exe"), None);
+        assert_eq!(muting("TEAMS.EXE"), Some("TEAMS.EXE".to_string()));
+        assert_eq!(muting("Zoom"), Some("Zoom".to_string()));
+        assert_eq!(muting("notepad.exe"), None);
+        assert_eq!(listed_foreground_app(None, &mute), None);
+    }
+
+    #[test]
+    fn blocked_app_in_the_foreground_drops_the_dictation() {
+        let block = apps(&[" KeePass ", "1Password.exe"]);
+        let blocking = |process: &str| listed_foreground_app(Some(process.to_string()), &block);
+        assert!(blocking("keepass.EXE").is_some());
+        assert!(blocking("1password").is_some());
+        assert!(blocking("KeePassXC.exe").is_none());
+        assert!(listed_foreground_app(Some("keepass.exe".to_string()), &[]).is_none());
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn captured_foreground_window_can_be_restored() {
+        // A headless session may have no foreground window at all
+        if let Some(window) = foreground_window() {
+            assert!(restore_foreground(window));
+            assert_eq!(foreground_window(), Some(window));
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn focus_is_never_restored_off_windows() {
+        assert!(!restore_foreground(WindowHandle(1)));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn no_foreground_app_is_known_off_windows() {
+        assert_eq!(foreground_process_name(), None);
+        assert_eq!(foreground_window(), None);
+    }
+}
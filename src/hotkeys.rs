@@ -0,0 +1,538 @@
+use anyhow::{anyhow, Result};
+use inputbot::KeybdKey;
+use std::time::{Duration, Instant};
+
+/// Transition of a polled key between two consecutive samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEdge {
+    Pressed,
+    Released,
+    None,
+}
+
+/// Turns polled `is_pressed` samples into press/release edges
+///
+/// A press is only reported if at least `min_interval` has passed since the last
+/// reported press, so one physical press always maps to one logical action even if
+/// the key bounces or is briefly misread while held. The release belonging to a
+/// suppressed press is suppressed as well.
+pub struct EdgeDetector {
+    was_pressed: bool,
+    press_suppressed: bool,
+    last_press: Option<Instant>,
+    min_interval: Duration,
+}
+
+impl EdgeDetector {
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            was_pressed: false,
+            press_suppressed: false,
+            last_press: None,
+            min_interval,
+        }
+    }
+
+    /// Feed the current key state and get the edge since the previous sample
+    pub fn update(&mut self, is_pressed: bool) -> KeyEdge {
+        let was_pressed = self.was_pressed;
+        self.was_pressed = is_pressed;
+
+        if is_pressed && !was_pressed {
+            let now = Instant::now();
+            if let Some(last_press) = self.last_press {
+                if now.duration_since(last_press) < self.min_interval {
+                    self.press_suppressed = true;
+                    return KeyEdge::None;
+                }
+            }
+            self.press_suppressed = false;
+            self.last_press = Some(now);
+            KeyEdge::Pressed
+        } else if !is_pressed && was_pressed {
+            if std::mem::take(&mut self.press_suppressed) {
+                KeyEdge::None
+            } else {
+                KeyEdge::Released
+            }
+        } else {
+            KeyEdge::None
+        }
+    }
+}
+
+/// What a completed tap gesture turned out to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tap {
+    Single,
+    Double,
+}
+
+/// Tells single presses from double taps
+///
+/// A press starts a tap that is only reported as `Single` once `window` has passed
+/// without a second press (see `poll`). A second press inside the window reports
+/// `Double` right away. With a zero window every press is a `Single`.
+pub struct DoubleTapDetector {
+    window: Duration,
+    first_press: Option<Instant>,
+}
+
+impl DoubleTapDetector {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            first_press: None,
+        }
+    }
+
+    /// Whether a first press is waiting to see if a second one follows
+    pub fn is_pending(&self) -> bool {
+        self.first_press.is_some()
+    }
+
+    /// Feed a press, returning the gesture if it is already decided
+    pub fn press(&mut self, now: Instant) -> Option<Tap> {
+        if self.window.is_zero() {
+            return Some(Tap::Single);
+        }
+        match self.first_press.take() {
+            Some(first) if now.duration_since(first) <= self.window => Some(Tap::Double),
+            _ => {
+                self.first_press = Some(now);
+                None
+            }
+        }
+    }
+
+    /// Report a pending press as `Single` once its window has run out
+    pub fn poll(&mut self, now: Instant) -> Option<Tap> {
+        let first = self.first_press?;
+        if now.duration_since(first) > self.window {
+            self.first_press = None;
+            Some(Tap::Single)
+        } else {
+            None
+        }
+    }
+}
+
+/// Ignores hotkeys for a short time after the tool sends keystrokes of its own,
+/// so its synthetic input (e.g. the paste shortcut) can never trigger an action
+pub struct SyntheticInputGuard {
+    window: Duration,
+    until: Option<Instant>,
+}
+
+impl SyntheticInputGuard {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            until: None,
+        }
+    }
+
+    /// Start (or extend) the quiet window; call right after emitting keystrokes
+    pub fn arm(&mut self, now: Instant) {
+        self.until = Some(now + self.window);
+    }
+
+    /// Whether hotkeys should be ignored at `now`
+    pub fn is_active(&self, now: Instant) -> bool {
+        self.until.is_some_and(|until| now < until)
+    }
+}
+
+/// Keys that can be named in the config, as (name, key); names are case-insensitive
+const KEY_NAMES: &[(&str, KeybdKey)] = &[
+    ("F1", KeybdKey::F1Key),
+    ("F2", KeybdKey::F2Key),
+    ("F3", KeybdKey::F3Key),
+    ("F4", KeybdKey::F4Key),
+    ("F5", KeybdKey::F5Key),
+    ("F6", KeybdKey::F6Key),
+    ("F7", KeybdKey::F7Key),
+    ("F8", KeybdKey::F8Key),
+    ("F9", KeybdKey::F9Key),
+    ("F10", KeybdKey::F10Key),
+    ("F11", KeybdKey::F11Key),
+    ("F12", KeybdKey::F12Key),
+    ("F13", KeybdKey::F13Key),
+    ("F14", KeybdKey::F14Key),
+    ("F15", KeybdKey::F15Key),
+    ("F16", KeybdKey::F16Key),
+    ("F17", KeybdKey::F17Key),
+    ("F18", KeybdKey::F18Key),
+    ("F19", KeybdKey::F19Key),
+    ("F20", KeybdKey::F20Key),
+    ("F21", KeybdKey::F21Key),
+    ("F22", KeybdKey::F22Key),
+    ("F23", KeybdKey::F23Key),
+    ("F24", KeybdKey::F24Key),
+    ("A", KeybdKey::AKey),
+    ("B", KeybdKey::BKey),
+    ("C", KeybdKey::CKey),
+    ("D", KeybdKey::DKey),
+    ("E", KeybdKey::EKey),
+    ("F", KeybdKey::FKey),
+    ("G", KeybdKey::GKey),
+    ("H", KeybdKey::HKey),
+    ("I", KeybdKey::IKey),
+    ("J", KeybdKey::JKey),
+    ("K", KeybdKey::KKey),
+    ("L", KeybdKey::LKey),
+    ("M", KeybdKey::MKey),
+    ("N", KeybdKey::NKey),
+    ("O", KeybdKey::OKey),
+    ("P", KeybdKey::PKey),
+    ("Q", KeybdKey::QKey),
+    ("R", KeybdKey::RKey),
+    ("S", KeybdKey::SKey),
+    ("T", KeybdKey::TKey),
+    ("U", KeybdKey::UKey),
+    ("V", KeybdKey::VKey),
+    ("W", KeybdKey::WKey),
+    ("X", KeybdKey::XKey),
+    ("Y", KeybdKey::YKey),
+    ("Z", KeybdKey::ZKey),
+    ("0", KeybdKey::Numrow0Key),
+    ("1", KeybdKey::Numrow1Key),
+    ("2", KeybdKey::Numrow2Key),
+    ("3", KeybdKey::Numrow3Key),
+    ("4", KeybdKey::Numrow4Key),
+    ("5", KeybdKey::Numrow5Key),
+    ("6", KeybdKey::Numrow6Key),
+    ("7", KeybdKey::Numrow7Key),
+    ("8", KeybdKey::Numrow8Key),
+    ("9", KeybdKey::Numrow9Key),
+    ("Esc", KeybdKey::EscapeKey),
+    ("Space", KeybdKey::SpaceKey),
+    ("Tab", KeybdKey::TabKey),
+    ("Insert", KeybdKey::InsertKey),
+    ("Delete", KeybdKey::DeleteKey),
+    ("Home", KeybdKey::HomeKey),
+    ("End", KeybdKey::EndKey),
+    ("PageUp", KeybdKey::PageUpKey),
+    ("PageDown", KeybdKey::PageDownKey),
+    ("ScrollLock", KeybdKey::ScrollLockKey),
+    ("CapsLock", KeybdKey::CapsLockKey),
+    ("LCtrl", KeybdKey::LControlKey),
+    ("RCtrl", KeybdKey::RControlKey),
+    ("LShift", KeybdKey::LShiftKey),
+    ("RShift", KeybdKey::RShiftKey),
+    ("LAlt", KeybdKey::LAltKey),
+    ("RAlt", KeybdKey::RAltKey),
+];
+
+/// Modifiers that can prefix a key, e.g. "ctrl+shift+F9"; either side counts
+const MODIFIER_NAMES: &[(&str, [KeybdKey; 2])] = &[
+    ("Ctrl", [KeybdKey::LControlKey, KeybdKey::RControlKey]),
+    ("Shift", [KeybdKey::LShiftKey, KeybdKey::RShiftKey]),
+    ("Alt", [KeybdKey::LAltKey, KeybdKey::RAltKey]),
+];
+
+/// A key, optionally held together with modifiers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hotkey {
+    key: KeybdKey,
+    /// Pairs of left/right keys, one of each must be held
+    modifiers: Vec<[KeybdKey; 2]>,
+    /// Name as written in the config, for messages
+    name: String,
+}
+
+impl Hotkey {
+    /// Parse a key name such as "F9", "Q" or "ctrl+shift+F9"
+    /// The error lists every valid name.
+    pub fn parse(text: &str) -> Result<Self> {
+        let parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let (key_name, modifier_names) =
+            parts.split_last().expect("split yields at least one part");
+        let modifiers = modifier_names
+            .iter()
+            .map(|name| {
+                MODIFIER_NAMES
+                    .iter()
+                    .find(|(known, _)| known.eq_ignore_ascii_case(name))
+                    .map(|(_, keys)| *keys)
+                    .ok_or_else(|| {
+                        let names: Vec<&str> =
+                            MODIFIER_NAMES.iter().map(|(known, _)| *known).collect();
+                        anyhow!(
+                            "unknown modifier '{}' in '{}', valid modifiers are: {}",
+                            name,
+                            text,
+                            names.join(", ")
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let key = KEY_NAMES
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(key_name))
+            .map(|(_, key)| *key)
+            .ok_or_else(|| {
+                let names: Vec<&str> = KEY_NAMES.iter().map(|(known, _)| *known).collect();
+                anyhow!(
+                    "unknown key '{}', valid keys are: {}",
+                    key_name,
+                    names.join(", ")
+                )
+            })?;
+        Ok(Self {
+            key,
+            modifiers,
+            name: parts.join("+"),
+        })
+    }
+
+    /// Whether the key and all its modifiers are held right now
+    /// Extra modifiers don't matter, so a plain "F9" also fires as Ctrl+F9.
+    pub fn is_pressed(&self) -> bool {
+        self.key.is_pressed()
+            && self
+                .modifiers
+                .iter()
+                .all(|pair| pair.iter().any(|key| key.is_pressed()))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Things a hotkey can do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Record,
+    Speak,
+    Accept,
+    Skip,
+    ResetLanguage,
+    Reload,
+    Diagnostics,
+    Cancel,
+    NextVoice,
+    Faster,
+    Slower,
+    Pause,
+    Translate,
+    NextLanguage,
+    NextModel,
+    SaveSpeech,
+}
+
+impl Action {
+    const ALL: [Action; 16] = [
+        Action::Record,
+        Action::Speak,
+        Action::Accept,
+        Action::Skip,
+        Action::ResetLanguage,
+        Action::Reload,
+        Action::Diagnostics,
+        Action::Cancel,
+        Action::NextVoice,
+        Action::Faster,
+        Action::Slower,
+        Action::Pause,
+        Action::Translate,
+        Action::NextLanguage,
+        Action::NextModel,
+        Action::SaveSpeech,
+    ];
+
+    /// Name used in `HOTKEY.<name>` config keys
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Record => "record",
+            Action::Speak => "speak",
+            Action::Accept => "accept",
+            Action::Skip => "skip",
+            Action::ResetLanguage => "reset_language",
+            Action::Reload => "reload",
+            Action::Diagnostics => "diagnostics",
+            Action::Cancel => "cancel",
+            Action::NextVoice => "next_voice",
+            Action::Faster => "faster",
+            Action::Slower => "slower",
+            Action::Pause => "pause",
+            Action::Translate => "translate",
+            Action::NextLanguage => "next_language",
+            Action::NextModel => "next_model",
+            Action::SaveSpeech => "save_speech",
+        }
+    }
+
+    /// Key bound unless the config says otherwise, "" = unbound
+    fn default_key(self) -> &'static str {
+        match self {
+            Action::Record => "F9",
+            Action::Speak => "F10",
+            Action::Accept => "F7",
+            Action::Skip => "F8",
+            Action::ResetLanguage => "F6",
+            Action::Reload => "F11",
+            Action::Diagnostics => "F12",
+            Action::Cancel => "Esc",
+            Action::NextVoice
+            | Action::Faster
+            | Action::Slower
+            | Action::Pause
+            | Action::Translate
+            | Action::NextLanguage
+            | Action::NextModel
+            | Action::SaveSpeech => "",
+        }
+    }
+}
+
+/// The key bound to each action; an action without a key can't be triggered
+#[derive(Debug, Clone)]
+pub struct Hotkeys {
+    keys: [Option<Hotkey>; Action::ALL.len()],
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Self {
+            keys: Action::ALL.map(|action| {
+                let key = action.default_key();
+                (!key.is_empty()).then(|| Hotkey::parse(key).expect("default keys are valid"))
+            }),
+        }
+    }
+}
+
+impl Hotkeys {
+    /// Bind the action called `action` to `key`; an empty `key` unbinds it
+    /// Errors name the valid actions or keys.
+    pub fn set(&mut self, action: &str, key: &str) -> Result<()> {
+        let action = Action::ALL
+            .into_iter()
+            .find(|known| known.name().eq_ignore_ascii_case(action))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Action::ALL.iter().map(|known| known.name()).collect();
+                anyhow!(
+                    "unknown hotkey action '{}', valid actions are: {}",
+                    action,
+                    names.join(", ")
+                )
+            })?;
+        self.keys[action as usize] = if key.trim().is_empty() {
+            None
+        } else {
+            Some(Hotkey::parse(key)?)
+        };
+        Ok(())
+    }
+
+    pub fn get(&self, action: Action) -> Option<&Hotkey> {
+        self.keys[action as usize].as_ref()
+    }
+
+    /// Whether the key bound to `action` is held (false if it has none)
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.get(action).is_some_and(Hotkey::is_pressed)
+    }
+
+    /// Key name for messages, e.g. "F9"
+    pub fn label(&self, action: Action) -> &str {
+        self.get(action).map_or("(unbound)", Hotkey::name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_millis(700);
+
+    #[test]
+    fn second_press_within_the_window_is_a_double_tap() {
+        let start = Instant::now();
+        let mut taps = DoubleTapDetector::new(WINDOW);
+        assert_eq!(taps.press(start), None);
+        assert!(taps.is_pending());
+        assert_eq!(taps.poll(start + Duration::from_millis(300)), None);
+        assert_eq!(
+            taps.press(start + Duration::from_millis(400)),
+            Some(Tap::Double)
+        );
+        assert!(!taps.is_pending());
+        assert_eq!(taps.poll(start + Duration::from_secs(2)), None);
+    }
+
+    #[test]
+    fn lone_press_is_a_single_tap_once_the_window_passes() {
+        let start = Instant::now();
+        let mut taps = DoubleTapDetector::new(WINDOW);
+        assert_eq!(taps.press(start), None);
+        assert_eq!(taps.poll(start + WINDOW), None);
+        assert_eq!(
+            taps.poll(start + Duration::from_millis(701)),
+            Some(Tap::Single)
+        );
+        assert!(!taps.is_pending());
+    }
+
+    #[test]
+    fn late_second_press_starts_a_new_tap() {
+        let start = Instant::now();
+        let mut taps = DoubleTapDetector::new(WINDOW);
+        assert_eq!(taps.press(start), None);
+        // Not polled in between, e.g. while the loop was busy
+        assert_eq!(taps.press(start + Duration::from_millis(900)), None);
+        assert!(taps.is_pending());
+        assert_eq!(
+            taps.press(start + Duration::from_millis(1000)),
+            Some(Tap::Double)
+        );
+    }
+
+    #[test]
+    fn zero_window_makes_every_press_a_single_tap() {
+        let start = Instant::now();
+        let mut taps = DoubleTapDetector::new(Duration::ZERO);
+        assert_eq!(taps.press(start), Some(Tap::Single));
+        assert_eq!(taps.press(start), Some(Tap::Single));
+        assert!(!taps.is_pending());
+    }
+
+    #[test]
+    fn repeated_press_within_the_debounce_is_ignored_with_its_release() {
+        let mut key = EdgeDetector::new(Duration::from_secs(60));
+        assert_eq!(key.update(true), KeyEdge::Pressed);
+        assert_eq!(key.update(true), KeyEdge::None);
+        assert_eq!(key.update(false), KeyEdge::Released);
+        assert_eq!(key.update(true), KeyEdge::None);
+        assert_eq!(key.update(false), KeyEdge::None);
+
+        let mut undebounced = EdgeDetector::new(Duration::ZERO);
+        assert_eq!(undebounced.update(true), KeyEdge::Pressed);
+        assert_eq!(undebounced.update(false), KeyEdge::Released);
+        assert_eq!(undebounced.update(true), KeyEdge::Pressed);
+    }
+
+    #[test]
+    fn hotkeys_are_ignored_while_synthetic_input_is_sent() {
+        let window = Duration::from_millis(150);
+        let mut guard = SyntheticInputGuard::new(window);
+        let start = Instant::now();
+        assert!(!guard.is_active(start));
+
+        guard.arm(start);
+        assert!(guard.is_active(start));
+        assert!(guard.is_active(start + window - Duration::from_millis(1)));
+        assert!(!guard.is_active(start + window));
+
+        // Typing more text extends the window
+        guard.arm(start + window);
+        assert!(guard.is_active(start + window));
+        assert!(!guard.is_active(start + window * 2));
+
+        let mut off = SyntheticInputGuard::new(Duration::ZERO);
+        off.arm(start);
+        assert!(!off.is_active(start));
+    }
+}
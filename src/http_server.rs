@@ -0,0 +1,328 @@
+use crate::audio::{self, Preprocessing, Recording};
+use crate::transcribe::{Segment, Task, TranscribeError, Transcriber};
+use anyhow::{anyhow, Result};
+use axum::body::{Body, Bytes};
+use axum::extract::{DefaultBodyLimit, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::io::Cursor;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio_stream::{Stream, StreamExt};
+use tracing::warn;
+
+/// Largest WAV accepted, about 9 minutes of 48 kHz stereo 16-bit audio
+const MAX_BODY_BYTES: usize = 100 * 1024 * 1024;
+
+struct ServerState {
+    /// Shared with the dictation loop; the lock makes requests wait for each other
+    transcriber: Arc<Mutex<Transcriber>>,
+    preprocessing: Preprocessing,
+    temp_dir: PathBuf,
+    next_request: AtomicU64,
+}
+
+#[derive(Deserialize)]
+struct TranscribeParams {
+    /// Language to force, e.g. "en"; unset or "auto" detects it
+    language: Option<String>,
+    /// Translate the speech to English instead of transcribing it
+    #[serde(default)]
+    translate: bool,
+}
+
+#[derive(Deserialize)]
+struct SegmentParams {
+    /// Language to force, e.g. "en"; unset or "auto" detects it
+    language: Option<String>,
+}
+
+/// A prepared request's audio, deleted when dropped
+struct TempInput(PathBuf);
+
+impl Drop for TempInput {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Serve `POST /transcribe` on 127.0.0.1:`port`: the body is a WAV file (any rate, channel
+/// count and sample format) and the reply is `{"text":...,"language":...}`
+/// Audio is prepared like a recording and transcribed with the same `Transcriber` as
+/// dictation, one request at a time. `?language=xx` forces the language and
+/// `?translate=true` returns English text.
+///
+/// `POST /segments` replies with the timed segments instead, as
+/// `{"segments":[{"start_ms":...,"end_ms":...,"text":...}]}`, and `POST /segments/stream`
+/// sends the same segment objects one JSON line at a time as whisper prints them.
+pub fn spawn_http_server(
+    port: u16,
+    transcriber: Arc<Mutex<Transcriber>>,
+    preprocessing: Preprocessing,
+    temp_dir: PathBuf,
+) -> Result<()> {
+    // Bind here so a port already in use is reported at startup
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    listener.set_nonblocking(true)?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let state = Arc::new(ServerState {
+        transcriber,
+        preprocessing,
+        temp_dir,
+        next_request: AtomicU64::new(0),
+    });
+    let app = Router::new()
+        .route("/transcribe", post(transcribe))
+        .route("/segments", post(segments))
+        .route("/segments/stream", post(stream_segments))
+        .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
+        .with_state(state);
+
+    std::thread::spawn(move || {
+        runtime.block_on(async move {
+            let listener = match tokio::net::TcpListener::from_std(listener) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    warn!("HTTP transcription server stopped: {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = axum::serve(listener, app).await {
+                warn!("HTTP transcription server stopped: {}", e);
+            }
+        })
+    });
+    Ok(())
+}
+
+async fn transcribe(
+    State(state): State<Arc<ServerState>>,
+    Query(params): Query<TranscribeParams>,
+    body: Bytes,
+) -> (StatusCode, Json<Value>) {
+    let recording = match decode_wav(&body) {
+        Ok(recording) => recording,
+        Err(e) => {
+            let error = format!("Invalid WAV: {}", e);
+            return (StatusCode::BAD_REQUEST, Json(json!({ "error": error })));
+        }
+    };
+    let language = params.language.filter(|l| !l.eq_ignore_ascii_case("auto"));
+    let task = if params.translate {
+        Task::Translate
+    } else {
+        Task::Transcribe
+    };
+    let result = tokio::task::spawn_blocking(move || {
+        state.transcribe(&recording, language.as_deref(), task)
+    })
+    .await
+    .unwrap_or_else(|e| Err(anyhow!("Transcription task failed: {}", e)));
+
+    match result {
+        Ok((text, language)) => (
+            StatusCode::OK,
+            Json(json!({ "text": text, "language": language })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("{:#}", e) })),
+        ),
+    }
+}
+
+async fn segments(
+    State(state): State<Arc<ServerState>>,
+    Query(params): Query<SegmentParams>,
+    body: Bytes,
+) -> (StatusCode, Json<Value>) {
+    let recording = match decode_wav(&body) {
+        Ok(recording) => recording,
+        Err(e) => {
+            let error = format!("Invalid WAV: {}", e);
+            return (StatusCode::BAD_REQUEST, Json(json!({ "error": error })));
+        }
+    };
+    let language = params.language.filter(|l| !l.eq_ignore_ascii_case("auto"));
+    let result =
+        tokio::task::spawn_blocking(move || state.segments(&recording, language.as_deref()))
+            .await
+            .unwrap_or_else(|e| Err(anyhow!("Transcription task failed: {}", e)));
+
+    match result {
+        Ok(segments) => {
+            let segments: Vec<Value> = segments.iter().map(segment_json).collect();
+            (StatusCode::OK, Json(json!({ "segments": segments })))
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("{:#}", e) })),
+        ),
+    }
+}
+
+/// Newline-delimited JSON segments, each sent as soon as whisper prints it; a failure
+/// partway ends the reply with an `{"error":...}` line
+async fn stream_segments(
+    State(state): State<Arc<ServerState>>,
+    Query(params): Query<SegmentParams>,
+    body: Bytes,
+) -> Response {
+    let recording = match decode_wav(&body) {
+        Ok(recording) => recording,
+        Err(e) => {
+            let error = format!("Invalid WAV: {}", e);
+            return (StatusCode::BAD_REQUEST, Json(json!({ "error": error }))).into_response();
+        }
+    };
+    let language = params.language.filter(|l| !l.eq_ignore_ascii_case("auto"));
+    let started =
+        tokio::task::spawn_blocking(move || state.stream_segments(&recording, language.as_deref()))
+            .await
+            .unwrap_or_else(|e| Err(anyhow!("Transcription task failed: {}", e)));
+
+    let segments = match started {
+        Ok(segments) => segments,
+        Err(e) => {
+            let error = json!({ "error": format!("{:#}", e) });
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(error)).into_response();
+        }
+    };
+    let lines = segments.map(|segment| {
+        let line = match segment {
+            Ok(segment) => segment_json(&segment),
+            Err(e) => json!({ "error": format!("{:#}", e) }),
+        };
+        Ok::<_, Infallible>(format!("{}\n", line))
+    });
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response()
+}
+
+/// JSON form of a segment, e.g. `{"start_ms":0,"end_ms":1500,"text":"Hello"}`
+fn segment_json(segment: &Segment) -> Value {
+    json!({
+        "start_ms": segment.start.as_millis() as u64,
+        "end_ms": segment.end.as_millis() as u64,
+        "text": segment.text,
+    })
+}
+
+impl ServerState {
+    /// Write a recording to a new temporary WAV prepared for whisper
+    fn prepare(&self, recording: &Recording) -> Result<TempInput> {
+        let request = self.next_request.fetch_add(1, Ordering::Relaxed);
+        let input = TempInput(self.temp_dir.join(format!(
+            "http_input_{}_{}.wav",
+            std::process::id(),
+            request
+        )));
+        audio::prepare_for_whisper(recording, &self.preprocessing, &input.0)?;
+        Ok(input)
+    }
+
+    /// Text and detected language of a recording; no speech gives empty text
+    fn transcribe(
+        &self,
+        recording: &Recording,
+        language: Option<&str>,
+        task: Task,
+    ) -> Result<(String, Option<String>)> {
+        let input = self.prepare(recording)?;
+        let transcriber = self.transcriber.lock().unwrap_or_else(|e| e.into_inner());
+        let result = transcriber.transcribe(&input.0, language, task, &|| false);
+        drop(transcriber);
+
+        match result {
+            Ok(transcription) => Ok((transcription.text, transcription.language)),
+            Err(e) if matches!(e.downcast_ref(), Some(TranscribeError::NoSpeech)) => {
+                Ok((String::new(), None))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Timed segments of a recording; no speech gives none
+    fn segments(&self, recording: &Recording, language: Option<&str>) -> Result<Vec<Segment>> {
+        let input = self.prepare(recording)?;
+        let transcriber = self.transcriber.lock().unwrap_or_else(|e| e.into_inner());
+        match transcriber.transcribe_segments(&input.0, language, &|| false) {
+            Err(e) if matches!(e.downcast_ref(), Some(TranscribeError::NoSpeech)) => Ok(Vec::new()),
+            result => result,
+        }
+    }
+
+    /// Start streaming the segments of a recording; the transcriber is only locked to
+    /// start whisper, the limit on heavy operations still applies
+    fn stream_segments(
+        &self,
+        recording: &Recording,
+        language: Option<&str>,
+    ) -> Result<impl Stream<Item = Result<Segment>>> {
+        let input = self.prepare(recording)?;
+        let transcriber = self.transcriber.lock().unwrap_or_else(|e| e.into_inner());
+        let segments = transcriber.transcribe_stream(&input.0, language)?;
+        // Whisper reads the input until the stream ends, so it is deleted only then
+        Ok(segments.map(move |segment| {
+            let _ = &input;
+            segment
+        }))
+    }
+}
+
+/// Decode a WAV file of any sample format into a mono recording
+fn decode_wav(bytes: &[u8]) -> Result<Recording> {
+    let mut reader = hound::WavReader::new(Cursor::new(bytes))?;
+    let spec = reader.spec();
+    let interleaved = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+    let mut samples = Vec::new();
+    audio::extend_mono(&mut samples, &interleaved, spec.channels);
+    if samples.is_empty() {
+        return Err(anyhow!("no audio samples"));
+    }
+    Ok(Recording {
+        samples,
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn segments_are_sent_with_millisecond_offsets() {
+        let segment = Segment {
+            start: Duration::from_millis(1500),
+            end: Duration::from_secs(3),
+            text: "Hello there".to_string(),
+        };
+        assert_eq!(
+            segment_json(&segment),
+            json!({ "start_ms": 1500, "end_ms": 3000, "text": "Hello there" })
+        );
+    }
+}
@@ -0,0 +1,99 @@
+/// Pins whisper's language once auto-detection keeps returning the same one
+///
+/// Auto-detection costs time on every run. After `pin_after` consecutive recordings
+/// detected as the same language, that language is passed to whisper explicitly
+/// until `reset` is called. A `pin_after` of 0 disables pinning.
+#[derive(Debug, Default)]
+pub struct LanguagePin {
+    pin_after: u32,
+    candidate: Option<String>,
+    streak: u32,
+    pinned: Option<String>,
+}
+
+impl LanguagePin {
+    pub fn new(pin_after: u32) -> Self {
+        Self {
+            pin_after,
+            ..Self::default()
+        }
+    }
+
+    /// Language to pass to whisper, None = auto-detect
+    pub fn language(&self) -> Option<&str> {
+        self.pinned.as_deref()
+    }
+
+    /// Record a detected language, returning true if this detection pinned it
+    pub fn observe(&mut self, detected: &str) -> bool {
+        if self.pin_after == 0 || self.pinned.is_some() {
+            return false;
+        }
+
+        if self.candidate.as_deref() == Some(detected) {
+            self.streak += 1;
+        } else {
+            self.candidate = Some(detected.to_string());
+            self.streak = 1;
+        }
+
+        if self.streak >= self.pin_after {
+            self.pinned = self.candidate.take();
+            self.streak = 0;
+            return true;
+        }
+        false
+    }
+
+    /// Go back to auto-detection and start counting from scratch
+    pub fn reset(&mut self) {
+        self.candidate = None;
+        self.streak = 0;
+        self.pinned = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_is_pinned_after_consecutive_detections() {
+        let mut pin = LanguagePin::new(3);
+        assert!(!pin.observe("en"));
+        assert!(!pin.observe("en"));
+        // A different language restarts the streak
+        assert!(!pin.observe("pt"));
+        assert!(!pin.observe("en"));
+        assert!(!pin.observe("en"));
+        assert_eq!(pin.language(), None);
+        assert!(pin.observe("en"));
+        assert_eq!(pin.language(), Some("en"));
+
+        // Once pinned, other detections don't move it
+        assert!(!pin.observe("pt"));
+        assert_eq!(pin.language(), Some("en"));
+    }
+
+    #[test]
+    fn reset_goes_back_to_auto_detection() {
+        let mut pin = LanguagePin::new(2);
+        pin.observe("pt");
+        pin.observe("pt");
+        assert_eq!(pin.language(), Some("pt"));
+
+        pin.reset();
+        assert_eq!(pin.language(), None);
+        assert!(!pin.observe("pt"));
+        assert!(pin.observe("pt"));
+    }
+
+    #[test]
+    fn zero_never_pins() {
+        let mut pin = LanguagePin::new(0);
+        for _ in 0..10 {
+            assert!(!pin.observe("en"));
+        }
+        assert_eq!(pin.language(), None);
+    }
+}
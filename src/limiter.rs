@@ -0,0 +1,172 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+use tracing::info;
+
+/// How often a waiting `acquire_unless` checks its cancel flag
+const CANCEL_POLL: Duration = Duration::from_millis(20);
+
+/// Caps how many CPU-heavy operations (whisper runs, Piper synthesis) run at once
+///
+/// A counting semaphore shared by everything that starts one: `acquire` blocks while
+/// `limit` operations are already running. A limit of 0 means no limit.
+pub struct HeavyOpLimiter {
+    state: Mutex<LimiterState>,
+    released: Condvar,
+}
+
+struct LimiterState {
+    running: usize,
+    limit: usize,
+}
+
+/// One running heavy operation; its slot is freed when dropped
+pub struct Permit<'a> {
+    limiter: &'a HeavyOpLimiter,
+}
+
+impl HeavyOpLimiter {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            state: Mutex::new(LimiterState { running: 0, limit }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Change the limit; operations already running keep their slot
+    pub fn set_limit(&self, limit: usize) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).limit = limit;
+        self.released.notify_all();
+    }
+
+    /// Wait for a free slot; `operation` names what is waiting, for the log
+    pub fn acquire(&self, operation: &str) -> Permit<'_> {
+        let never = AtomicBool::new(false);
+        self.acquire_unless(operation, &never)
+            .expect("waiting without a cancel flag is never cancelled")
+    }
+
+    /// Like `acquire`, but give up and return None once `cancelled` is set
+    pub fn acquire_unless(&self, operation: &str, cancelled: &AtomicBool) -> Option<Permit<'_>> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.limit > 0 && state.running >= state.limit {
+            info!(
+                "Waiting for another transcription/synthesis before starting {}...",
+                operation
+            );
+            while state.limit > 0 && state.running >= state.limit {
+                if cancelled.load(Ordering::Relaxed) {
+                    return None;
+                }
+                state = self
+                    .released
+                    .wait_timeout(state, CANCEL_POLL)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+            }
+        }
+        state.running += 1;
+        Some(Permit { limiter: self })
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.running = state.running.saturating_sub(1);
+        drop(state);
+        self.limiter.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn waiting_for_a_slot_gives_up_when_cancelled() {
+        let limiter = Arc::new(HeavyOpLimiter::new(1));
+        let _held = limiter.acquire("transcription");
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let waiter = {
+            let limiter = limiter.clone();
+            let cancelled = cancelled.clone();
+            thread::spawn(move || {
+                limiter
+                    .acquire_unless("speech synthesis", &cancelled)
+                    .is_some()
+            })
+        };
+        thread::sleep(Duration::from_millis(50));
+        cancelled.store(true, Ordering::Relaxed);
+        assert!(!waiter.join().unwrap());
+    }
+
+    #[test]
+    fn waiting_for_a_slot_gets_it_once_released() {
+        let limiter = Arc::new(HeavyOpLimiter::new(1));
+        let held = limiter.acquire("transcription");
+        let waiter = {
+            let limiter = limiter.clone();
+            thread::spawn(move || {
+                let cancelled = AtomicBool::new(false);
+                limiter
+                    .acquire_unless("speech synthesis", &cancelled)
+                    .is_some()
+            })
+        };
+        thread::sleep(Duration::from_millis(50));
+        drop(held);
+        assert!(waiter.join().unwrap());
+    }
+
+    #[test]
+    fn no_limit_never_waits() {
+        let limiter = HeavyOpLimiter::new(0);
+        let cancelled = AtomicBool::new(true);
+        let _first = limiter.acquire("transcription");
+        assert!(limiter
+            .acquire_unless("speech synthesis", &cancelled)
+            .is_some());
+    }
+
+    #[test]
+    fn second_heavy_op_waits_while_the_limit_is_reached() {
+        let limiter = Arc::new(HeavyOpLimiter::new(1));
+        let held = limiter.acquire("transcription");
+        let waiter = {
+            let limiter = limiter.clone();
+            thread::spawn(move || {
+                let _permit = limiter.acquire("speech synthesis");
+            })
+        };
+        thread::sleep(Duration::from_millis(100));
+        assert!(!waiter.is_finished());
+
+        drop(held);
+        waiter.join().unwrap();
+        // The slot is free again once the second op is done
+        let _first = limiter.acquire("transcription");
+    }
+
+    #[test]
+    fn slot_is_freed_even_after_the_lock_was_poisoned() {
+        let limiter = Arc::new(HeavyOpLimiter::new(1));
+        let permit = limiter.acquire("transcription");
+        let poisoner = limiter.clone();
+        let _ = thread::spawn(move || {
+            let _state = poisoner.state.lock().unwrap();
+            panic!("poison the limiter's lock");
+        })
+        .join();
+        assert!(limiter.state.is_poisoned());
+
+        drop(permit);
+        let cancelled = AtomicBool::new(true);
+        assert!(limiter
+            .acquire_unless("speech synthesis", &cancelled)
+            .is_some());
+    }
+}
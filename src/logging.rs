@@ -0,0 +1,138 @@
+//! Log output: the console as before, plus an optional rotating log file
+
+use std::fmt;
+use std::path::Path;
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// This crate's log target; other crates (HTTP server, audio) only log warnings and errors
+const TARGET: &str = env!("CARGO_CRATE_NAME");
+
+/// Log files are named `local_tts.<date>.log` (or `local_tts.log` without rotation)
+const FILE_PREFIX: &str = "local_tts";
+
+/// When to start a new log file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogRotation {
+    Daily,
+    Hourly,
+    Never,
+}
+
+impl From<LogRotation> for Rotation {
+    fn from(rotation: LogRotation) -> Self {
+        match rotation {
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+/// Send log events to the console and, with `dir` set, to rotating files in it
+/// Warnings and errors go to stderr, everything else to stdout, with the same
+/// "WARNING:" / "[DEBUG]" prefixes the console always had. Files get a timestamp and
+/// level on every line and keep at most `max_files` (0 = all). The returned guard
+/// flushes the file when dropped, so it must live until the program exits.
+pub fn init(dir: Option<&Path>, rotation: LogRotation, max_files: usize) -> Option<WorkerGuard> {
+    let mut guard = None;
+    let mut file_error = None;
+    let file = dir.and_then(|dir| {
+        let mut builder = RollingFileAppender::builder()
+            .rotation(rotation.into())
+            .filename_prefix(FILE_PREFIX)
+            .filename_suffix("log");
+        if max_files > 0 {
+            builder = builder.max_log_files(max_files);
+        }
+        match builder.build(dir) {
+            Ok(appender) => {
+                let (writer, flush_guard) = tracing_appender::non_blocking(appender);
+                guard = Some(flush_guard);
+                let layer = tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_target(false)
+                    .with_writer(writer)
+                    .with_filter(filter());
+                Some(layer)
+            }
+            Err(e) => {
+                file_error = Some(e);
+                None
+            }
+        }
+    });
+
+    tracing_subscriber::registry()
+        .with(console_layer())
+        .with(file)
+        .init();
+    if let Some(dir) = dir {
+        match file_error {
+            Some(e) => tracing::warn!("Can't write logs to '{}': {}", dir.display(), e),
+            None => tracing::info!("Logging to '{}'.", dir.display()),
+        }
+    }
+    guard
+}
+
+/// Console-only logging for what happens before `init`, such as warnings while loading
+/// the config that says where the log file goes; use with `tracing::subscriber::with_default`
+pub fn console() -> impl Subscriber + Send + Sync {
+    tracing_subscriber::registry().with(console_layer())
+}
+
+/// Debug messages from this crate, only warnings and errors from others
+fn filter() -> Targets {
+    Targets::new()
+        .with_target(TARGET, LevelFilter::DEBUG)
+        .with_default(LevelFilter::WARN)
+}
+
+/// Warnings and errors to stderr, the rest to stdout
+fn console_layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_subscriber::fmt::layer()
+        .event_format(ConsoleFormat)
+        .with_writer(
+            std::io::stderr
+                .with_max_level(Level::WARN)
+                .or_else(std::io::stdout),
+        )
+        .with_filter(filter())
+}
+
+/// Console lines as plain messages, prefixed by level like the old prints
+struct ConsoleFormat;
+
+impl<S, N> FormatEvent<S, N> for ConsoleFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        match *event.metadata().level() {
+            Level::WARN => write!(writer, "WARNING: ")?,
+            Level::DEBUG | Level::TRACE => write!(writer, "[DEBUG] ")?,
+            Level::ERROR | Level::INFO => {}
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
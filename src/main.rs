@@ -1,19 +1,104 @@
 mod audio;
 mod clipboard;
+mod clipboard_history;
+mod code_mode;
+mod config;
+mod dataset;
+mod diagnostics;
+mod disk;
+mod events;
+mod focus;
+mod hotkeys;
+mod http_server;
+mod language_pin;
+mod limiter;
+mod logging;
+mod merge;
+mod meter;
 mod narrate;
+mod notify;
+mod piper_server;
+mod postprocess;
+mod power;
+mod preview;
+mod process;
+mod punctuate;
+mod toml_config;
 mod transcribe;
+mod tray;
+mod vad;
+mod voice_commands;
+#[cfg(feature = "whisper-rs")]
+mod whisper_lib;
+#[cfg(feature = "punctuate-onnx")]
+mod onnx_punctuator;
+mod worker;
 
 use anyhow::Result;
-use audio::{get_device_by_index, list_input_devices, AudioRecorder};
+use audio::{list_input_devices, AudioRecorder};
 use clipboard::ClipboardManager;
+use clipboard_history::ClipboardHistory;
+use config::{AppConfig, BusyPolicy, NoSpeechAction, RecordMode};
+use cpal::traits::DeviceTrait;
+use dataset::{DatasetExporter, EntryStatus};
+use diagnostics::{LogTail, RecordingStats};
+use events::{Event, EventBus, Ticker};
+use focus::WindowHandle;
+use hotkeys::{Action, DoubleTapDetector, EdgeDetector, KeyEdge, SyntheticInputGuard, Tap};
 use inputbot::KeybdKey;
-use narrate::{Narrator, NarratorConfig};
+use language_pin::LanguagePin;
+use limiter::HeavyOpLimiter;
+use merge::MergeWindow;
+use meter::LevelMeter;
+use narrate::{Narrator, NarratorConfig, SPEED_STEP};
+use preview::{Preview, PreviewAction};
 use std::io::{self, Write};
-use std::time::Duration;
-use transcribe::Transcriber;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, TryLockError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+use transcribe::{Task, TranscribeError, Transcriber};
+use tray::{TrayCommand, TrayState};
+use worker::TranscriptionWorker;
 
-fn select_microphone() -> Result<usize> {
-    let devices = list_input_devices()?;
+/// Activity lines kept for the diagnostics report
+const DIAGNOSTICS_LOG_LINES: usize = 50;
+
+/// How long shutting down waits for a cancelled transcription to end
+const SHUTDOWN_WAIT: Duration = Duration::from_secs(2);
+
+/// Number-row keys that pick a clipboard history slot (with Ctrl+Shift)
+const HISTORY_SLOT_KEYS: [KeybdKey; 9] = [
+    KeybdKey::Numrow1Key,
+    KeybdKey::Numrow2Key,
+    KeybdKey::Numrow3Key,
+    KeybdKey::Numrow4Key,
+    KeybdKey::Numrow5Key,
+    KeybdKey::Numrow6Key,
+    KeybdKey::Numrow7Key,
+    KeybdKey::Numrow8Key,
+    KeybdKey::Numrow9Key,
+];
+
+/// Microphone named on the command line with `--mic "Name"` (or `--mic=Name`)
+fn mic_arg() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--mic" {
+            return args.next();
+        }
+        if let Some(name) = arg.strip_prefix("--mic=") {
+            return Some(name.to_string());
+        }
+    }
+    None
+}
+
+/// Ask which microphone to use, returning its name
+fn select_microphone() -> Result<String> {
+    let mut devices = list_input_devices()?;
 
     if devices.is_empty() {
         return Err(anyhow::anyhow!("No input devices found"));
@@ -33,8 +118,9 @@ fn select_microphone() -> Result<usize> {
 
         if let Ok(num) = input.trim().parse::<usize>() {
             if num >= 1 && num <= devices.len() {
-                println!("Selected: {}", devices[num - 1]);
-                return Ok(num - 1);
+                let name = devices.swap_remove(num - 1);
+                println!("Selected: {}", name);
+                return Ok(name);
             }
         }
         println!("Invalid selection. Please try again.");
@@ -43,139 +129,1469 @@ fn select_microphone() -> Result<usize> {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    println!("Starting Local TTS Tool...");
+    // The log file isn't known until the config is loaded, so its warnings go to the console
+    let mut config = tracing::subscriber::with_default(logging::console(), AppConfig::load)?;
+    // Flushes the log file when main returns
+    let _log_guard = logging::init(
+        config.log_dir.as_deref(),
+        config.log_rotation,
+        config.log_max_files,
+    );
+    info!("Starting Local TTS Tool...");
 
     // 1. Initialize Components
-    let path_to_model = "ggml-large-v3-turbo.bin"; // Best model with CUDA acceleration
-    if !std::path::Path::new(path_to_model).exists() {
-        eprintln!("ERROR: Model file '{}' not found!", path_to_model);
-        eprintln!("Please download a ggml model (e.g. from https://huggingface.co/ggerganov/whisper.cpp) and place it in the project root.");
+    let path_to_model = &config.whisper_model;
+    if !path_to_model.exists() {
+        error!("Model file '{}' not found!", path_to_model.display());
+        error!("Please download a ggml model (e.g. from https://huggingface.co/ggerganov/whisper.cpp) and place it in the project root.");
         return Ok(());
     }
+    let path_to_model = local_model_path(&config, path_to_model)?;
+    let battery_model = match &config.whisper_model_battery {
+        Some(path) if path.exists() => Some(local_model_path(&config, path)?),
+        Some(path) => {
+            warn!(
+                "Battery model '{}' not found, using '{}' on battery too.",
+                path.display(),
+                config.whisper_model.display()
+            );
+            None
+        }
+        None => None,
+    };
+    // Models for the next-model key, the default first; copied to the cache when chosen
+    let mut models: Vec<(String, PathBuf)> =
+        vec![("default".to_string(), config.whisper_model.clone())];
+    for (name, path) in &config.whisper_models {
+        if path.exists() {
+            models.push((name.clone(), path.clone()));
+        } else {
+            warn!(
+                "Whisper model '{}' ({}) not found, skipping it.",
+                name,
+                path.display()
+            );
+        }
+    }
+    let mut model_index = 0;
+    // Model used on AC power, and on battery without WHISPER_MODEL_BATTERY
+    let mut active_model = path_to_model.clone();
 
-    // Select microphone
-    let device_index = select_microphone()?;
-    let device = get_device_by_index(device_index)?;
+    // Select microphone: --mic, then DEVICE, then the one picked last time, then ask
+    let device = match mic_arg().or_else(|| config.device.clone()).as_deref() {
+        Some("default") => {
+            let device = audio::default_input_device()?;
+            info!(
+                "Using default microphone: {}",
+                device.name().unwrap_or_else(|_| "unknown".to_string())
+            );
+            device
+        }
+        Some(name) => {
+            let device = audio::get_device_by_name(name)?;
+            info!(
+                "Using microphone: {}",
+                device.name().unwrap_or_else(|_| name.to_string())
+            );
+            device
+        }
+        None => {
+            let last_used =
+                config
+                    .last_microphone()
+                    .and_then(|name| match audio::get_device_by_name(&name) {
+                        Ok(device) => {
+                            info!("Using microphone: {} (last used)", name);
+                            Some(device)
+                        }
+                        Err(_) => {
+                            info!("Last used microphone '{}' is not connected.", name);
+                            None
+                        }
+                    });
+            match last_used {
+                Some(device) => device,
+                None => {
+                    let name = select_microphone()?;
+                    let device = audio::get_device_by_name(&name)?;
+                    config.save_last_microphone(&name);
+                    device
+                }
+            }
+        }
+    };
 
     let mut recorder = AudioRecorder::new();
     recorder.set_device(device);
-    let transcriber = match Transcriber::new(path_to_model) {
-        Ok(t) => t,
+    if config.audio_start_delay_ms > 0 {
+        // Give slow USB interfaces time to come up before the first recording
+        std::thread::sleep(Duration::from_millis(config.audio_start_delay_ms));
+    }
+    // Shared with the HTTP server, which must not run whisper at the same time
+    let transcriber = match Transcriber::new(
+        &path_to_model,
+        config.whisper_backend,
+        config.whisper_gpu,
+        config.whisper_mmap,
+    ) {
+        Ok(t) => Arc::new(Mutex::new(t)),
         Err(e) => {
-            eprintln!("Failed to initialize Whisper: {}", e);
+            error!("Failed to initialize Whisper: {}", e);
             return Ok(());
         }
     };
     let mut clipboard_mgr = ClipboardManager::new()?;
+    apply_live_settings(&config, &mut recorder, &mut clipboard_mgr);
+    apply_transcriber_settings(&config, &mut lock_transcriber(&transcriber));
+
+    // Shared by whisper and Piper so they queue instead of competing for the CPU
+    let heavy_ops = Arc::new(HeavyOpLimiter::new(config.max_heavy_ops));
+    lock_transcriber(&transcriber).set_limiter(heavy_ops.clone());
 
     // Initialize TTS narrator (optional - will warn if not configured)
-    let narrator: Option<Narrator> = match NarratorConfig::load() {
+    let mut narrator: Option<Narrator> = match NarratorConfig::load(&config) {
         Ok(config) => {
-            println!("TTS narrator initialized with Piper.");
-            Some(Narrator::new(config))
+            info!("TTS narrator initialized with Piper.");
+            let mut narrator = Narrator::new(config);
+            narrator.set_limiter(heavy_ops.clone());
+            Some(narrator)
         }
         Err(e) => {
-            eprintln!("WARNING: TTS narrator not available: {}", e);
-            eprintln!("F10 text-to-speech will be disabled.");
+            warn!("TTS narrator not available: {}", e);
+            error!("Text-to-speech will be disabled.");
             None
         }
     };
 
+    if config.speak_transcription && narrator.is_none() {
+        warn!("SPEAK_TRANSCRIPTION is on but TTS is not available, transcriptions won't be read aloud.");
+    }
+
+    config.restore_last_profile();
+    if config.profiles.len() > 1 {
+        info!("Using profile '{}'.", config.profile().name);
+    }
+
+    // External listeners (e.g. a streaming overlay) get events over UDP
+    let events = EventBus::new();
+    if let Some(target) = &config.events_udp_target {
+        match events::spawn_udp_forwarder(&events, target) {
+            Ok(()) => info!("Sending events to {} over UDP.", target),
+            Err(e) => warn!("Failed to set up UDP events to {}: {}", target, e),
+        }
+    }
+    if config.events_websocket_port > 0 {
+        let port = config.events_websocket_port;
+        match events::spawn_websocket_server(&events, port) {
+            Ok(()) => info!("Serving events on ws://127.0.0.1:{}.", port),
+            Err(e) => warn!(
+                "Failed to start the websocket server on port {}: {}",
+                port, e
+            ),
+        }
+    }
+    if config.http_port > 0 {
+        let port = config.http_port;
+        match http_server::spawn_http_server(
+            port,
+            Arc::clone(&transcriber),
+            config.preprocessing(),
+            disk::temp_dir(config.temp_dir.as_deref()),
+        ) {
+            Ok(()) => info!(
+                "Transcribing WAV files POSTed to http://127.0.0.1:{}/transcribe.",
+                port
+            ),
+            Err(e) => warn!("Failed to start the HTTP server on port {}: {}", port, e),
+        }
+    }
+    if let Some(narrator) = narrator.as_mut() {
+        narrator.set_events(events.clone());
+    }
+    let mut level_ticker = Ticker::new(Duration::from_millis(config.level_interval_ms));
+    let mut level_meter = LevelMeter::default();
+    let mut language_pin = LanguagePin::new(config.pin_language_after);
+    let mut dataset = dataset_exporter(&config);
+    // Dataset retention runs on the first loop iteration and then hourly
+    let mut retention_ticker = Ticker::new(Duration::from_secs(60 * 60));
+    // Prepared audio of the latest recording and its length; each recording gets a file of
+    // its own (process id and counter), deleted once it has been transcribed and exported
+    let temp_dir = disk::temp_dir(config.temp_dir.as_deref());
+    let mut recordings_prepared: u64 = 0;
+    let mut temp_path = PathBuf::new();
+    let mut clip_duration = Duration::ZERO;
+    let mut clipboard_history = ClipboardHistory::new(config.clipboard_history);
+    let mut clipboard_ticker = Ticker::new(Duration::from_millis(500));
+    // Call app in the foreground that mutes TTS (MUTE_TTS_APPS), checked twice a second
+    let mut muting_app: Option<String> = None;
+    let mut muting_ticker = Ticker::new(Duration::from_millis(500));
+    // Window that had focus when recording started, so the paste lands there
+    let mut paste_target = None;
+    // Whisper runs here, one recording at a time; a record press meanwhile may be queued
+    let mut transcription_worker = TranscriptionWorker::new(transcriber.clone());
+    let mut record_queued = false;
+    // A spoken end command (END_COMMAND) stops toggle-mode dictation like a record press
+    let mut end_requested = false;
+
+    let keys = &config.hotkeys;
     println!("\nHotkeys:");
-    println!("  F9  - Hold to record, release to transcribe (Speech-to-Text)");
+    match config.record_mode {
+        RecordMode::Hold => println!(
+            "  {:<3} - Hold to record, release to transcribe (Speech-to-Text)",
+            keys.label(Action::Record)
+        ),
+        RecordMode::Toggle => println!(
+            "  {:<3} - Press to start recording, press again to transcribe (Speech-to-Text)",
+            keys.label(Action::Record)
+        ),
+    }
+    println!(
+        "  {:<3} - Cancel a transcription in progress",
+        keys.label(Action::Cancel)
+    );
+    if config.pin_language_after > 0 {
+        println!(
+            "  {:<3} - Go back to language auto-detection after it was pinned",
+            keys.label(Action::ResetLanguage)
+        );
+    }
+    if keys.get(Action::NextLanguage).is_some() && !config.languages.is_empty() {
+        println!(
+            "  {:<3} - Switch the dictation language ({}, then auto-detect)",
+            keys.label(Action::NextLanguage),
+            config.languages.join(", ")
+        );
+    }
+    if keys.get(Action::NextModel).is_some() && !config.whisper_models.is_empty() {
+        println!(
+            "  {:<3} - Switch to the next Whisper model",
+            keys.label(Action::NextModel)
+        );
+    }
+    if keys.get(Action::Translate).is_some() {
+        println!(
+            "  {:<3} - Switch between transcribing and translating to English",
+            keys.label(Action::Translate)
+        );
+    }
+    if config.preview {
+        println!(
+            "  {:<3} - Paste the previewed transcription",
+            keys.label(Action::Accept)
+        );
+        println!(
+            "  {:<3} - Skip the previewed transcription ({} re-records)",
+            keys.label(Action::Skip),
+            keys.label(Action::Record)
+        );
+    }
     if narrator.is_some() {
-        println!("  F10 - Read selected text aloud (Text-to-Speech)");
-        println!("        Press F10 again while playing to stop");
+        let speak = keys.label(Action::Speak);
+        println!("  {:<3} - Read selected text aloud (Text-to-Speech)", speak);
+        println!("        Press {} again while playing to stop", speak);
+        if config.double_tap_ms > 0 {
+            println!(
+                "        Double-tap {} while playing to skip to the next utterance",
+                speak
+            );
+        }
+        if keys.get(Action::Pause).is_some() {
+            println!(
+                "  {:<3} - Pause/resume reading aloud",
+                keys.label(Action::Pause)
+            );
+        }
+    }
+    if let Some(narrator) = narrator.as_ref() {
+        if keys.get(Action::NextVoice).is_some() && !narrator.config().voices.is_empty() {
+            println!(
+                "  {:<3} - Switch to the next TTS voice",
+                keys.label(Action::NextVoice)
+            );
+        }
+    }
+    if narrator.is_some() && keys.get(Action::SaveSpeech).is_some() {
+        println!(
+            "  {:<3} - Save the selection as speech to a WAV file",
+            keys.label(Action::SaveSpeech)
+        );
+    }
+    if narrator.is_some()
+        && (keys.get(Action::Faster).is_some() || keys.get(Action::Slower).is_some())
+    {
+        println!(
+            "  {}/{} - Make TTS speech faster/slower",
+            keys.label(Action::Faster),
+            keys.label(Action::Slower)
+        );
     }
+    if narrator.is_some() && config.clipboard_history > 0 {
+        println!(
+            "  Ctrl+Shift+1-{} - Read a recent clipboard entry aloud (1 = current)",
+            config.clipboard_history.min(HISTORY_SLOT_KEYS.len())
+        );
+    }
+    println!(
+        "  {:<3} - Reload {}",
+        keys.label(Action::Reload),
+        config::config_file()
+    );
+    if keys.get(Action::Diagnostics).is_some() {
+        println!(
+            "  {:<3} - Write a diagnostics report for bug reports",
+            keys.label(Action::Diagnostics)
+        );
+    }
+    println!("  Ctrl+C - Quit");
     println!("\nListening...");
 
-    let mut was_f9_pressed = false;
-    let mut was_f10_pressed = false;
+    // Ctrl+C ends the loop below, which stops recording, whisper and speech on the way out
+    let ctrl_c = Arc::new(AtomicBool::new(false));
+    {
+        let ctrl_c = ctrl_c.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                ctrl_c.store(true, Ordering::Relaxed);
+            }
+        });
+    }
 
-    // Event Loop - poll F9 and F10 key states
+    // Tray menu commands arrive on a channel and are handled in the loop below
+    let (tray_sender, tray_commands) = mpsc::channel();
+    let mut tray = if config.tray {
+        match tray::spawn(tray_sender) {
+            Ok(tray) => Some(tray),
+            Err(e) => {
+                warn!("Failed to create the tray icon: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let mut hotkeys_enabled = true;
+    let mut input_guard = SyntheticInputGuard::new(Duration::from_millis(config.hotkey_guard_ms));
+    let mut merge_window = MergeWindow::new(Duration::from_millis(config.merge_window_ms));
+    // Set when a reload came while whisper was running; applied once it's free
+    let mut transcriber_settings_pending = false;
+    let mut punctuator = punctuate::from_config(&config);
+
+    // Speak is debounced so a held key can't re-trigger (record: see record_debounce)
+    let mut record_key = EdgeDetector::new(record_debounce(&config));
+    let mut speak_key = EdgeDetector::new(Duration::from_millis(config.speak_debounce_ms));
+    let mut speak_taps = DoubleTapDetector::new(Duration::from_millis(config.double_tap_ms));
+    let mut accept_key = EdgeDetector::new(Duration::ZERO);
+    let mut skip_key = EdgeDetector::new(Duration::ZERO);
+    let mut reset_language_key = EdgeDetector::new(Duration::ZERO);
+    let mut reload_key = EdgeDetector::new(Duration::ZERO);
+    let mut next_voice_key = EdgeDetector::new(Duration::ZERO);
+    let mut faster_key = EdgeDetector::new(Duration::ZERO);
+    let mut slower_key = EdgeDetector::new(Duration::ZERO);
+    let mut pause_key = EdgeDetector::new(Duration::ZERO);
+    let mut translate_key = EdgeDetector::new(Duration::ZERO);
+    let mut next_language_key = EdgeDetector::new(Duration::ZERO);
+    let mut next_model_key = EdgeDetector::new(Duration::ZERO);
+    let mut save_speech_key = EdgeDetector::new(Duration::ZERO);
+    // Speech being written to a file by the save_speech key, and where
+    let mut saving_speech: Option<(PathBuf, JoinHandle<Result<()>>)> = None;
+    let mut history_keys: Vec<EdgeDetector> = HISTORY_SLOT_KEYS
+        .iter()
+        .map(|_| EdgeDetector::new(Duration::ZERO))
+        .collect();
+    let mut preview = Preview::default();
+    let mut diagnostics_detector = EdgeDetector::new(Duration::ZERO);
+    let mut activity = LogTail::new(DIAGNOSTICS_LOG_LINES);
+    let mut last_recording: Option<RecordingStats> = None;
+
+    // Event Loop - poll the hotkey states
     loop {
-        let is_f9_pressed = KeybdKey::F9Key.is_pressed();
-        let is_f10_pressed = KeybdKey::F10Key.is_pressed();
+        let keys = &config.hotkeys;
+        let mut record_edge = record_key.update(keys.is_pressed(Action::Record));
+        let mut speak_edge = speak_key.update(keys.is_pressed(Action::Speak));
+        let mut accept_edge = accept_key.update(keys.is_pressed(Action::Accept));
+        let mut skip_edge = skip_key.update(keys.is_pressed(Action::Skip));
+        let mut reset_language_edge =
+            reset_language_key.update(keys.is_pressed(Action::ResetLanguage));
+        let reload_edge = reload_key.update(keys.is_pressed(Action::Reload));
+        let mut next_voice_edge = next_voice_key.update(keys.is_pressed(Action::NextVoice));
+        let mut faster_edge = faster_key.update(keys.is_pressed(Action::Faster));
+        let mut slower_edge = slower_key.update(keys.is_pressed(Action::Slower));
+        let mut pause_edge = pause_key.update(keys.is_pressed(Action::Pause));
+        let mut translate_edge = translate_key.update(keys.is_pressed(Action::Translate));
+        let mut next_language_edge =
+            next_language_key.update(keys.is_pressed(Action::NextLanguage));
+        let mut next_model_edge = next_model_key.update(keys.is_pressed(Action::NextModel));
+        let mut save_speech_edge = save_speech_key.update(keys.is_pressed(Action::SaveSpeech));
+        let mut diagnostics_edge =
+            diagnostics_detector.update(keys.is_pressed(Action::Diagnostics));
 
-        // F9 handling - Speech-to-Text
-        if is_f9_pressed && !was_f9_pressed {
-            // Key just pressed - start recording
-            println!("Recording started...");
+        let mut reload_requested = reload_edge == KeyEdge::Pressed;
+        let mut tray_record = false;
+        let mut quit_requested = ctrl_c.load(Ordering::Relaxed);
+        while let Ok(command) = tray_commands.try_recv() {
+            match command {
+                TrayCommand::Record => tray_record = true,
+                TrayCommand::StopSpeaking => stop_speaking(narrator.as_ref()),
+                TrayCommand::ToggleEnabled => {
+                    hotkeys_enabled = !hotkeys_enabled;
+                    info!(
+                        "Hotkeys {}.",
+                        if hotkeys_enabled {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        }
+                    );
+                }
+                TrayCommand::ReloadConfig => reload_requested = true,
+                TrayCommand::Quit => quit_requested = true,
+            }
+        }
+        // Ctrl+C or the tray's Quit - release the microphone, stop whisper and speech,
+        // and delete the temporary recording before exiting
+        if quit_requested {
+            info!("Shutting down...");
+            if recorder.is_recording() {
+                level_meter.clear();
+                let _ = recorder.stop();
+            }
+            transcription_worker.shut_down(SHUTDOWN_WAIT);
+            if let Some(narrator) = narrator.as_ref() {
+                // Also deletes the synthesized chunks that weren't played yet
+                let _ = narrator.stop();
+            }
+            let _ = std::fs::remove_file(&temp_path);
+            config.save_last_profile();
+            return Ok(());
+        }
+        // Disabled from the tray, or our own keystrokes may still be arriving
+        let hotkeys_live = hotkeys_enabled && !input_guard.is_active(Instant::now());
+        if !hotkeys_live {
+            // Only let a recording that was already running finish
+            if !recorder.is_recording() {
+                record_edge = KeyEdge::None;
+            }
+            speak_edge = KeyEdge::None;
+            accept_edge = KeyEdge::None;
+            skip_edge = KeyEdge::None;
+            reset_language_edge = KeyEdge::None;
+            diagnostics_edge = KeyEdge::None;
+            next_voice_edge = KeyEdge::None;
+            faster_edge = KeyEdge::None;
+            slower_edge = KeyEdge::None;
+            pause_edge = KeyEdge::None;
+            translate_edge = KeyEdge::None;
+            next_language_edge = KeyEdge::None;
+            next_model_edge = KeyEdge::None;
+            save_speech_edge = KeyEdge::None;
+        }
+
+        // Reload key (F11 by default) - reload the config and apply what can change while running
+        if reload_requested {
+            match AppConfig::load() {
+                Ok(reloaded) => {
+                    let pin_language_after = config.pin_language_after;
+                    let history_size = config.clipboard_history;
+                    let previous_record_debounce = record_debounce(&config);
+                    let previous_profile = config.active_profile.clone();
+                    for key in config.apply_reload(reloaded) {
+                        warn!("{} changed, restart to apply it.", key);
+                    }
+                    apply_live_settings(&config, &mut recorder, &mut clipboard_mgr);
+                    heavy_ops.set_limit(config.max_heavy_ops);
+                    match try_lock_transcriber(&transcriber) {
+                        Some(mut transcriber) => {
+                            apply_transcriber_settings(&config, &mut transcriber)
+                        }
+                        None => {
+                            info!("Busy transcribing, the Whisper settings apply once it's done.");
+                            transcriber_settings_pending = true;
+                        }
+                    }
+                    // Keep the detector's state so a recording in progress isn't disturbed
+                    if record_debounce(&config) != previous_record_debounce {
+                        record_key = EdgeDetector::new(record_debounce(&config));
+                    }
+                    speak_key = EdgeDetector::new(Duration::from_millis(config.speak_debounce_ms));
+                    speak_taps =
+                        DoubleTapDetector::new(Duration::from_millis(config.double_tap_ms));
+                    level_ticker = Ticker::new(Duration::from_millis(config.level_interval_ms));
+                    if config.pin_language_after != pin_language_after {
+                        language_pin = LanguagePin::new(config.pin_language_after);
+                    }
+                    if config.clipboard_history != history_size {
+                        clipboard_history = ClipboardHistory::new(config.clipboard_history);
+                    }
+                    dataset = dataset_exporter(&config);
+                    merge_window = MergeWindow::new(Duration::from_millis(config.merge_window_ms));
+                    punctuator = punctuate::from_config(&config);
+                    if let Some(narrator) = narrator.as_mut() {
+                        match NarratorConfig::load(&config) {
+                            Ok(narrator_config) => narrator.set_config(narrator_config),
+                            Err(e) => {
+                                warn!("Keeping the previous TTS settings: {}", e)
+                            }
+                        }
+                    }
+                    if config.active_profile != previous_profile {
+                        config.save_last_profile();
+                    }
+                    info!("Config reloaded (profile '{}').", config.profile().name);
+                    activity.push(format!(
+                        "Config reloaded (profile '{}')",
+                        config.profile().name
+                    ));
+                }
+                Err(e) => {
+                    error!("Failed to reload config: {}", e);
+                    activity.push(format!("Failed to reload config: {}", e));
+                }
+            }
+        }
+
+        // Diagnostics key - write a report for bug reports
+        if diagnostics_edge == KeyEdge::Pressed {
+            let audio = config
+                .diagnostics_include_audio
+                .then_some(temp_path.as_path());
+            match try_lock_transcriber(&transcriber) {
+                Some(transcriber) => match write_diagnostics(
+                    &config,
+                    &transcriber,
+                    narrator.as_ref(),
+                    &activity,
+                    last_recording.as_ref(),
+                    audio,
+                ) {
+                    Ok(path) => info!("Diagnostics written to '{}'.", path.display()),
+                    Err(e) => error!("Failed to write diagnostics: {}", e),
+                },
+                None => info!("Busy transcribing, write the diagnostics when it's done."),
+            }
+        }
+
+        // Next-voice key - cycle through the PIPER_VOICE.<name> voices
+        if next_voice_edge == KeyEdge::Pressed {
+            if let Some(narrator) = narrator.as_ref() {
+                if narrator.config().voices.is_empty() {
+                    info!("No TTS voices configured (PIPER_VOICE.<name>).");
+                } else {
+                    match narrator.next_voice() {
+                        Some(name) => info!("TTS voice: {}", name),
+                        None => info!("TTS voice: automatic (by language)"),
+                    }
+                }
+            }
+        }
+
+        // Save-speech key - synthesize the selection into a WAV file instead of reading it aloud
+        if save_speech_edge == KeyEdge::Pressed {
+            if let Some(narrator) = narrator.as_ref() {
+                if saving_speech.is_some() {
+                    info!("Still saving the previous speech.");
+                } else {
+                    match get_selected_text() {
+                        Ok(text) if text.trim().is_empty() => info!("No text selected."),
+                        Ok(text) => {
+                            let path = narrator.saved_speech_path();
+                            match narrator.synthesize_to_file(&text, &path) {
+                                Ok(job) => {
+                                    info!("Saving speech to {}...", path.display());
+                                    saving_speech = Some((path, job));
+                                }
+                                Err(e) => error!("Failed to save the speech: {}", e),
+                            }
+                        }
+                        Err(e) => error!("Failed to get selected text: {}", e),
+                    }
+                }
+            } else {
+                info!("TTS not available. Please configure Piper.");
+            }
+        }
+        if saving_speech
+            .as_ref()
+            .is_some_and(|(_, job)| job.is_finished())
+        {
+            if let Some((path, job)) = saving_speech.take() {
+                match job.join() {
+                    Ok(Ok(())) => info!("Saved speech to {}", path.display()),
+                    Ok(Err(e)) => error!("Failed to save the speech: {}", e),
+                    Err(_) => error!("Failed to save the speech: Piper thread panicked"),
+                }
+            }
+        }
+
+        // Pause key - hold the narration, or continue it if already paused
+        if pause_edge == KeyEdge::Pressed {
+            if let Some(narrator) = narrator.as_ref() {
+                if narrator.resume() {
+                    info!("TTS resumed.");
+                } else if narrator.pause() {
+                    info!(
+                        "TTS paused ({} resumes, {} stops).",
+                        config.hotkeys.label(Action::Pause),
+                        config.hotkeys.label(Action::Speak)
+                    );
+                }
+            }
+        }
+
+        // Faster/slower keys - nudge Piper's length scale for the next utterance
+        if let Some(narrator) = narrator.as_ref() {
+            let delta = match (faster_edge, slower_edge) {
+                (KeyEdge::Pressed, _) => Some(-SPEED_STEP),
+                (_, KeyEdge::Pressed) => Some(SPEED_STEP),
+                _ => None,
+            };
+            if let Some(delta) = delta {
+                let speed = narrator.adjust_speed(delta);
+                info!("TTS length scale: {:.1} (lower is faster)", speed);
+            }
+        }
+
+        // Translate key - switch between transcribing and translating to English
+        if translate_edge == KeyEdge::Pressed {
+            config.translate = !config.translate;
+            if config.translate {
+                info!("Translating dictation to English.");
+            } else {
+                info!("Transcribing dictation in the spoken language.");
+            }
+        }
+
+        // Next-language key - force the next language from LANGUAGES, then auto-detect again
+        if next_language_edge == KeyEdge::Pressed {
+            if config.languages.is_empty() {
+                info!("No languages to switch between (LANGUAGES=en,pt).");
+            } else {
+                match config.next_language() {
+                    Some(language) => info!("Dictation language: {}", language),
+                    None => info!("Dictation language: auto-detect"),
+                }
+            }
+        }
+
+        // Next-model key - cycle through WHISPER_MODEL and the WHISPER_MODEL.<name> models
+        if next_model_edge == KeyEdge::Pressed {
+            if models.len() < 2 {
+                info!("No other Whisper models configured (WHISPER_MODEL.<name>).");
+            } else if let Some(mut transcriber) = try_lock_transcriber(&transcriber) {
+                model_index = (model_index + 1) % models.len();
+                let (name, path) = &models[model_index];
+                match local_model_path(&config, path) {
+                    Ok(path) => {
+                        info!("Whisper model: {} ({})", name, path.display());
+                        transcriber.set_model(path.clone());
+                        active_model = path;
+                    }
+                    Err(e) => error!("Failed to switch to model '{}': {}", name, e),
+                }
+            } else {
+                info!("Busy transcribing, switch the model when it's done.");
+            }
+        }
+
+        // Reset-language key (F6) - unpin the language
+        if reset_language_edge == KeyEdge::Pressed && config.pin_language_after > 0 {
+            language_pin.reset();
+            info!("Language auto-detection restored.");
+        }
+
+        // Accept/skip keys (F7/F8) - decide on a previewed transcription
+        if preview.is_pending() {
+            if accept_edge == KeyEdge::Pressed {
+                if let Some(text) = preview.resolve(PreviewAction::Accept) {
+                    export_entry(dataset.as_ref(), &temp_path, &text, EntryStatus::Accepted);
+                    paste_into(
+                        &mut clipboard_mgr,
+                        &mut input_guard,
+                        &mut merge_window,
+                        &config,
+                        &text,
+                        paste_target,
+                    );
+                }
+            } else if skip_edge == KeyEdge::Pressed {
+                reject_preview(
+                    &mut preview,
+                    PreviewAction::Skip,
+                    dataset.as_ref(),
+                    &temp_path,
+                );
+            }
+        }
+
+        // While whisper works: Esc cancels it, and record presses follow ON_BUSY
+        if transcription_worker.is_busy() {
+            if config.hotkeys.is_pressed(Action::Cancel) {
+                transcription_worker.cancel();
+            }
+            if record_edge == KeyEdge::Pressed || tray_record {
+                busy_record_press(config.on_busy, &mut record_queued);
+                record_edge = KeyEdge::None;
+                tray_record = false;
+            }
+        } else if std::mem::take(&mut record_queued) {
+            // In hold mode only a record key that is still held starts the queued recording
+            if config.record_mode == RecordMode::Toggle || config.hotkeys.is_pressed(Action::Record)
+            {
+                info!("Starting the queued recording...");
+                record_edge = KeyEdge::Pressed;
+            } else {
+                info!(
+                    "{} was released while busy, nothing recorded.",
+                    config.hotkeys.label(Action::Record)
+                );
+            }
+        }
+
+        // In toggle mode a recording stops itself once speech is followed by enough silence
+        let auto_stop = recorder.is_recording()
+            && silence_stops_recording(&config, recorder.silence_duration());
+        if auto_stop {
+            level_meter.clear();
+            info!("Silence detected, stopping the recording.");
+        }
+        // Recordings that hit MAX_RECORDING_SECONDS stop in either mode
+        let buffer_full = recorder.is_recording() && recorder.is_full();
+        if buffer_full {
+            level_meter.clear();
+            warn!(
+                "Recording reached the {}s limit (MAX_RECORDING_SECONDS) and was cut off there.",
+                config.max_recording_seconds
+            );
+        }
+
+        let (start_recording, stop_recording) = match config.record_mode {
+            // Releasing the key after a recording was cut off has nothing left to stop
+            RecordMode::Hold => (
+                record_edge == KeyEdge::Pressed,
+                (record_edge == KeyEdge::Released && recorder.is_recording()) || buffer_full,
+            ),
+            RecordMode::Toggle => {
+                let toggled = record_edge == KeyEdge::Pressed;
+                let recording = recorder.is_recording();
+                let ended = std::mem::take(&mut end_requested);
+                (
+                    toggled && !recording,
+                    ((toggled || ended) && recording) || auto_stop || buffer_full,
+                )
+            }
+        };
+        // The tray's record entry toggles recording in either mode
+        let recording = recorder.is_recording();
+        let start_recording = start_recording || (tray_record && !recording);
+        let stop_recording = stop_recording || (tray_record && recording);
+
+        // Record key (F9) - Speech-to-Text
+        if start_recording {
+            // Key just pressed - start recording (also discards a pending preview)
+            reject_preview(
+                &mut preview,
+                PreviewAction::ReRecord,
+                dataset.as_ref(),
+                &temp_path,
+            );
+            if config.speak_transcription {
+                // Don't record the previous read-back through the microphone
+                stop_speaking(narrator.as_ref());
+            }
+            info!("Recording started...");
+            if let Some(battery_model) = &battery_model {
+                let source = power::power_source();
+                let model = power::model_for(source, &active_model, Some(battery_model));
+                match try_lock_transcriber(&transcriber) {
+                    Some(mut transcriber) if transcriber.model_path() != model => {
+                        info!(
+                            "Power source: {:?}, switching to model '{}'.",
+                            source,
+                            model.display()
+                        );
+                        transcriber.set_model(model.to_path_buf());
+                    }
+                    Some(_) => {}
+                    None => debug!("Busy transcribing, keeping the current model."),
+                }
+            }
+            paste_target = if config.restore_focus {
+                focus::foreground_window()
+            } else {
+                None
+            };
+            activity.push("Recording started");
             if let Err(e) = recorder.start() {
-                eprintln!("Failed to start recording: {}", e);
+                error!("Failed to start recording: {}", e);
+                activity.push(format!("Failed to start recording: {}", e));
             }
-        } else if !is_f9_pressed && was_f9_pressed {
-            // Key just released - stop and transcribe
-            println!("Recording stopped. Transcribing...");
+        } else if stop_recording {
+            // Key released (or pressed again in toggle mode) - stop and transcribe
+            level_meter.clear();
+            info!(
+                "Recording stopped. Transcribing... ({} to cancel)",
+                config.hotkeys.label(Action::Cancel)
+            );
             match recorder.stop() {
-                Ok(audio_data) => {
-                    if audio_data.is_empty() {
-                        println!("Audio buffer empty, ignoring.");
-                        was_f9_pressed = is_f9_pressed;
+                Ok(recording) => {
+                    if recording.is_empty() {
+                        info!("Audio buffer empty, ignoring.");
+                        continue;
+                    }
+                    if let Some(app) = blocking_app(&config) {
+                        // Not even transcribed, the app may well be a password manager
+                        info!("Dictation blocked for {}.", app);
+                        activity.push(format!("Dictation blocked for {}", app));
                         continue;
                     }
 
-                    println!("Captured {} samples.", audio_data.len());
+                    info!(
+                        "Captured {:.1}s of audio ({} Hz, {} channel(s)).",
+                        recording.duration().as_secs_f32(),
+                        recording.sample_rate,
+                        recording.channels
+                    );
+                    let stats = RecordingStats::of(&recording);
+                    activity.push(format!(
+                        "Captured {:.1}s, peak {:.3}, RMS {:.3}",
+                        stats.duration.as_secs_f32(),
+                        stats.peak,
+                        stats.rms
+                    ));
+                    last_recording = Some(stats);
 
-                    let temp_filename = "temp_input.wav";
-                    if let Err(e) = recorder.save_to_file(&audio_data, temp_filename) {
-                        eprintln!("Failed to save WAV file: {}", e);
-                        was_f9_pressed = is_f9_pressed;
+                    recordings_prepared += 1;
+                    let audio_path = temp_dir.join(format!(
+                        "temp_input_{}_{}.wav",
+                        std::process::id(),
+                        recordings_prepared
+                    ));
+                    if let Err(e) = disk::ensure_free_space(&audio_path, config.min_free_disk_mb) {
+                        error!("{:#}", e);
                         continue;
                     }
+                    if let Err(e) =
+                        audio::prepare_for_whisper(&recording, &config.preprocessing(), &audio_path)
+                    {
+                        error!("Failed to prepare audio for whisper: {}", e);
+                        let _ = std::fs::remove_file(&audio_path);
+                        continue;
+                    }
+                    // Only still there if kept for diagnostics (see release_recording)
+                    let _ = std::fs::remove_file(&temp_path);
+                    temp_path = audio_path;
+                    clip_duration = recording.duration();
 
-                    match transcriber.transcribe(temp_filename) {
-                        Ok(text) => {
-                            println!("Transcribed: '{}'", text);
-                            if !text.is_empty() {
-                                if let Err(e) = clipboard_mgr.paste_text(&text) {
-                                    eprintln!("Failed to paste: {}", e);
-                                }
-                            }
+                    // The profile's language skips detection; otherwise a pinned one may
+                    let language = config
+                        .profile()
+                        .language
+                        .clone()
+                        .or_else(|| language_pin.language().map(str::to_string));
+                    let task = if config.translate {
+                        Task::Translate
+                    } else {
+                        Task::Transcribe
+                    };
+                    let chunk_threshold = (config.chunk_after_s > 0)
+                        .then(|| Duration::from_secs(config.chunk_after_s));
+                    if transcribe::needs_chunking(clip_duration, chunk_threshold) {
+                        info!("Long recording, transcribing it in parts...");
+                    }
+                    let job = worker::Job {
+                        audio_path: temp_path.clone(),
+                        duration: clip_duration,
+                        language,
+                        task,
+                        chunk_threshold,
+                        chunk_length: Duration::from_secs(config.chunk_length_s.max(1)),
+                    };
+                    if let Err(e) = transcription_worker.start(job) {
+                        error!("Failed to start transcription: {}", e);
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to stop recording: {}", e);
+                    activity.push(format!("Failed to stop recording: {}", e));
+                }
+            }
+        }
+
+        // Whisper settings from a reload that came while it was running
+        if transcriber_settings_pending {
+            if let Some(mut transcriber) = try_lock_transcriber(&transcriber) {
+                apply_transcriber_settings(&config, &mut transcriber);
+                transcriber_settings_pending = false;
+            }
+        }
+
+        // Transcription finished on the worker thread - paste, preview or report it
+        if let Some(result) = transcription_worker.poll() {
+            match result {
+                Ok(transcription)
+                    if transcribe::is_implausible_rate(
+                        &transcription.text,
+                        clip_duration,
+                        (config.max_words_per_second > 0.0).then_some(config.max_words_per_second),
+                    ) =>
+                {
+                    let rate = transcribe::words_per_second(&transcription.text, clip_duration);
+                    info!(
+                        "Discarded a likely hallucination ({:.1} words/s in {:.1}s of audio): '{}'",
+                        rate,
+                        clip_duration.as_secs_f32(),
+                        truncate_for_display(&transcription.text, 50)
+                    );
+                    activity.push(format!(
+                        "Discarded a likely hallucination ({:.1} words/s)",
+                        rate
+                    ));
+                    export_entry(
+                        dataset.as_ref(),
+                        &temp_path,
+                        &transcription.text,
+                        EntryStatus::Hallucination,
+                    );
+                }
+                Ok(transcription) => {
+                    if let Some(language) = &transcription.language {
+                        if language_pin.observe(language) {
+                            info!(
+                                "Language pinned to '{}' ({} to reset).",
+                                language,
+                                config.hotkeys.label(Action::ResetLanguage)
+                            );
                         }
-                        Err(e) => eprintln!("Transcription failed: {}", e),
+                    }
+                    let (raw_text, ends_dictation) = end_command(&config, &transcription.text);
+                    if ends_dictation {
+                        // The recording queued behind this one would continue the dictation
+                        info!("End command heard, dictation stopped.");
+                        end_requested = true;
+                        record_queued = false;
+                    }
+                    if raw_text.trim().is_empty() {
+                        info!("Only the end command was heard, nothing to paste.");
+                        continue;
+                    }
+                    let text = postprocess::apply(&raw_text, config.profile(), punctuator.as_ref());
+                    info!("Transcribed: '{}'", text);
+                    if config.notifications {
+                        notify::show("Transcribed", &truncate_for_display(&text, 100));
+                    }
+                    events.publish(Event::Transcription {
+                        text: text.clone(),
+                        language: transcription.language.clone(),
+                    });
+                    if config.log_sensitive {
+                        activity.push(format!("Transcribed: '{}'", text));
+                    } else {
+                        activity.push(format!("Transcribed {} characters", text.chars().count()));
+                    }
+                    if reads_back(&config, muting_app.as_deref()) {
+                        speak_back(narrator.as_ref(), &events, &text);
+                    }
+                    if config.preview {
+                        preview.show(
+                            text,
+                            &format!(
+                                "[{}] paste   [{}] skip   [{}] re-record",
+                                config.hotkeys.label(Action::Accept),
+                                config.hotkeys.label(Action::Skip),
+                                config.hotkeys.label(Action::Record)
+                            ),
+                        );
+                    } else {
+                        export_entry(dataset.as_ref(), &temp_path, &text, EntryStatus::Accepted);
+                        paste_into(
+                            &mut clipboard_mgr,
+                            &mut input_guard,
+                            &mut merge_window,
+                            &config,
+                            &text,
+                            paste_target,
+                        );
                     }
                 }
-                Err(e) => eprintln!("Failed to stop recording: {}", e),
+                Err(e) => match e.downcast_ref::<TranscribeError>() {
+                    Some(TranscribeError::NoSpeech) => {
+                        export_entry(dataset.as_ref(), &temp_path, "", EntryStatus::NoSpeech);
+                        if config.on_no_speech == NoSpeechAction::Report {
+                            info!("No speech detected.");
+                        }
+                        activity.push("No speech detected");
+                    }
+                    _ if e.downcast_ref::<process::Cancelled>().is_some() => {
+                        info!("Transcription cancelled.");
+                        activity.push("Transcription cancelled");
+                    }
+                    _ => {
+                        error!("Transcription failed: {}", e);
+                        activity.push(format!("Transcription failed: {}", e));
+                        if config.notifications {
+                            notify::show("Transcription failed", &e.to_string());
+                        }
+                    }
+                },
             }
         }
+        release_recording(&mut temp_path, &config, &preview, &transcription_worker);
 
-        // F10 handling - Text-to-Speech
-        if is_f10_pressed && !was_f10_pressed {
-            if let Some(ref narrator) = narrator {
-                if narrator.is_playing() {
-                    // Stop current playback
-                    println!("Stopping TTS playback...");
-                    if let Err(e) = narrator.stop() {
-                        eprintln!("Failed to stop playback: {}", e);
+        if let Some(dataset) = dataset.as_ref() {
+            if retention_ticker.tick() {
+                match dataset.enforce_retention(&config.dataset_retention()) {
+                    Ok(0) => {}
+                    Ok(removed) => info!("Deleted {} old dataset entries.", removed),
+                    Err(e) => error!("Failed to clean up old dataset entries: {}", e),
+                }
+            }
+        }
+
+        // Clipboard history - remember new clipboard texts, Ctrl+Shift+N reads one aloud
+        if config.clipboard_history > 0 && hotkeys_live {
+            if clipboard_ticker.tick() {
+                if let Some(text) = clipboard_mgr.get_text() {
+                    clipboard_history.push(&text);
+                }
+            }
+            let chord_held = (KeybdKey::LControlKey.is_pressed()
+                || KeybdKey::RControlKey.is_pressed())
+                && (KeybdKey::LShiftKey.is_pressed() || KeybdKey::RShiftKey.is_pressed());
+            for (slot, (key, detector)) in HISTORY_SLOT_KEYS
+                .iter()
+                .zip(history_keys.iter_mut())
+                .enumerate()
+            {
+                if detector.update(chord_held && key.is_pressed()) != KeyEdge::Pressed {
+                    continue;
+                }
+                match (narrator.as_ref(), clipboard_history.get(slot)) {
+                    (None, _) => info!("TTS not available. Please configure Piper."),
+                    (Some(_), None) => info!("No clipboard entry {} yet.", slot + 1),
+                    (Some(_), Some(_)) if muting_app.is_some() => {
+                        print_tts_muted(muting_app.as_deref())
+                    }
+                    (Some(narrator), Some(text)) => {
+                        info!(
+                            "Speaking clipboard entry {}: '{}'",
+                            slot + 1,
+                            truncate_for_display(text, 50)
+                        );
+                        events.publish(Event::Speech {
+                            text: text.to_string(),
+                        });
+                        if let Err(e) = narrator.speak(text) {
+                            error!("TTS failed: {}", e);
+                        }
                     }
+                }
+            }
+        }
+
+        if let Err(e) = recorder.recover_if_needed() {
+            error!("Failed to rebuild the audio stream: {}", e);
+        }
+
+        if let Some(tray) = tray.as_mut() {
+            let state = if recorder.is_recording() {
+                TrayState::Recording
+            } else if transcription_worker.is_busy() {
+                TrayState::Transcribing
+            } else if narrator.as_ref().is_some_and(Narrator::is_playing) {
+                TrayState::Speaking
+            } else {
+                TrayState::Idle
+            };
+            tray.set_state(state);
+        }
+
+        if recorder.is_recording() && level_ticker.tick() {
+            let level = recorder.input_level();
+            if config.level_meter {
+                level_meter.draw(level.peak);
+            }
+            events.publish(Event::Level {
+                peak: level.peak,
+                rms: level.rms,
+            });
+        }
+
+        // Keep TTS out of calls: stop it as soon as a listed app comes to the foreground
+        if !config.mute_tts_apps.is_empty() && muting_ticker.tick() {
+            let muting = focus::listed_foreground_app(
+                focus::foreground_process_name(),
+                &config.mute_tts_apps,
+            );
+            if let (Some(app), Some(narrator)) = (&muting, narrator.as_ref()) {
+                if muting_app.is_none() && narrator.is_playing() {
+                    info!("{} is in the foreground, stopping TTS.", app);
+                    stop_speaking(Some(narrator));
+                }
+            }
+            muting_app = muting;
+        } else if config.mute_tts_apps.is_empty() {
+            muting_app = None;
+        }
+
+        // Speak key (F10) - Text-to-Speech
+        // While speaking: single press stops, double tap skips to the next queued utterance
+        let mut speak_tap = speak_taps.poll(Instant::now());
+        if speak_edge == KeyEdge::Pressed {
+            if let Some(ref narrator) = narrator {
+                if narrator.is_playing() || speak_taps.is_pending() {
+                    speak_tap = speak_taps.press(Instant::now());
+                } else if muting_app.is_some() {
+                    print_tts_muted(muting_app.as_deref());
                 } else {
                     // Get selected text and speak it
                     match get_selected_text() {
                         Ok(text) => {
                             if text.trim().is_empty() {
-                                println!("No text selected.");
+                                info!("No text selected.");
                             } else {
-                                println!("Speaking: '{}'", truncate_for_display(&text, 50));
+                                info!("Speaking: '{}'", truncate_for_display(&text, 50));
+                                activity
+                                    .push(format!("Speaking {} characters", text.chars().count()));
+                                events.publish(Event::Speech { text: text.clone() });
                                 if let Err(e) = narrator.speak(&text) {
-                                    eprintln!("TTS failed: {}", e);
+                                    error!("TTS failed: {}", e);
+                                    activity.push(format!("TTS failed: {}", e));
                                 }
                             }
                         }
-                        Err(e) => eprintln!("Failed to get selected text: {}", e),
+                        Err(e) => {
+                            error!("Failed to get selected text: {}", e);
+                            activity.push(format!("Failed to get selected text: {}", e));
+                        }
                     }
                 }
             } else {
-                println!("TTS not available. Please configure Piper.");
+                info!("TTS not available. Please configure Piper.");
             }
         }
+        if let (Some(tap), Some(narrator)) = (speak_tap, narrator.as_ref()) {
+            handle_speak_tap(narrator, tap);
+        }
 
-        was_f9_pressed = is_f9_pressed;
-        was_f10_pressed = is_f10_pressed;
         std::thread::sleep(Duration::from_millis(20));
     }
 }
 
+/// Gather settings, devices, tool versions and recent activity into a report file
+/// in the working directory, returning its path
+fn write_diagnostics(
+    config: &AppConfig,
+    transcriber: &Transcriber,
+    narrator: Option<&Narrator>,
+    activity: &LogTail,
+    last_recording: Option<&RecordingStats>,
+    audio: Option<&Path>,
+) -> Result<PathBuf> {
+    let mut settings = vec![("App", format!("{:#?}", config))];
+    let whisper_version = match transcriber.executable_path() {
+        Some(path) => diagnostics::tool_version(path),
+        None => "not found".to_string(),
+    };
+    let mut versions = vec![("whisper-cli", whisper_version)];
+    match narrator {
+        Some(narrator) => {
+            settings.push(("TTS", format!("{:#?}", narrator.config())));
+            versions.push((
+                "piper",
+                diagnostics::tool_version(&narrator.config().piper_path),
+            ));
+        }
+        None => settings.push(("TTS", "not available".to_string())),
+    }
+    let (input_devices, output_devices) = diagnostics::describe_devices();
+    let report = diagnostics::Report {
+        settings,
+        input_devices,
+        output_devices,
+        versions,
+        recent: activity.lines().collect(),
+        last_recording,
+    };
+    diagnostics::write_report(
+        &std::env::current_dir()?,
+        &diagnostics::assemble(&report),
+        audio,
+    )
+}
+
+/// Absolute path of a model, using a copy in MODEL_CACHE_DIR if one is configured
+fn local_model_path(config: &AppConfig, model: &Path) -> Result<PathBuf> {
+    let model = std::env::current_dir()?.join(model);
+    Ok(match &config.model_cache_dir {
+        Some(cache_dir) => match transcribe::cached_model_path(&model, cache_dir) {
+            Ok(path) => path,
+            Err(e) => {
+                warn!("Failed to cache model, loading it in place: {}", e);
+                model
+            }
+        },
+        None => model,
+    })
+}
+
+/// The transcriber, waiting for an HTTP request that is using it
+fn lock_transcriber(transcriber: &Mutex<Transcriber>) -> MutexGuard<'_, Transcriber> {
+    transcriber.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The transcriber, or None while a transcription is using it
+/// The hotkey loop uses this so it never waits for whisper to finish.
+fn try_lock_transcriber(transcriber: &Mutex<Transcriber>) -> Option<MutexGuard<'_, Transcriber>> {
+    match transcriber.try_lock() {
+        Ok(transcriber) => Some(transcriber),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// Push the settings that can change while running into the components that use them
+fn apply_live_settings(
+    config: &AppConfig,
+    recorder: &mut AudioRecorder,
+    clipboard_mgr: &mut ClipboardManager,
+) {
+    recorder.set_retry_policy(
+        config.audio_start_retries,
+        Duration::from_millis(config.audio_start_backoff_ms),
+    );
+    recorder.set_error_policy(
+        config.audio_error_limit,
+        Duration::from_millis(config.audio_error_window_ms),
+    );
+    recorder.set_silence_threshold(config.silence_threshold);
+    recorder.set_max_duration(
+        (config.max_recording_seconds > 0)
+            .then(|| Duration::from_secs(config.max_recording_seconds)),
+    );
+    clipboard_mgr.set_typing_delay(
+        Duration::from_millis(config.type_char_delay_ms),
+        Duration::from_millis(config.type_jitter_ms),
+    );
+    clipboard_mgr.set_typing_chunks(
+        config.type_chunk_chars,
+        Duration::from_millis(config.type_chunk_delay_ms),
+    );
+    clipboard_mgr.set_restore_clipboard(
+        config
+            .restore_clipboard
+            .then(|| Duration::from_millis(config.restore_clipboard_delay_ms)),
+    );
+    clipboard_mgr.set_append(
+        config.append_clipboard.clone(),
+        config.append_clipboard_paste,
+    );
+    match clipboard::parse_key_combo(&config.paste_keys) {
+        Ok(keys) => clipboard_mgr.set_paste_keys(keys),
+        Err(e) => config::warn_invalid("PASTE_KEYS", &format!("{} ({})", config.paste_keys, e)),
+    }
+}
+
+/// Push the Whisper settings that can change while running into the transcriber
+fn apply_transcriber_settings(config: &AppConfig, transcriber: &mut Transcriber) {
+    transcriber.set_extra_args(config.whisper_extra_args.clone());
+    transcriber.set_threads(config.whisper_threads);
+    transcriber.set_timeout(
+        (config.whisper_timeout_seconds > 0)
+            .then(|| Duration::from_secs(config.whisper_timeout_seconds)),
+    );
+    transcriber.set_prompts(config.whisper_prompts.clone());
+    transcriber.set_verbose(config.verbose);
+    transcriber.set_log_sensitive(config.log_sensitive);
+    transcriber.set_strip_markup(config.strip_whisper_markup);
+}
+
+/// Minimum time between two record presses
+/// Hold mode must see every release immediately; in toggle mode a quick second press
+/// would stop the recording it just started, so it is ignored.
+fn record_debounce(config: &AppConfig) -> Duration {
+    match config.record_mode {
+        RecordMode::Hold => Duration::ZERO,
+        RecordMode::Toggle => Duration::from_millis(config.toggle_debounce_ms),
+    }
+}
+
+/// Dataset exporter for the configured directory, if dataset export is enabled
+fn dataset_exporter(config: &AppConfig) -> Option<DatasetExporter> {
+    config.dataset_dir.as_ref().map(|dir| {
+        info!("Saving dictations to dataset in '{}'.", dir.display());
+        DatasetExporter::new(dir.clone(), config.dataset_include_rejected)
+    })
+}
+
+/// Refocus the window the recording was started from (if known) and paste the text there
+/// Hotkeys are ignored briefly afterwards so the simulated keystrokes can't trigger them.
+fn paste_into(
+    clipboard_mgr: &mut ClipboardManager,
+    input_guard: &mut SyntheticInputGuard,
+    merge_window: &mut MergeWindow,
+    config: &AppConfig,
+    text: &str,
+    target: Option<WindowHandle>,
+) {
+    let text = merge_window.join(text, Instant::now(), target);
+    if let Some(window) = target {
+        if !focus::restore_foreground(window) {
+            warn!("Could not refocus the original window, pasting into the current one.");
+        }
+    }
+    if let Err(e) = clipboard_mgr.deliver(&text, config.output_mode, config.keep_clipboard) {
+        error!("Failed to paste: {}", e);
+    }
+    input_guard.arm(Instant::now());
+}
+
+/// Add a dictation to the dataset, if dataset export is enabled
+fn export_entry(
+    dataset: Option<&DatasetExporter>,
+    audio_path: &Path,
+    text: &str,
+    status: EntryStatus,
+) {
+    if let Some(dataset) = dataset {
+        if let Err(e) = dataset.export(audio_path, text, status) {
+            error!("Failed to save dataset entry: {}", e);
+        }
+    }
+}
+
+/// Discard a pending preview (skip or re-record), keeping it in the dataset as skipped
+fn reject_preview(
+    preview: &mut Preview,
+    action: PreviewAction,
+    dataset: Option<&DatasetExporter>,
+    audio_path: &Path,
+) {
+    if let Some(text) = preview.pending_text() {
+        export_entry(dataset, audio_path, text, EntryStatus::Skipped);
+    }
+    preview.resolve(action);
+}
+
+/// Delete the prepared recording once whisper, the preview and the dataset are done with it
+/// DIAGNOSTICS_INCLUDE_AUDIO keeps it for the next report until another recording replaces it.
+fn release_recording(
+    audio_path: &mut PathBuf,
+    config: &AppConfig,
+    preview: &Preview,
+    worker: &TranscriptionWorker,
+) {
+    if audio_path.as_os_str().is_empty()
+        || config.diagnostics_include_audio
+        || preview.is_pending()
+        || worker.is_busy()
+    {
+        return;
+    }
+    let _ = std::fs::remove_file(&*audio_path);
+    *audio_path = PathBuf::new();
+}
+
+/// Whether a recording that has been silent for `silence` stops itself
+/// (TOGGLE_SILENCE_STOP_MS); in hold mode only releasing the key ends a recording
+fn silence_stops_recording(config: &AppConfig, silence: Duration) -> bool {
+    config.record_mode == RecordMode::Toggle
+        && config.toggle_silence_stop_ms > 0
+        && silence >= Duration::from_millis(config.toggle_silence_stop_ms)
+}
+
+/// Handle a record press that came while whisper is busy: drop it or queue one recording
+/// for when the transcription is done (ON_BUSY)
+fn busy_record_press(policy: BusyPolicy, record_queued: &mut bool) {
+    match policy {
+        BusyPolicy::Reject => info!("Busy transcribing, please wait before recording again."),
+        BusyPolicy::Queue => {
+            info!("Busy transcribing, recording will start when it's done.");
+            *record_queued = true;
+        }
+    }
+}
+
+/// Apply the spoken end command (END_COMMAND) to a transcription: the text without it, and
+/// whether it ends the dictation, which only toggle mode keeps going
+fn end_command(config: &AppConfig, text: &str) -> (String, bool) {
+    let stripped = config
+        .end_command
+        .as_deref()
+        .and_then(|phrase| voice_commands::strip_end_command(text, phrase));
+    match stripped {
+        Some(stripped) => (stripped, config.record_mode == RecordMode::Toggle),
+        None => (text.to_string(), false),
+    }
+}
+
+/// Whether a finished transcription is read aloud (SPEAK_TRANSCRIPTION), which a call app
+/// in the foreground (MUTE_TTS_APPS) prevents
+fn reads_back(config: &AppConfig, muting_app: Option<&str>) -> bool {
+    config.speak_transcription && muting_app.is_none()
+}
+
+/// Read a transcription aloud, replacing whatever is currently being spoken
+fn speak_back(narrator: Option<&Narrator>, events: &EventBus, text: &str) {
+    if let Some(narrator) = narrator {
+        stop_speaking(Some(narrator));
+        events.publish(Event::Speech {
+            text: text.to_string(),
+        });
+        if let Err(e) = narrator.speak(text) {
+            error!("TTS failed: {}", e);
+        }
+    }
+}
+
+/// Foreground app listed in BLOCK_DICTATION_APPS, if any
+fn blocking_app(config: &AppConfig) -> Option<String> {
+    if config.block_dictation_apps.is_empty() {
+        return None;
+    }
+    focus::listed_foreground_app(
+        focus::foreground_process_name(),
+        &config.block_dictation_apps,
+    )
+}
+
+/// Tell the user why TTS didn't start (MUTE_TTS_APPS)
+fn print_tts_muted(app: Option<&str>) {
+    info!(
+        "TTS is muted while {} is in the foreground.",
+        app.unwrap_or("a call app")
+    );
+}
+
+/// Act on a finished speak-key gesture made while speaking
+fn handle_speak_tap(narrator: &Narrator, tap: Tap) {
+    match tap {
+        // Playback may have ended by itself while waiting for a second tap
+        Tap::Single if !narrator.is_playing() => {}
+        Tap::Single => {
+            info!("Stopping TTS playback...");
+            if let Err(e) = narrator.stop() {
+                error!("Failed to stop playback: {}", e);
+            }
+        }
+        Tap::Double => {
+            info!("Skipping to the next utterance...");
+            if let Err(e) = narrator.skip() {
+                error!("Failed to skip: {}", e);
+            }
+        }
+    }
+}
+
+/// Stop TTS playback if any is running
+fn stop_speaking(narrator: Option<&Narrator>) {
+    if let Some(narrator) = narrator {
+        if narrator.is_playing() {
+            if let Err(e) = narrator.stop() {
+                error!("Failed to stop playback: {}", e);
+            }
+        }
+    }
+}
+
 /// Get selected text by simulating Ctrl+C and reading from clipboard
 fn get_selected_text() -> Result<String> {
     use arboard::Clipboard;
@@ -201,9 +1617,9 @@ fn get_selected_text() -> Result<String> {
     let mut clipboard =
         Clipboard::new().map_err(|e| anyhow::anyhow!("Failed to access clipboard: {}", e))?;
 
-    let selected_text = clipboard.get_text().unwrap_or_default();
-    println!(
-        "[DEBUG] Clipboard contains: '{}'",
+    let selected_text = clipboard::selected_text(&mut clipboard)?;
+    debug!(
+        "Clipboard contains: '{}'",
         truncate_for_display(&selected_text, 50)
     );
 
@@ -221,3 +1637,71 @@ fn truncate_for_display(text: &str, max_chars: usize) -> String {
         format!("{}...", truncated)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silence_stops_only_toggle_mode_recordings() {
+        let mut config = AppConfig {
+            toggle_silence_stop_ms: 2000,
+            record_mode: RecordMode::Toggle,
+            ..AppConfig::default()
+        };
+        let silent_for = Duration::from_millis;
+        assert!(!silence_stops_recording(&config, silent_for(1999)));
+        assert!(silence_stops_recording(&config, silent_for(2000)));
+
+        config.record_mode = RecordMode::Hold;
+        assert!(!silence_stops_recording(&config, silent_for(60000)));
+
+        config.record_mode = RecordMode::Toggle;
+        config.toggle_silence_stop_ms = 0;
+        assert!(!silence_stops_recording(&config, silent_for(60000)));
+    }
+
+    #[test]
+    fn record_presses_while_busy_follow_on_busy() {
+        let mut record_queued = false;
+        busy_record_press(BusyPolicy::Reject, &mut record_queued);
+        busy_record_press(BusyPolicy::Reject, &mut record_queued);
+        assert!(!record_queued);
+
+        // Several presses still queue a single recording
+        busy_record_press(BusyPolicy::Queue, &mut record_queued);
+        busy_record_press(BusyPolicy::Queue, &mut record_queued);
+        assert!(record_queued);
+    }
+
+    #[test]
+    fn end_command_strips_itself_and_stops_toggle_dictation() {
+        let mut config = AppConfig {
+            record_mode: RecordMode::Toggle,
+            ..AppConfig::default()
+        };
+        let text = "Thanks. End dictation.";
+        assert_eq!(end_command(&config, text), (text.to_string(), false));
+
+        config.end_command = Some("end dictation".to_string());
+        assert_eq!(end_command(&config, text), ("Thanks.".to_string(), true));
+        assert_eq!(
+            end_command(&config, "Thanks."),
+            ("Thanks.".to_string(), false)
+        );
+
+        // A hold-mode recording already ended with the key release
+        config.record_mode = RecordMode::Hold;
+        assert_eq!(end_command(&config, text), ("Thanks.".to_string(), false));
+    }
+
+    #[test]
+    fn transcription_is_read_back_only_when_enabled() {
+        let mut config = AppConfig::default();
+        assert!(!reads_back(&config, None));
+
+        config.speak_transcription = true;
+        assert!(reads_back(&config, None));
+        assert!(!reads_back(&config, Some("Teams.exe")));
+    }
+}
@@ -0,0 +1,67 @@
+use crate::focus::WindowHandle;
+use std::time::{Duration, Instant};
+
+/// Joins a dictation onto the previous paste when it follows closely enough
+///
+/// Pasting leaves the cursor after the text, so continuing the previous dictation only
+/// takes a separating space in front of the new text. Nothing is merged into a
+/// different window than the previous paste went to.
+pub struct MergeWindow {
+    window: Duration,
+    last_paste: Option<(Instant, Option<WindowHandle>)>,
+}
+
+impl MergeWindow {
+    /// `window` of zero never merges
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last_paste: None,
+        }
+    }
+
+    /// Whether a dictation finished at `now` for `target` continues the previous paste
+    pub fn should_merge(&self, now: Instant, target: Option<WindowHandle>) -> bool {
+        !self.window.is_zero()
+            && self.last_paste.is_some_and(|(at, last_target)| {
+                last_target == target && now.saturating_duration_since(at) <= self.window
+            })
+    }
+
+    /// Text to paste for `text`, prefixed with a space if it continues the previous paste
+    /// Records this paste as the one the next dictation may continue.
+    pub fn join(&mut self, text: &str, now: Instant, target: Option<WindowHandle>) -> String {
+        let merge = self.should_merge(now, target);
+        self.last_paste = Some((now, target));
+        if merge && !text.starts_with(char::is_whitespace) {
+            format!(" {}", text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dictation_within_the_window_continues_the_previous_paste() {
+        let mut merge = MergeWindow::new(Duration::from_secs(2));
+        let start = Instant::now();
+        assert_eq!(merge.join("Hello.", start, None), "Hello.");
+        let soon = start + Duration::from_secs(1);
+        assert_eq!(merge.join("How are you?", soon, None), " How are you?");
+        assert_eq!(merge.join(" Fine.", soon, None), " Fine.");
+        let later = soon + Duration::from_secs(3);
+        assert_eq!(merge.join("Bye.", later, None), "Bye.");
+    }
+
+    #[test]
+    fn zero_window_never_merges() {
+        let mut merge = MergeWindow::new(Duration::ZERO);
+        let now = Instant::now();
+        merge.join("Hello.", now, None);
+        assert!(!merge.should_merge(now, None));
+    }
+}
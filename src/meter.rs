@@ -0,0 +1,41 @@
+//! Console level meter redrawn in place while recording
+
+use std::io::Write;
+
+/// Characters in the bar at full scale
+const BAR_WIDTH: usize = 30;
+
+/// One console line showing the microphone peak as a row of `#`
+#[derive(Default)]
+pub struct LevelMeter {
+    visible: bool,
+}
+
+impl LevelMeter {
+    /// Redraw the meter for `peak` (0-1) over the current line
+    pub fn draw(&mut self, peak: f32) {
+        print!("\r{}", bar(peak));
+        let _ = std::io::stdout().flush();
+        self.visible = true;
+    }
+
+    /// Blank the meter line, so the next message starts at the beginning of a clean line
+    pub fn clear(&mut self) {
+        if !self.visible {
+            return;
+        }
+        print!("\r{}\r", " ".repeat(bar(0.0).len()));
+        let _ = std::io::stdout().flush();
+        self.visible = false;
+    }
+}
+
+/// e.g. "Level [#########                     ]"
+fn bar(peak: f32) -> String {
+    let filled = ((peak.clamp(0.0, 1.0) * BAR_WIDTH as f32).round() as usize).min(BAR_WIDTH);
+    format!(
+        "Level [{}{}]",
+        "#".repeat(filled),
+        " ".repeat(BAR_WIDTH - filled)
+    )
+}
@@ -1,17 +1,247 @@
-use anyhow::{anyhow, Result};
+use crate::config::{config_file, read_config_file, set_bool, set_parsed, split_args, AppConfig};
+use crate::disk;
+use crate::events::{Event, EventBus};
+use crate::audio;
+use crate::limiter::HeavyOpLimiter;
+use crate::notify;
+use crate::piper_server::PiperServer;
+use crate::process::{format_command, run_with_input, Cancelled};
+use anyhow::{anyhow, Context, Result};
+use rodio::source::ChannelVolume;
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
-use std::process::{Child, Command, Stdio};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SendError, SyncSender};
 use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, error, info, warn};
 use whatlang::{detect, Lang};
 
 /// Configuration for Piper TTS
+#[derive(Debug, Clone)]
 pub struct NarratorConfig {
     pub piper_path: PathBuf,
     pub models: HashMap<String, PathBuf>, // language code -> model path
     pub default_model: PathBuf,
+    /// Voices to cycle through with the next_voice hotkey, as (name, model path)
+    pub voices: Vec<(String, PathBuf)>,
     pub speed: f32,
+    /// Extra Piper arguments, appended after the ones the tool manages
+    pub extra_args: Vec<String>,
+    /// Show a desktop notification when Piper or playback fails (NOTIFICATIONS)
+    pub notifications: bool,
+    /// Log the full Piper command before each run
+    pub verbose: bool,
+    /// Also log the text being spoken (only with `verbose`)
+    pub log_sensitive: bool,
+    /// Kill and retry Piper if one utterance takes longer than this, None = wait forever
+    pub stall_timeout: Option<Duration>,
+    /// Keep one Piper running between utterances instead of starting it for each (PIPER_PERSISTENT)
+    pub persistent: bool,
+    /// Extra attempts when Piper fails on a chunk, each to a fresh output file
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each further one
+    pub retry_backoff: Duration,
+    /// Minimum free disk space (MB) needed to write Piper's output, 0 = don't check
+    pub min_free_disk_mb: u64,
+    /// Local folder Piper's output is written to (TEMP_DIR)
+    pub temp_dir: PathBuf,
+    /// Output channels Piper's mono speech is copied to before playback
+    pub channel_map: ChannelMap,
+    /// Silence inserted between paragraphs, None = no pause
+    pub paragraph_gap: Option<Duration>,
+    /// Synthesize and play each sentence on its own instead of whole paragraphs
+    pub split_sentences: bool,
+    /// How many chunks may be synthesized ahead of the one playing
+    pub lookahead: usize,
+    /// Output device name patterns in order of preference, empty = the system default
+    pub output_devices: Vec<String>,
+    /// Publish `Event::Word` as playback reaches each word (TTS_WORD_EVENTS)
+    pub word_events: bool,
+    /// Make Piper's output play at the rate in the voice's .onnx.json (TTS_CHECK_SAMPLE_RATE)
+    pub check_sample_rate: bool,
+    /// Also save everything spoken to a timestamped WAV file in this folder (TTS_SAVE_DIR)
+    pub save_dir: Option<PathBuf>,
+}
+
+/// How mono speech is laid out on the output device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelMap {
+    /// Total channels written to the played file (1 = leave Piper's mono output as-is)
+    pub channels: u16,
+    /// Zero-based channels that carry the speech, the others stay silent
+    pub active: Vec<u16>,
+}
+
+impl Default for ChannelMap {
+    /// Plain stereo: the same signal on left and right
+    fn default() -> Self {
+        Self {
+            channels: 2,
+            active: vec![0, 1],
+        }
+    }
+}
+
+impl ChannelMap {
+    /// Build a map from TTS_CHANNELS and TTS_CHANNEL_MAP (1-based, comma-separated)
+    /// An empty `active` list means every channel.
+    pub fn parse(channels: &str, active: Option<&str>) -> Result<Self> {
+        let channels: u16 = channels
+            .trim()
+            .parse()
+            .ok()
+            .filter(|&count| count > 0)
+            .ok_or_else(|| anyhow!("expected a channel count of 1 or more, got '{}'", channels))?;
+
+        let active = match active.map(str::trim).filter(|list| !list.is_empty()) {
+            None => (0..channels).collect(),
+            Some(list) => list
+                .split(',')
+                .map(|item| match item.trim().parse::<u16>() {
+                    Ok(number) if (1..=channels).contains(&number) => Ok(number - 1),
+                    _ => Err(anyhow!(
+                        "'{}' is not a channel between 1 and {}",
+                        item.trim(),
+                        channels
+                    )),
+                })
+                .collect::<Result<Vec<u16>>>()?,
+        };
+
+        Ok(Self { channels, active })
+    }
+
+    /// Whether playback can use Piper's file unchanged
+    fn is_passthrough(&self) -> bool {
+        self.channels == 1 && self.active == [0]
+    }
+
+    /// Volume of each output channel: full on the active ones, silent on the others
+    fn volumes(&self) -> Vec<f32> {
+        (0..self.channels)
+            .map(|channel| {
+                if self.active.contains(&channel) {
+                    1.0
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+}
+
+/// Piper settings as read from the config file, before paths are checked
+struct Settings {
+    piper_path: Option<PathBuf>,
+    models: HashMap<String, PathBuf>,
+    default_model: Option<PathBuf>,
+    voices: Vec<(String, PathBuf)>,
+    speed: f32,
+    extra_args: Vec<String>,
+    stall_timeout_ms: u64,
+    persistent: bool,
+    retries: u32,
+    retry_backoff_ms: u64,
+    channels: Option<String>,
+    channel_active: Option<String>,
+    output_devices: Vec<String>,
+    paragraph_gap_ms: u64,
+    split_sentences: bool,
+    lookahead: usize,
+    word_events: bool,
+    check_sample_rate: bool,
+    save_dir: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            piper_path: None,
+            models: HashMap::new(),
+            default_model: None,
+            voices: Vec::new(),
+            speed: 1.0,
+            extra_args: Vec::new(),
+            stall_timeout_ms: 30000,
+            persistent: false,
+            retries: 2,
+            retry_backoff_ms: 200,
+            channels: None,
+            channel_active: None,
+            output_devices: Vec::new(),
+            paragraph_gap_ms: 0,
+            split_sentences: true,
+            lookahead: 1,
+            word_events: false,
+            check_sample_rate: true,
+            save_dir: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Apply a config file setting, returning false if it isn't a Piper setting
+    /// An invalid value is an error and leaves the setting unchanged.
+    fn apply(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "PIPER_PATH" => self.piper_path = Some(PathBuf::from(value)),
+            "PIPER_MODEL" | "PIPER_MODEL_DEFAULT" => {
+                self.default_model = Some(PathBuf::from(value))
+            }
+            "SPEED" => set_parsed(&mut self.speed, key, value)?,
+            "PIPER_EXTRA_ARGS" => {
+                self.extra_args =
+                    split_args(value).map_err(|e| anyhow!("Invalid {} ({})", key, e))?
+            }
+            "PIPER_TIMEOUT_MS" => set_parsed(&mut self.stall_timeout_ms, key, value)?,
+            "PIPER_PERSISTENT" => set_bool(&mut self.persistent, key, value)?,
+            "PIPER_RETRIES" => set_parsed(&mut self.retries, key, value)?,
+            "PIPER_RETRY_BACKOFF_MS" => set_parsed(&mut self.retry_backoff_ms, key, value)?,
+            "TTS_CHANNELS" => self.channels = Some(value.to_string()),
+            "TTS_CHANNEL_MAP" => self.channel_active = Some(value.to_string()),
+            "PARAGRAPH_GAP_MS" => set_parsed(&mut self.paragraph_gap_ms, key, value)?,
+            "TTS_SPLIT_SENTENCES" => set_bool(&mut self.split_sentences, key, value)?,
+            "TTS_LOOKAHEAD" => set_parsed(&mut self.lookahead, key, value)?,
+            "OUTPUT_DEVICES" => {
+                self.output_devices = value
+                    .split(',')
+                    .map(|pattern| pattern.trim().to_string())
+                    .filter(|pattern| !pattern.is_empty())
+                    .collect()
+            }
+            "TTS_WORD_EVENTS" => set_bool(&mut self.word_events, key, value)?,
+            "TTS_CHECK_SAMPLE_RATE" => set_bool(&mut self.check_sample_rate, key, value)?,
+            "TTS_SAVE_DIR" => self.save_dir = (!value.is_empty()).then(|| PathBuf::from(value)),
+            _ => {
+                if let Some(name) = key.strip_prefix("PIPER_VOICE.") {
+                    self.voices.retain(|(existing, _)| existing != name);
+                    self.voices.push((name.to_string(), PathBuf::from(value)));
+                } else if let Some(lang_code) = key.strip_prefix("PIPER_MODEL_") {
+                    // e.g. "en" from "PIPER_MODEL_EN"
+                    self.models
+                        .insert(lang_code.to_lowercase(), PathBuf::from(value));
+                } else {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Check a setting the way `NarratorConfig::load` would apply it, returning false if it
+/// isn't a Piper setting
+pub fn check_setting(key: &str, value: &str) -> Result<bool> {
+    Settings::default().apply(key, value)
 }
 
 impl NarratorConfig {
@@ -22,43 +252,39 @@ impl NarratorConfig {
     /// - PIPER_MODEL_EN for English
     /// - PIPER_MODEL_PT for Portuguese
     /// - PIPER_MODEL (or PIPER_MODEL_DEFAULT) as fallback
-    pub fn load() -> Result<Self> {
+    ///
+    /// Settings shared with dictation (MIN_FREE_DISK_MB, TEMP_DIR, NOTIFICATIONS, VERBOSE,
+    /// LOG_SENSITIVE) are taken from `app`.
+    pub fn load(app: &AppConfig) -> Result<Self> {
         let current_dir = env::current_dir()?;
 
-        // Try to load from config file first
-        let config_path = current_dir.join("tts_config.txt");
-        let mut piper_path: Option<PathBuf> = None;
-        let mut models: HashMap<String, PathBuf> = HashMap::new();
-        let mut default_model: Option<PathBuf> = None;
-        let mut speed: f32 = 1.0;
-
-        if config_path.exists() {
-            let content = std::fs::read_to_string(&config_path)?;
-            for line in content.lines() {
-                let line = line.trim();
-                if line.is_empty() || line.starts_with('#') {
-                    continue;
-                }
-                if let Some((key, value)) = line.split_once('=') {
-                    let key = key.trim();
-                    let value = value.trim();
-
-                    if key == "PIPER_PATH" {
-                        piper_path = Some(PathBuf::from(value));
-                    } else if key == "PIPER_MODEL" || key == "PIPER_MODEL_DEFAULT" {
-                        default_model = Some(PathBuf::from(value));
-                    } else if key.starts_with("PIPER_MODEL_") {
-                        // Extract language code (e.g., "EN" from "PIPER_MODEL_EN")
-                        let lang_code = key.strip_prefix("PIPER_MODEL_").unwrap().to_lowercase();
-                        if lang_code != "default" {
-                            models.insert(lang_code, PathBuf::from(value));
-                        }
-                    } else if key == "SPEED" {
-                        speed = value.parse().unwrap_or(1.0);
-                    }
-                }
+        let mut settings = Settings::default();
+        for (key, value) in read_config_file()? {
+            if let Err(e) = settings.apply(&key, &value) {
+                warn!("{} in {}, ignoring it", e, config_file());
             }
         }
+        let Settings {
+            mut piper_path,
+            mut models,
+            mut default_model,
+            mut voices,
+            speed,
+            extra_args,
+            stall_timeout_ms,
+            persistent,
+            retries,
+            retry_backoff_ms,
+            channels,
+            channel_active,
+            output_devices,
+            paragraph_gap_ms,
+            split_sentences,
+            lookahead,
+            word_events,
+            check_sample_rate,
+            save_dir,
+        } = settings;
 
         // Environment variables override config file
         if let Ok(path) = env::var("PIPER_PATH") {
@@ -93,8 +319,8 @@ impl NarratorConfig {
             if path.exists() {
                 true
             } else {
-                eprintln!(
-                    "WARNING: Piper model for '{}' not found at '{}', will use default",
+                warn!(
+                    "Piper model for '{}' not found at '{}', will use default",
                     lang,
                     path.display()
                 );
@@ -102,58 +328,79 @@ impl NarratorConfig {
             }
         });
 
+        voices.retain(|(name, path)| {
+            if path.exists() {
+                true
+            } else {
+                warn!(
+                    "Piper model for voice '{}' not found at '{}', skipping it",
+                    name,
+                    path.display()
+                );
+                false
+            }
+        });
+
+        let channel_map = match ChannelMap::parse(
+            channels.as_deref().unwrap_or("2"),
+            channel_active.as_deref(),
+        ) {
+            Ok(map) => map,
+            Err(e) => {
+                warn!(
+                    "Invalid TTS_CHANNELS/TTS_CHANNEL_MAP in {}: {}, using stereo",
+                    config_file(),
+                    e
+                );
+                ChannelMap::default()
+            }
+        };
+
         // Log detected models
         if !models.is_empty() {
-            println!("Language-specific TTS models loaded:");
+            info!("Language-specific TTS models loaded:");
             for (lang, path) in &models {
-                println!("  {} -> {}", lang.to_uppercase(), path.display());
+                info!("  {} -> {}", lang.to_uppercase(), path.display());
             }
-            println!("  DEFAULT -> {}", default_model.display());
+            info!("  DEFAULT -> {}", default_model.display());
         }
 
         Ok(Self {
             piper_path,
             models,
             default_model,
+            voices,
             speed,
+            min_free_disk_mb: app.min_free_disk_mb,
+            temp_dir: disk::temp_dir(app.temp_dir.as_deref()),
+            extra_args,
+            notifications: app.notifications,
+            verbose: app.verbose,
+            log_sensitive: app.log_sensitive,
+            stall_timeout: (stall_timeout_ms > 0).then(|| Duration::from_millis(stall_timeout_ms)),
+            persistent,
+            retries,
+            retry_backoff: Duration::from_millis(retry_backoff_ms),
+            channel_map,
+            output_devices,
+            paragraph_gap: (paragraph_gap_ms > 0).then(|| Duration::from_millis(paragraph_gap_ms)),
+            split_sentences,
+            lookahead,
+            word_events,
+            check_sample_rate,
+            save_dir,
         })
     }
 
     /// Get the appropriate model path for the given text
     pub fn get_model_for_text(&self, text: &str) -> &PathBuf {
         if let Some(info) = detect(text) {
-            let lang_code = match info.lang() {
-                Lang::Eng => "en",
-                Lang::Por => "pt",
-                Lang::Spa => "es",
-                Lang::Fra => "fr",
-                Lang::Deu => "de",
-                Lang::Ita => "it",
-                Lang::Nld => "nl",
-                Lang::Rus => "ru",
-                Lang::Jpn => "ja",
-                Lang::Cmn => "zh",
-                Lang::Kor => "ko",
-                Lang::Ara => "ar",
-                Lang::Hin => "hi",
-                Lang::Tur => "tr",
-                Lang::Pol => "pl",
-                Lang::Ukr => "uk",
-                Lang::Ces => "cs",
-                Lang::Ron => "ro",
-                Lang::Hun => "hu",
-                Lang::Ell => "el",
-                Lang::Swe => "sv",
-                Lang::Dan => "da",
-                Lang::Fin => "fi",
-                Lang::Nob => "no", // Norwegian Bokmål
-                _ => "default",
-            };
+            let lang_code = language_code(info.lang());
 
             // Check confidence - only use detected language if confident enough
             if info.is_reliable() {
                 if let Some(model) = self.models.get(lang_code) {
-                    println!(
+                    info!(
                         "[TTS] Detected language: {} (confidence: {:.0}%)",
                         lang_code.to_uppercase(),
                         info.confidence() * 100.0
@@ -161,8 +408,8 @@ impl NarratorConfig {
                     return model;
                 }
             } else {
-                println!("[TTS] Language detection failed");
-                println!("[TTS] Confidence: {:.0}%", info.confidence() * 100.0);
+                info!("[TTS] Language detection failed");
+                info!("[TTS] Confidence: {:.0}%", info.confidence() * 100.0);
             }
         }
 
@@ -170,70 +417,233 @@ impl NarratorConfig {
     }
 }
 
+/// Short language code (as used in PIPER_MODEL_XX and by whisper) for a detected language
+/// Returns "default" for languages without a mapping
+pub fn language_code(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Eng => "en",
+        Lang::Por => "pt",
+        Lang::Spa => "es",
+        Lang::Fra => "fr",
+        Lang::Deu => "de",
+        Lang::Ita => "it",
+        Lang::Nld => "nl",
+        Lang::Rus => "ru",
+        Lang::Jpn => "ja",
+        Lang::Cmn => "zh",
+        Lang::Kor => "ko",
+        Lang::Ara => "ar",
+        Lang::Hin => "hi",
+        Lang::Tur => "tr",
+        Lang::Pol => "pl",
+        Lang::Ukr => "uk",
+        Lang::Ces => "cs",
+        Lang::Ron => "ro",
+        Lang::Hun => "hu",
+        Lang::Ell => "el",
+        Lang::Swe => "sv",
+        Lang::Dan => "da",
+        Lang::Fin => "fi",
+        Lang::Nob => "no", // Norwegian Bokmål
+        _ => "default",
+    }
+}
+
+/// How often the playback thread checks for stop/skip requests and finished chunks
+const PLAYBACK_POLL: Duration = Duration::from_millis(20);
+
 /// Manages TTS playback with cancellation support
+///
+/// Each `speak` splits the text into paragraphs (and those into sentences, unless
+/// TTS_SPLIT_SENTENCES is off) and starts a session of two threads:
+/// one runs Piper for each chunk in turn, the other plays the finished chunks in order.
+/// They are joined by a bounded channel, so at most TTS_LOOKAHEAD chunks are synthesized
+/// ahead of the one playing and playback starts as soon as the first chunk is ready.
+///
+/// A narration goes from idle to playing on `speak`, and back to idle when the last chunk
+/// has played or on `stop`. While playing, `pause` holds it (synthesis ahead still goes on)
+/// until `resume`; `stop` and `speak` also end a paused narration. `is_playing` is true
+/// both while playing and while paused.
 pub struct Narrator {
     config: NarratorConfig,
-    current_process: Arc<Mutex<Option<Child>>>,
+    // Name of the voice picked with `next_voice`, None = choose the model by language
+    voice: Mutex<Option<String>>,
+    // Length scale for the next `speak`; starts at SPEED and is changed by `adjust_speed`
+    speed: Mutex<f32>,
+    session: Mutex<Option<Session>>,
+    // Numbers sessions, keeping chunk files of consecutive sessions apart
+    sessions_started: AtomicU64,
+    // Cleared for the session once Piper rejects --length-scale
+    length_scale_supported: Arc<AtomicBool>,
+    limiter: Arc<HeavyOpLimiter>,
+    // Piper kept running between utterances with PIPER_PERSISTENT
+    piper_server: Arc<Mutex<Option<PiperServer>>>,
+    events: Option<EventBus>,
+}
+
+/// One `speak` call being synthesized and played in the background
+struct Session {
+    cancelled: Arc<AtomicBool>,
+    skip_requested: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    synth_thread: JoinHandle<()>,
+    play_thread: JoinHandle<()>,
+}
+
+impl Session {
+    /// Cancel everything still to come and wait for both threads to wind down
+    /// Both threads check `cancelled` while they wait (for Piper, a synthesis slot or
+    /// playback), so this returns within a poll interval or so.
+    fn cancel(self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        let _ = self.play_thread.join();
+        let _ = self.synth_thread.join();
+    }
 }
 
 impl Narrator {
     pub fn new(config: NarratorConfig) -> Self {
+        let speed = clamp_speed(config.speed);
         Self {
             config,
-            current_process: Arc::new(Mutex::new(None)),
+            voice: Mutex::new(None),
+            speed: Mutex::new(speed),
+            session: Mutex::new(None),
+            sessions_started: AtomicU64::new(0),
+            length_scale_supported: Arc::new(AtomicBool::new(true)),
+            limiter: Arc::new(HeavyOpLimiter::new(0)),
+            piper_server: Arc::new(Mutex::new(None)),
+            events: None,
         }
     }
 
-    /// Check if audio is currently playing
+    /// Share a limit on concurrent whisper/Piper runs (MAX_HEAVY_OPS)
+    pub fn set_limiter(&mut self, limiter: Arc<HeavyOpLimiter>) {
+        self.limiter = limiter;
+    }
+
+    /// Where word-boundary events go when TTS_WORD_EVENTS is on
+    pub fn set_events(&mut self, events: EventBus) {
+        self.events = Some(events);
+    }
+
+    /// Use a reloaded configuration from the next `speak` on
+    pub fn config(&self) -> &NarratorConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: NarratorConfig) {
+        self.set_speed(config.speed);
+        self.config = config;
+        self.length_scale_supported.store(true, Ordering::Relaxed);
+        // A changed PIPER_PATH or PIPER_EXTRA_ARGS needs a new Piper anyway
+        *self.piper_server.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Switch to the next configured voice, returning its name
+    /// After the last voice it goes back to choosing the model by language (None).
+    pub fn next_voice(&self) -> Option<String> {
+        let mut voice = self.voice.lock().unwrap_or_else(|e| e.into_inner());
+        let next = match voice.as_deref() {
+            Some(current) => self
+                .config
+                .voices
+                .iter()
+                .position(|(name, _)| name == current)
+                .map_or(0, |index| index + 1),
+            None => 0,
+        };
+        *voice = self.config.voices.get(next).map(|(name, _)| name.clone());
+        voice.clone()
+    }
+
+    /// Use `speed` as Piper's length scale from the next `speak` on, returning the value set
+    /// It is kept within MIN_SPEED..=MAX_SPEED, so Piper never gets a zero or negative scale.
+    pub fn set_speed(&self, speed: f32) -> f32 {
+        let speed = clamp_speed(speed);
+        *self.speed.lock().unwrap_or_else(|e| e.into_inner()) = speed;
+        speed
+    }
+
+    /// Change the length scale by `delta` (negative = faster), returning the new value
+    pub fn adjust_speed(&self, delta: f32) -> f32 {
+        let current = *self.speed.lock().unwrap_or_else(|e| e.into_inner());
+        self.set_speed(current + delta)
+    }
+
+    /// Model of the selected voice, if one is selected and still configured
+    fn voice_model(&self) -> Option<PathBuf> {
+        let voice = self.voice.lock().unwrap_or_else(|e| e.into_inner());
+        let name = voice.as_deref()?;
+        self.config
+            .voices
+            .iter()
+            .find(|(voice, _)| voice == name)
+            .map(|(_, path)| path.clone())
+    }
+
+    /// Check if a narration is in progress: playing, paused, or its next chunk being synthesized
     pub fn is_playing(&self) -> bool {
-        if let Ok(mut guard) = self.current_process.lock() {
-            if let Some(ref mut child) = *guard {
-                // Check if process is still running
-                match child.try_wait() {
-                    Ok(None) => return true, // Still running
-                    Ok(Some(_)) => {
-                        // Process finished, clean up
-                        *guard = None;
-                        return false;
-                    }
-                    Err(_) => {
-                        *guard = None;
-                        return false;
+        if let Ok(mut guard) = self.session.lock() {
+            match guard.as_ref() {
+                Some(session) if !session.play_thread.is_finished() => return true,
+                Some(_) => {
+                    // Session finished, clean up
+                    if let Some(session) = guard.take() {
+                        session.cancel();
                     }
                 }
+                None => {}
             }
         }
         false
     }
 
-    /// Stop current playback if any
+    /// Stop current playback if any, discarding chunks synthesized but not yet played
     pub fn stop(&self) -> Result<()> {
-        if let Ok(mut guard) = self.current_process.lock() {
-            if let Some(ref mut child) = guard.take() {
-                // Kill the process tree on Windows
-                #[cfg(target_os = "windows")]
-                {
-                    use std::os::windows::process::CommandExt;
-                    // Use taskkill to kill the process and its children
-                    let _ = Command::new("taskkill")
-                        .args(["/F", "/T", "/PID", &child.id().to_string()])
-                        .creation_flags(0x08000000) // CREATE_NO_WINDOW
-                        .output();
-                }
+        let session = self.session.lock().ok().and_then(|mut guard| guard.take());
+        if let Some(session) = session {
+            session.cancel();
+        }
+        Ok(())
+    }
 
-                #[cfg(not(target_os = "windows"))]
-                {
-                    let _ = child.kill();
-                }
+    /// Pause the narration in progress, returning false if there is none
+    pub fn pause(&self) -> bool {
+        let guard = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        match guard.as_ref() {
+            Some(session) if !session.play_thread.is_finished() => {
+                session.paused.store(true, Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Continue a paused narration, returning false if there is none
+    pub fn resume(&self) -> bool {
+        let guard = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        match guard.as_ref() {
+            Some(session) if session.paused.load(Ordering::Relaxed) => {
+                session.paused.store(false, Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
 
-                let _ = child.wait();
+    /// Stop the chunk that is playing and move on to the next one
+    pub fn skip(&self) -> Result<()> {
+        if let Ok(guard) = self.session.lock() {
+            if let Some(session) = guard.as_ref() {
+                session.skip_requested.store(true, Ordering::Relaxed);
             }
         }
         Ok(())
     }
 
     /// Speak the given text using Piper TTS
-    /// This spawns a non-blocking process that pipes Piper output to an audio player
+    /// Synthesis and playback run in the background; this returns right away.
     pub fn speak(&self, text: &str) -> Result<()> {
         if text.trim().is_empty() {
             return Err(anyhow!("No text to speak"));
@@ -242,127 +652,1366 @@ impl Narrator {
         // Stop any current playback first
         self.stop()?;
 
-        #[cfg(target_os = "windows")]
+        let session_id = self.sessions_started.fetch_add(1, Ordering::Relaxed);
+        let temp_dir = &self.config.temp_dir;
+        disk::ensure_free_space(&temp_dir.join("tts_output.wav"), self.config.min_free_disk_mb)?;
+
+        let model_path = self.model_for(text);
+        let (chunks, paragraph_starts): (Vec<String>, Vec<bool>) =
+            split_chunks(text, self.config.split_sentences)
+                .into_iter()
+                .unzip();
+        let chunk_paths: Vec<PathBuf> = (0..chunks.len())
+            .map(|index| {
+                temp_dir.join(format!(
+                    "tts_output_{}_{}_{}.wav",
+                    std::process::id(),
+                    session_id,
+                    index
+                ))
+            })
+            .collect();
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let skip_requested = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(false));
+        let (ready_tx, ready_rx) = mpsc::sync_channel(lookahead_bound(self.config.lookahead));
+
+        let synthesizer = self.synthesizer(cancelled.clone());
+        let events = self.events.clone().filter(|_| self.config.word_events);
+        let player = ChunkPlayer {
+            config: self.config.clone(),
+            device: self.preferred_output_device(),
+            cancelled: cancelled.clone(),
+            skip_requested: skip_requested.clone(),
+            paused: paused.clone(),
+            paragraph_starts,
+            chunks: if events.is_some() {
+                chunks.clone()
+            } else {
+                Vec::new()
+            },
+            events,
+        };
+        let synth_thread = thread::spawn(move || {
+            synthesizer.run(&chunks, &chunk_paths, &model_path, ready_tx)
+        });
+
+        let play_thread = thread::spawn(move || player.run(ready_rx));
+
+        if let Ok(mut guard) = self.session.lock() {
+            *guard = Some(Session {
+                cancelled,
+                skip_requested,
+                paused,
+                synth_thread,
+                play_thread,
+            });
+        }
+        Ok(())
+    }
+
+    /// Synthesize `text` into a WAV file at `output_path` on a background thread, without
+    /// playing it
+    /// The whole text goes to Piper in one run, with the voice and speed `speak` would use.
+    pub fn synthesize_to_file(
+        &self,
+        text: &str,
+        output_path: &Path,
+    ) -> Result<JoinHandle<Result<()>>> {
+        if text.trim().is_empty() {
+            return Err(anyhow!("No text to speak"));
+        }
+        if let Some(dir) = output_path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
         {
-            use std::io::Write;
-            use std::os::windows::process::CommandExt;
+            std::fs::create_dir_all(dir)?;
+        }
+        disk::ensure_free_space(output_path, self.config.min_free_disk_mb)?;
 
-            let temp_audio = env::temp_dir().join("tts_output.wav");
+        let text = text.to_string();
+        let output_path = output_path.to_path_buf();
+        let model_path = self.model_for(&text);
+        let synthesizer = self.synthesizer(Arc::new(AtomicBool::new(false)));
+        Ok(thread::spawn(move || {
+            let written = synthesizer.synthesize_retrying(&text, &model_path, &output_path)?;
+            if written != output_path {
+                std::fs::rename(&written, &output_path)?;
+            }
+            if synthesizer.config.check_sample_rate {
+                if let Some(rate) = voice_sample_rate(&model_path) {
+                    set_sample_rate(&output_path, rate)?;
+                }
+            }
+            Ok(())
+        }))
+    }
 
-            // Debug: print the command being run
-            //println!(
-            //    self.config.piper_path.display(),
-            //    self.config.model_path.display(),
-            //    temp_audio.display()
-            //);
+    /// New 'speech_<unix time in ms>.wav' in TTS_SAVE_DIR, or the working directory
+    pub fn saved_speech_path(&self) -> PathBuf {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let dir = self.config.save_dir.as_deref().unwrap_or(Path::new(""));
+        dir.join(format!("speech_{}.wav", millis))
+    }
 
-            // Select model based on detected language
-            let model_path = self.config.get_model_for_text(text);
+    /// The selected voice's model, or the one for the language detected in `text`
+    fn model_for(&self, text: &str) -> PathBuf {
+        match self.voice_model() {
+            Some(model_path) => model_path,
+            None => self.config.get_model_for_text(text).clone(),
+        }
+    }
 
-            // Run Piper to generate WAV file
-            // --length-scale: <1.0 = faster, >1.0 = slower (default 1.0)
-            let piper_result = Command::new(&self.config.piper_path)
-                .arg("--model")
-                .arg(model_path)
-                .arg("--length-scale")
-                .arg(self.config.speed.to_string())
-                .arg("--output_file")
-                .arg(&temp_audio)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .creation_flags(0x08000000) // CREATE_NO_WINDOW
-                .spawn();
-
-            match piper_result {
-                Ok(mut child) => {
-                    // Write text to Piper's stdin
-                    if let Some(ref mut stdin) = child.stdin {
-                        let _ = stdin.write_all(text.as_bytes());
-                    }
-                    // Drop stdin to signal EOF
-                    drop(child.stdin.take());
-
-                    // Wait for Piper to finish generating audio
-                    let output = child.wait_with_output()?;
-                    if !output.status.success() {
-                        let stdout = String::from_utf8_lossy(&output.stdout);
-                        let stderr = String::from_utf8_lossy(&output.stderr);
-                        return Err(anyhow!(
-                            "Piper failed (exit code {:?}): stdout='{}' stderr='{}'",
-                            output.status.code(),
-                            stdout.trim(),
-                            stderr.trim()
-                        ));
-                    }
+    /// Piper runner using the current speed, stopping once `cancelled` is set
+    fn synthesizer(&self, cancelled: Arc<AtomicBool>) -> Synthesizer {
+        let mut config = self.config.clone();
+        config.speed = *self.speed.lock().unwrap_or_else(|e| e.into_inner());
+        Synthesizer {
+            config,
+            length_scale_supported: self.length_scale_supported.clone(),
+            limiter: self.limiter.clone(),
+            cancelled,
+            piper_server: self.piper_server.clone(),
+        }
+    }
+
+    /// The most preferred connected device from OUTPUT_DEVICES, looked up afresh so
+    /// plugging in headphones takes effect on the next utterance
+    fn preferred_output_device(&self) -> Option<(String, cpal::Device)> {
+        if self.config.output_devices.is_empty() {
+            return None;
+        }
+        let available = match audio::list_output_devices() {
+            Ok(available) => available,
+            Err(e) => {
+                warn!("Could not list output devices: {}", e);
+                return None;
+            }
+        };
+        let name = audio::select_by_preference(&self.config.output_devices, &available)?;
+        match audio::find_output_device(name) {
+            Ok(device) => Some((name.to_string(), device)),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        }
+    }
+}
+
+/// How much one press of the faster/slower hotkeys changes the length scale
+pub const SPEED_STEP: f32 = 0.1;
 
-                    // Play the audio file using PowerShell (non-blocking)
-                    // Escape single quotes to prevent PowerShell command injection
-                    let safe_path = temp_audio.display().to_string().replace("'", "''");
-                    let player = Command::new("powershell")
-                        .args([
-                            "-NoProfile",
-                            "-WindowStyle",
-                            "Hidden",
-                            "-Command",
-                            &format!(
-                                "(New-Object Media.SoundPlayer '{}').PlaySync()",
-                                safe_path
-                            ),
-                        ])
-                        .creation_flags(0x08000000) // CREATE_NO_WINDOW
-                        .spawn()?;
-
-                    // Store the player process for cancellation
-                    if let Ok(mut guard) = self.current_process.lock() {
-                        *guard = Some(player);
+/// Bounds of the length scale `set_speed` accepts
+const MIN_SPEED: f32 = 0.5;
+const MAX_SPEED: f32 = 2.0;
+
+/// Keep a length scale within bounds, rounded to the 0.1 steps the hotkeys use
+fn clamp_speed(speed: f32) -> f32 {
+    if !speed.is_finite() {
+        return 1.0;
+    }
+    ((speed * 10.0).round() / 10.0).clamp(MIN_SPEED, MAX_SPEED)
+}
+
+/// Capacity of the channel between synthesis and playback for a look-ahead of `lookahead`
+/// chunks: the synthesis thread holds one more finished chunk while it waits to send it.
+fn lookahead_bound(lookahead: usize) -> usize {
+    lookahead.max(1) - 1
+}
+
+/// Runs Piper for the chunks of one session, on the synthesis thread
+struct Synthesizer {
+    config: NarratorConfig,
+    length_scale_supported: Arc<AtomicBool>,
+    limiter: Arc<HeavyOpLimiter>,
+    cancelled: Arc<AtomicBool>,
+    piper_server: Arc<Mutex<Option<PiperServer>>>,
+}
+
+impl Synthesizer {
+    /// Synthesize `chunks` into `paths` one by one, handing each to the player when done
+    /// Stops at the first failure, on cancellation, or once the player is gone.
+    fn run(&self, chunks: &[String], paths: &[PathBuf], model_path: &Path, ready: SyncSender<PathBuf>) {
+        let voice_rate = if self.config.check_sample_rate {
+            voice_sample_rate(model_path)
+        } else {
+            None
+        };
+        let mut mismatch_reported = false;
+        let mut saver = self.config.save_dir.as_deref().map(ClipSaver::new);
+        for (chunk, path) in chunks.iter().zip(paths) {
+            if self.cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            let path = match self.synthesize_retrying(chunk, model_path, path) {
+                Ok(path) => path,
+                Err(e) => {
+                    if !self.cancelled.load(Ordering::Relaxed) {
+                        error!("TTS failed: {}", e);
+                        if self.config.notifications {
+                            notify::show("TTS failed", &e.to_string());
+                        }
                     }
+                    return;
                 }
-                Err(e) => {
-                    return Err(anyhow!("Failed to start Piper: {}", e));
+            };
+            if let Some(voice_rate) = voice_rate {
+                match set_sample_rate(&path, voice_rate) {
+                    Ok(Some(written_rate)) if !mismatch_reported => {
+                        warn!(
+                            "Piper wrote {} Hz audio but the voice is {} Hz, playing it at {} Hz",
+                            written_rate, voice_rate, voice_rate
+                        );
+                        mismatch_reported = true;
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Could not check the sample rate: {}", e),
                 }
             }
+            if let Some(clip) = saver.as_mut() {
+                if let Err(e) = clip.append(&path) {
+                    warn!("Failed to save the speech: {}", e);
+                    saver = None;
+                }
+            }
+            if let Err(SendError(path)) = ready.send(path) {
+                let _ = std::fs::remove_file(path);
+                return;
+            }
         }
+    }
 
-        #[cfg(not(target_os = "windows"))]
-        {
-            use std::io::Write;
+    /// Synthesize one chunk, retrying up to PIPER_RETRIES times with a backoff
+    /// Every attempt writes to a fresh file, in case the failed one is still locked.
+    /// Returns the file that was written, or the last error (`Cancelled` when cancelled).
+    fn synthesize_retrying(&self, text: &str, model_path: &Path, path: &Path) -> Result<PathBuf> {
+        let mut attempt = 0;
+        loop {
+            let attempt_path = retry_path(path, attempt);
+            let error = match self.synthesize_to(text, model_path, &attempt_path) {
+                Ok(()) => return Ok(attempt_path),
+                Err(e) => e,
+            };
+            let _ = std::fs::remove_file(&attempt_path);
+            if self.cancelled.load(Ordering::Relaxed) {
+                return Err(Cancelled.into());
+            }
+            if attempt >= self.config.retries {
+                return Err(error);
+            }
+            attempt += 1;
+            warn!(
+                "Piper failed, retrying ({}/{}): {}",
+                attempt, self.config.retries, error
+            );
+            let backoff = self.config.retry_backoff * 2u32.pow((attempt - 1).min(5));
+            if !wait_unless_cancelled(&self.cancelled, backoff) {
+                return Err(Cancelled.into());
+            }
+        }
+    }
 
-            let player_cmd = if cfg!(target_os = "macos") {
-                "afplay"
-            } else {
-                "aplay"
+    /// Synthesize `text` into a WAV file at `output_path`, without --length-scale
+    /// if this Piper build turns out to reject it
+    fn synthesize_to(&self, text: &str, model_path: &Path, output_path: &Path) -> Result<()> {
+        // Only ask for a speed when one is configured, since some Piper builds reject the flag
+        let use_length_scale =
+            self.config.speed != 1.0 && self.length_scale_supported.load(Ordering::Relaxed);
+
+        if self.config.persistent {
+            let synthesize = |length_scale| {
+                self.synthesize_with_retry(|| {
+                    self.synthesize_persistent(text, model_path, output_path, length_scale)
+                })
+            };
+            return match synthesize(use_length_scale) {
+                Err(e) if use_length_scale && mentions_length_scale_rejection(&e.to_string()) => {
+                    warn!("This Piper build/model doesn't support --length-scale, speed control is unavailable.");
+                    self.length_scale_supported.store(false, Ordering::Relaxed);
+                    synthesize(false)
+                }
+                result => result,
             };
+        }
+
+        let mut output = self.synthesize_with_retry(|| {
+            self.synthesize(text, model_path, output_path, use_length_scale)
+        })?;
+
+        if !output.status.success() && use_length_scale && rejects_length_scale(&output) {
+            warn!("This Piper build/model doesn't support --length-scale, speed control is unavailable.");
+            self.length_scale_supported.store(false, Ordering::Relaxed);
+            output = self
+                .synthesize_with_retry(|| self.synthesize(text, model_path, output_path, false))?;
+        }
+
+        if !output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!(
+                "Piper failed (exit code {:?}): stdout='{}' stderr='{}'",
+                output.status.code(),
+                stdout.trim(),
+                stderr.trim()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Run `attempt`, killing and restarting Piper once if it stalls
+    fn synthesize_with_retry<T>(&self, attempt: impl Fn() -> Result<T>) -> Result<T> {
+        match attempt() {
+            Err(e)
+                if e.downcast_ref::<Cancelled>().is_some()
+                    && !self.cancelled.load(Ordering::Relaxed) =>
+            {
+                warn!(
+                    "Piper stalled for over {}ms, restarting it and retrying...",
+                    self.stall_timeout_ms()
+                );
+                attempt().map_err(|e| match e.downcast_ref::<Cancelled>() {
+                    Some(_) if !self.cancelled.load(Ordering::Relaxed) => anyhow!(
+                        "Piper stalled twice (no result within {}ms, see PIPER_TIMEOUT_MS)",
+                        self.stall_timeout_ms()
+                    ),
+                    _ => e,
+                })
+            }
+            result => result,
+        }
+    }
 
-            let temp_audio = env::temp_dir().join("tts_output.wav");
+    fn stall_timeout_ms(&self) -> u128 {
+        self.config.stall_timeout.map_or(0, |timeout| timeout.as_millis())
+    }
 
-            let model_path = self.config.get_model_for_text(text);
+    /// Whether Piper has been at it for longer than the stall timeout, or the session
+    /// was cancelled
+    fn should_stop(&self, started: Instant) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self
+                .config
+                .stall_timeout
+                .is_some_and(|timeout| started.elapsed() > timeout)
+    }
 
-            let mut piper = Command::new(&self.config.piper_path)
-                .arg("--model")
-                .arg(model_path)
+    /// Piper command for `model_path`, with `output_args` saying where the audio goes
+    /// followed by PIPER_EXTRA_ARGS
+    fn piper_command(
+        &self,
+        model_path: &Path,
+        length_scale: bool,
+        output_args: &[&OsStr],
+    ) -> Command {
+        let mut command = Command::new(&self.config.piper_path);
+        command.arg("--model").arg(model_path);
+        if length_scale {
+            // --length-scale: <1.0 = faster, >1.0 = slower (default 1.0)
+            command
                 .arg("--length-scale")
-                .arg(self.config.speed.to_string())
-                .arg("--output_file")
-                .arg(&temp_audio)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .spawn()?;
+                .arg(self.config.speed.to_string());
+        }
+        command.args(output_args).args(&self.config.extra_args);
+
+        #[cfg(target_os = "windows")]
+        {
+            use std::os::windows::process::CommandExt;
+            command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        }
+
+        if self.config.verbose {
+            debug!(
+                "Piper command: {}",
+                format_command(&command, self.config.log_sensitive)
+            );
+        }
+        command
+    }
+
+    /// Run Piper to write `text` as a WAV file, waiting for it to finish
+    /// Piper is killed with a `Cancelled` error if it runs past the stall timeout
+    /// or the session is cancelled.
+    fn synthesize(
+        &self,
+        text: &str,
+        model_path: &Path,
+        output_path: &Path,
+        length_scale: bool,
+    ) -> Result<Output> {
+        let mut command = self.piper_command(
+            model_path,
+            length_scale,
+            &[OsStr::new("--output_file"), output_path.as_os_str()],
+        );
+        if self.config.verbose && self.config.log_sensitive {
+            debug!("Piper input: {:?}", text);
+        }
+
+        // Text goes in on stdin; closing it tells Piper to start synthesizing
+        let Some(_permit) = self
+            .limiter
+            .acquire_unless("speech synthesis", &self.cancelled)
+        else {
+            return Err(Cancelled.into());
+        };
+        let started = Instant::now();
+        run_with_input(&mut command, Some(text.as_bytes()), &|| {
+            self.should_stop(started)
+        })
+        .map_err(|e| match e.downcast_ref::<Cancelled>() {
+            Some(_) => e,
+            None => anyhow!("Failed to start Piper: {}", e),
+        })
+    }
 
-            if let Some(ref mut stdin) = piper.stdin {
-                let _ = stdin.write_all(text.as_bytes());
+    /// Have the Piper kept running with PIPER_PERSISTENT write `text` to `output_path`,
+    /// starting it first if it isn't running with this model and speed
+    /// Like `synthesize`, gives up with `Cancelled` past the stall timeout. After that or
+    /// any other failure the process is killed, so the next attempt starts a fresh one.
+    fn synthesize_persistent(
+        &self,
+        text: &str,
+        model_path: &Path,
+        output_path: &Path,
+        length_scale: bool,
+    ) -> Result<()> {
+        let command = self.piper_command(model_path, length_scale, &[OsStr::new("--json-input")]);
+        if self.config.verbose && self.config.log_sensitive {
+            debug!("Piper input: {:?}", text);
+        }
+
+        let Some(_permit) = self
+            .limiter
+            .acquire_unless("speech synthesis", &self.cancelled)
+        else {
+            return Err(Cancelled.into());
+        };
+        let mut running = self.piper_server.lock().unwrap_or_else(|e| e.into_inner());
+        let mut server = match running.take() {
+            Some(server) if server.runs(&command) => server,
+            old => {
+                // Stop the old one before loading another model
+                drop(old);
+                PiperServer::spawn(command)?
             }
-            drop(piper.stdin.take());
-            piper.wait()?;
+        };
+        let started = Instant::now();
+        server.synthesize(text, output_path, &|| self.should_stop(started))?;
+        *running = Some(server);
+        Ok(())
+    }
+}
 
-            let player = Command::new(player_cmd).arg(&temp_audio).spawn()?;
+/// Sleep for `duration`, returning false if `cancelled` is set meanwhile
+fn wait_unless_cancelled(cancelled: &AtomicBool, duration: Duration) -> bool {
+    let until = Instant::now() + duration;
+    while Instant::now() < until {
+        if cancelled.load(Ordering::Relaxed) {
+            return false;
+        }
+        thread::sleep(PLAYBACK_POLL.min(until - Instant::now()));
+    }
+    !cancelled.load(Ordering::Relaxed)
+}
 
-            if let Ok(mut guard) = self.current_process.lock() {
-                *guard = Some(player);
+/// Output file for a retry: the chunk's own path for the first attempt, then
+/// 'tts_output_1_0_retry1.wav' and so on
+fn retry_path(path: &Path, attempt: u32) -> PathBuf {
+    if attempt == 0 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_retry{}.wav", stem, attempt))
+}
+
+/// Plays the synthesized chunks of one session in order, on the playback thread
+struct ChunkPlayer {
+    config: NarratorConfig,
+    device: Option<(String, cpal::Device)>,
+    cancelled: Arc<AtomicBool>,
+    skip_requested: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    /// Whether each chunk begins a paragraph, so PARAGRAPH_GAP_MS goes before it
+    paragraph_starts: Vec<bool>,
+    /// Text of each chunk, only kept when word events are published
+    chunks: Vec<String>,
+    events: Option<EventBus>,
+}
+
+impl ChunkPlayer {
+    /// Play chunks as they arrive until synthesis is done or the session is cancelled
+    /// Chunk files are deleted once played; after a cancel, chunks that were synthesized
+    /// ahead are deleted unplayed.
+    fn run(&self, ready: Receiver<PathBuf>) {
+        // The stream lives on this thread; it can't be moved to another one
+        let (_stream, output) = match self.open_output() {
+            Ok(output) => output,
+            Err(e) => {
+                error!("TTS playback failed: {}", e);
+                if self.config.notifications {
+                    notify::show("TTS playback failed", &e.to_string());
+                }
+                for path in ready.iter() {
+                    let _ = std::fs::remove_file(path);
+                }
+                return;
             }
+        };
+        let gap = self.config.paragraph_gap.unwrap_or_default();
+        let mut chunks = self.chunks.iter();
+        let mut paragraph_starts = self.paragraph_starts.iter();
+        let mut word_index = 0;
+        for (index, path) in ready.iter().enumerate() {
+            let text = chunks.next();
+            let new_paragraph = paragraph_starts.next().copied().unwrap_or(true);
+            if !self.wait(pause_before(index, new_paragraph, gap)) {
+                let _ = std::fs::remove_file(&path);
+                break;
+            }
+            if !self.wait_while_paused() || self.cancelled.load(Ordering::Relaxed) {
+                let _ = std::fs::remove_file(&path);
+                break;
+            }
+            self.skip_requested.store(false, Ordering::Relaxed);
+            let mut words = self.word_track(&path, text, word_index);
+            if let Err(e) = self.play(&output, &path, &mut words) {
+                error!("TTS playback failed: {}", e);
+                if self.config.notifications {
+                    notify::show("TTS playback failed", &e.to_string());
+                }
+            }
+            word_index += text.map_or(0, |text| text.split_whitespace().count());
+            let _ = std::fs::remove_file(&path);
         }
 
+        // Synthesis stops promptly once cancelled, ending this loop
+        for path in ready.iter() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    /// Sleep for `duration`, returning false if the session is cancelled meanwhile
+    fn wait(&self, duration: Duration) -> bool {
+        wait_unless_cancelled(&self.cancelled, duration)
+    }
+
+    /// Hold off starting the next chunk while paused, returning false if the session is
+    /// cancelled meanwhile
+    fn wait_while_paused(&self) -> bool {
+        while self.paused.load(Ordering::Relaxed) {
+            if self.cancelled.load(Ordering::Relaxed) {
+                return false;
+            }
+            thread::sleep(PLAYBACK_POLL);
+        }
+        true
+    }
+
+    /// Pass a change of the session's pause flag on through `set_paused`, keeping the time
+    /// spent paused out of the word timings; returns whether playback is paused
+    fn follow_pause(
+        &self,
+        paused_since: &mut Option<Instant>,
+        words: &mut Option<&mut WordTrack>,
+        set_paused: impl FnOnce(bool),
+    ) -> bool {
+        let paused = self.paused.load(Ordering::Relaxed);
+        if paused != paused_since.is_some() {
+            set_paused(paused);
+            match paused_since.take() {
+                Some(since) => {
+                    if let Some(words) = words.as_mut() {
+                        words.delay(since.elapsed());
+                    }
+                }
+                None => *paused_since = Some(Instant::now()),
+            }
+        }
+        paused
+    }
+
+    fn stop_requested(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || self.skip_requested.load(Ordering::Relaxed)
+    }
+
+    /// Word timings for the chunk at `path` with `text`, if word events are published
+    fn word_track<'a>(
+        &'a self,
+        path: &Path,
+        text: Option<&'a String>,
+        first_index: usize,
+    ) -> Option<WordTrack<'a>> {
+        let (events, text) = (self.events.as_ref()?, text?);
+        let duration = match wav_duration(path) {
+            Ok(duration) => duration,
+            Err(e) => {
+                warn!("No word events for this paragraph: {}", e);
+                return None;
+            }
+        };
+        Some(WordTrack {
+            events,
+            words: word_timings(text, duration),
+            first_index,
+            next: 0,
+            started: Instant::now(),
+        })
+    }
+
+    /// Open the preferred OUTPUT_DEVICES device, falling back to the system default
+    fn open_output(&self) -> Result<(OutputStream, OutputStreamHandle)> {
+        if let Some((name, device)) = &self.device {
+            match OutputStream::try_from_device(device) {
+                Ok(output) => {
+                    if self.config.verbose {
+                        debug!("Playing on output device '{}'", name);
+                    }
+                    return Ok(output);
+                }
+                Err(e) => warn!(
+                    "Could not play on '{}', using the default device: {}",
+                    name, e
+                ),
+            }
+        }
+        OutputStream::try_default().map_err(|e| anyhow!("No audio output device: {}", e))
+    }
+
+    /// Play one chunk to the end, or until a stop or skip is requested
+    /// Word events in `words` are published as playback reaches them.
+    fn play(
+        &self,
+        output: &OutputStreamHandle,
+        path: &Path,
+        words: &mut Option<WordTrack>,
+    ) -> Result<()> {
+        let file = File::open(path)
+            .map_err(|e| anyhow!("Failed to read Piper output '{}': {}", path.display(), e))?;
+        let source = Decoder::new(BufReader::new(file))
+            .map_err(|e| anyhow!("Failed to decode Piper output '{}': {}", path.display(), e))?;
+        let sink = Sink::try_new(output)?;
+        let channel_map = &self.config.channel_map;
+        if channel_map.is_passthrough() {
+            sink.append(source);
+        } else {
+            sink.append(ChannelVolume::new(source, channel_map.volumes()));
+        }
+
+        let mut words = words.as_mut().map(WordTrack::restart);
+        let mut paused_since = None;
+        while !sink.empty() {
+            if self.stop_requested() {
+                sink.stop();
+                break;
+            }
+            let paused = self.follow_pause(&mut paused_since, &mut words, |paused| {
+                if paused {
+                    sink.pause()
+                } else {
+                    sink.play()
+                }
+            });
+            if let (false, Some(words)) = (paused, words.as_mut()) {
+                words.publish_due();
+            }
+            thread::sleep(PLAYBACK_POLL);
+        }
+        Ok(())
+    }
+}
+
+/// Publishes `Event::Word` for one chunk as its playback reaches each word
+struct WordTrack<'a> {
+    events: &'a EventBus,
+    /// Estimated start of each word within the chunk
+    words: Vec<(Duration, &'a str)>,
+    /// Index of the chunk's first word within the whole text
+    first_index: usize,
+    next: usize,
+    started: Instant,
+}
+
+impl WordTrack<'_> {
+    /// Count time from now, when playback actually started
+    fn restart(&mut self) -> &mut Self {
+        self.started = Instant::now();
+        self
+    }
+
+    /// Push the remaining words back by `pause`, the time playback was held
+    fn delay(&mut self, pause: Duration) {
+        self.started += pause;
+    }
+
+    /// Publish every word whose start has been reached and wasn't published yet
+    fn publish_due(&mut self) {
+        let elapsed = self.started.elapsed();
+        while let Some(&(offset, word)) = self.words.get(self.next) {
+            if offset > elapsed {
+                break;
+            }
+            self.events.publish(Event::Word {
+                index: self.first_index + self.next,
+                word: word.to_string(),
+                offset,
+            });
+            self.next += 1;
+        }
+    }
+}
+
+/// Estimated start of each whitespace-separated word of `text` when spoken over `duration`
+/// Piper doesn't report word timing, so the time is shared out in proportion to each
+/// word's length plus one for the pause after it; starts strictly increase.
+fn word_timings(text: &str, duration: Duration) -> Vec<(Duration, &str)> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let weight = |word: &str| word.chars().count() + 1;
+    let total: usize = words.iter().map(|word| weight(word)).sum();
+    let mut elapsed = 0;
+    words
+        .into_iter()
+        .map(|word| {
+            let start = duration.mul_f64(elapsed as f64 / total.max(1) as f64);
+            elapsed += weight(word);
+            (start, word)
+        })
+        .collect()
+}
+
+/// Playing time of a WAV file
+fn wav_duration(path: &Path) -> Result<Duration> {
+    let reader = hound::WavReader::open(path)
+        .map_err(|e| anyhow!("Failed to read Piper output '{}': {}", path.display(), e))?;
+    let rate = reader.spec().sample_rate.max(1) as f64;
+    Ok(Duration::from_secs_f64(reader.duration() as f64 / rate))
+}
+
+/// Collects every chunk of one `speak` into a single WAV file in TTS_SAVE_DIR
+/// The file is created with the first chunk and finished when this is dropped.
+struct ClipSaver {
+    path: PathBuf,
+    writer: Option<hound::WavWriter<std::io::BufWriter<File>>>,
+}
+
+impl ClipSaver {
+    /// Saver for a new 'tts_<unix time in ms>.wav' in `dir`
+    fn new(dir: &Path) -> Self {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        Self {
+            path: dir.join(format!("tts_{}.wav", millis)),
+            writer: None,
+        }
+    }
+
+    /// Append the audio of the chunk at `path`
+    fn append(&mut self, path: &Path) -> Result<()> {
+        let mut reader = hound::WavReader::open(path)
+            .map_err(|e| anyhow!("Failed to read Piper output '{}': {}", path.display(), e))?;
+        let spec = reader.spec();
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => {
+                if let Some(dir) = self.path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                self.writer
+                    .insert(hound::WavWriter::create(&self.path, spec)?)
+            }
+        };
+        if writer.spec() != spec {
+            return Err(anyhow!("Piper's output format changed between chunks"));
+        }
+        match spec.sample_format {
+            hound::SampleFormat::Int => {
+                for sample in reader.samples::<i32>() {
+                    writer.write_sample(sample?)?;
+                }
+            }
+            hound::SampleFormat::Float => {
+                for sample in reader.samples::<f32>() {
+                    writer.write_sample(sample?)?;
+                }
+            }
+        }
         Ok(())
     }
 }
+
+impl Drop for ClipSaver {
+    fn drop(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            match writer.finalize() {
+                Ok(()) => info!("Speech saved to '{}'.", self.path.display()),
+                Err(e) => warn!("Failed to save the speech: {}", e),
+            }
+        }
+    }
+}
+
+/// The "audio" section of a Piper voice's .onnx.json
+#[derive(Deserialize)]
+struct VoiceConfig {
+    audio: VoiceAudio,
+}
+
+#[derive(Deserialize)]
+struct VoiceAudio {
+    sample_rate: u32,
+}
+
+/// Sample rate of the voice at `model_path`, from the '<model>.json' Piper keeps next to it
+/// None (with a warning) if the file is missing or has no usable rate.
+fn voice_sample_rate(model_path: &Path) -> Option<u32> {
+    let mut json_path = model_path.as_os_str().to_owned();
+    json_path.push(".json");
+    let json_path = PathBuf::from(json_path);
+    let result = std::fs::read_to_string(&json_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| parse_voice_sample_rate(&json));
+    match result {
+        Ok(rate) => Some(rate),
+        Err(e) => {
+            warn!(
+                "Could not read the voice's sample rate from '{}': {}",
+                json_path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Sample rate in a Piper voice config ('{"audio": {"sample_rate": 22050}, ...}')
+fn parse_voice_sample_rate(json: &str) -> Result<u32> {
+    let config: VoiceConfig =
+        serde_json::from_str(json).context("Failed to parse the voice config")?;
+    if config.audio.sample_rate == 0 {
+        return Err(anyhow!("The voice config has a sample rate of 0"));
+    }
+    Ok(config.audio.sample_rate)
+}
+
+/// Rewrite the WAV at `path` to play at `rate` if its header says otherwise
+/// The samples are kept as they are, only the rate changes. Returns the rate the header
+/// had if it was rewritten.
+fn set_sample_rate(path: &Path, rate: u32) -> Result<Option<u32>> {
+    let mut reader = hound::WavReader::open(path)
+        .map_err(|e| anyhow!("Failed to read Piper output '{}': {}", path.display(), e))?;
+    let spec = reader.spec();
+    if spec.sample_rate == rate {
+        return Ok(None);
+    }
+    let corrected = hound::WavSpec {
+        sample_rate: rate,
+        ..spec
+    };
+    match spec.sample_format {
+        hound::SampleFormat::Int => {
+            let samples = reader.samples::<i32>().collect::<Result<Vec<i32>, _>>()?;
+            drop(reader);
+            let mut writer = hound::WavWriter::create(path, corrected)?;
+            for sample in samples {
+                writer.write_sample(sample)?;
+            }
+            writer.finalize()?;
+        }
+        hound::SampleFormat::Float => {
+            let samples = reader.samples::<f32>().collect::<Result<Vec<f32>, _>>()?;
+            drop(reader);
+            let mut writer = hound::WavWriter::create(path, corrected)?;
+            for sample in samples {
+                writer.write_sample(sample)?;
+            }
+            writer.finalize()?;
+        }
+    }
+    Ok(Some(spec.sample_rate))
+}
+
+/// The chunks `text` is synthesized in, each with whether it begins a paragraph
+/// Paragraphs are split into sentences when `by_sentence` is set (TTS_SPLIT_SENTENCES).
+fn split_chunks(text: &str, by_sentence: bool) -> Vec<(String, bool)> {
+    let mut chunks = Vec::new();
+    for paragraph in split_paragraphs(text) {
+        let pieces = if by_sentence {
+            split_sentences(paragraph)
+        } else {
+            vec![paragraph]
+        };
+        for (index, piece) in pieces.into_iter().enumerate() {
+            chunks.push((piece.trim().to_string(), index == 0));
+        }
+    }
+    chunks
+}
+
+/// Silence to play before chunk `index`: `gap` when it begins a paragraph other than
+/// the first, none within a paragraph
+fn pause_before(index: usize, paragraph_start: bool, gap: Duration) -> Duration {
+    if index > 0 && paragraph_start {
+        gap
+    } else {
+        Duration::ZERO
+    }
+}
+
+/// Paragraphs of `text`, split on blank lines, without empty ones
+fn split_paragraphs(text: &str) -> Vec<&str> {
+    let mut paragraphs = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            paragraphs.push(&text[start..offset]);
+            start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    paragraphs.push(&text[start..]);
+    paragraphs.retain(|paragraph| !paragraph.trim().is_empty());
+    paragraphs
+}
+
+/// Words that end in a period without ending the sentence, lowercase and without the period
+const ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "dr", "dra", "prof", "sr", "sra", "jr", "st", "vs", "etc", "e.g", "i.e",
+    "fig", "approx",
+];
+
+/// Sentences of `paragraph`, split after '.', '!', '?' or '…' followed by whitespace, and at
+/// line breaks; a period after a known abbreviation or a single letter (an initial) doesn't
+/// end a sentence. Closing quotes and brackets stay with their sentence.
+fn split_sentences(paragraph: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = paragraph.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let end = match c {
+            '\n' => index + 1,
+            '.' | '!' | '?' | '…' => {
+                let mut end = index + c.len_utf8();
+                while let Some(&(next_index, next)) = chars.peek() {
+                    if !matches!(
+                        next,
+                        '.' | '!' | '?' | '"' | '\'' | ')' | ']' | '”' | '’' | '»'
+                    ) {
+                        break;
+                    }
+                    end = next_index + next.len_utf8();
+                    chars.next();
+                }
+                let at_break = !matches!(chars.peek(), Some(&(_, next)) if !next.is_whitespace());
+                if !at_break || (c == '.' && ends_with_abbreviation(&paragraph[start..index])) {
+                    continue;
+                }
+                end
+            }
+            _ => continue,
+        };
+        sentences.push(&paragraph[start..end]);
+        start = end;
+    }
+    sentences.push(&paragraph[start..]);
+    sentences.retain(|sentence| !sentence.trim().is_empty());
+    sentences
+}
+
+/// Whether the last word of `text` is an abbreviation or initial that takes a period
+fn ends_with_abbreviation(text: &str) -> bool {
+    let word = text
+        .rsplit(char::is_whitespace)
+        .next()
+        .unwrap_or("")
+        .trim_start_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase();
+    let mut letters = word.chars();
+    let is_initial =
+        matches!((letters.next(), letters.next()), (Some(c), None) if c.is_alphabetic());
+    is_initial || ABBREVIATIONS.contains(&word.as_str())
+}
+
+/// Whether a failed Piper run complained about the --length-scale flag itself
+fn rejects_length_scale(output: &Output) -> bool {
+    [&output.stderr, &output.stdout]
+        .iter()
+        .any(|text| mentions_length_scale_rejection(&String::from_utf8_lossy(text)))
+}
+
+/// Whether Piper's `message` complains about the --length-scale flag
+fn mentions_length_scale_rejection(message: &str) -> bool {
+    let text = message.to_lowercase();
+    (text.contains("length-scale") || text.contains("length_scale"))
+        && (text.contains("unrecognized")
+            || text.contains("unknown")
+            || text.contains("unexpected argument")
+            || text.contains("not supported"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Settings as loaded from an empty config, running `piper_path`
+    fn test_config(piper_path: PathBuf, temp_dir: PathBuf) -> NarratorConfig {
+        NarratorConfig {
+            piper_path,
+            models: HashMap::new(),
+            default_model: PathBuf::from("voice.onnx"),
+            voices: Vec::new(),
+            speed: 1.0,
+            extra_args: Vec::new(),
+            notifications: false,
+            verbose: false,
+            log_sensitive: false,
+            stall_timeout: Some(Duration::from_millis(30000)),
+            persistent: false,
+            retries: 0,
+            retry_backoff: Duration::from_millis(200),
+            min_free_disk_mb: 0,
+            temp_dir,
+            channel_map: ChannelMap::default(),
+            paragraph_gap: None,
+            split_sentences: true,
+            lookahead: 1,
+            output_devices: Vec::new(),
+            word_events: false,
+            check_sample_rate: false,
+            save_dir: None,
+        }
+    }
+
+    fn synthesizer(config: NarratorConfig) -> Synthesizer {
+        Synthesizer {
+            config,
+            length_scale_supported: Arc::new(AtomicBool::new(true)),
+            limiter: Arc::new(HeavyOpLimiter::new(0)),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            piper_server: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Write a shell script standing in for Piper into a fresh temp folder
+    #[cfg(unix)]
+    fn fake_piper(name: &str, script: &str) -> (PathBuf, PathBuf) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join(format!("fake_piper_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let executable = dir.join("piper");
+        std::fs::write(&executable, format!("#!/bin/sh\n{}", script)).unwrap();
+        std::fs::set_permissions(&executable, std::fs::Permissions::from_mode(0o755)).unwrap();
+        (executable, dir)
+    }
+
+    #[test]
+    fn extra_args_are_appended_in_order_to_the_piper_command() {
+        let mut config = test_config(PathBuf::from("piper"), env::temp_dir());
+        config.speed = 1.2;
+        config.extra_args =
+            crate::config::split_args("--noise-scale 0.5 --sentence-silence 0.3").unwrap();
+        let synthesizer = synthesizer(config);
+        let command_line = |output_args: &[&OsStr]| -> String {
+            let args: Vec<String> = synthesizer
+                .piper_command(Path::new("voice.onnx"), true, output_args)
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect();
+            args.join(" ")
+        };
+
+        assert_eq!(
+            command_line(&[OsStr::new("--output_file"), OsStr::new("out.wav")]),
+            "--model voice.onnx --length-scale 1.2 --output_file out.wav \
+             --noise-scale 0.5 --sentence-silence 0.3"
+        );
+        assert_eq!(
+            command_line(&[OsStr::new("--json-input")]),
+            "--model voice.onnx --length-scale 1.2 --json-input \
+             --noise-scale 0.5 --sentence-silence 0.3"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stalled_persistent_piper_is_restarted_and_the_text_retried() {
+        // The first server reads the request and hangs; the second one answers
+        let (piper, dir) = fake_piper(
+            "stall",
+            "marker=\"$(dirname \"$0\")/stalled_once\"\n\
+             if [ ! -e \"$marker\" ]; then touch \"$marker\"; read line; exec sleep 30; fi\n\
+             while read line; do\n\
+             out=$(printf '%s' \"$line\" | sed 's/.*\"output_file\":\"\\([^\"]*\\)\".*/\\1/')\n\
+             printf 'RIFF' > \"$out\"\n\
+             echo \"$out\"\n\
+             done\n",
+        );
+        let mut config = test_config(piper, dir.clone());
+        config.persistent = true;
+        config.stall_timeout = Some(Duration::from_millis(300));
+        let synthesizer = synthesizer(config);
+
+        let first = dir.join("first.wav");
+        synthesizer
+            .synthesize_to("Hello there.", Path::new("voice.onnx"), &first)
+            .unwrap();
+        assert!(dir.join("stalled_once").exists());
+        assert!(first.exists());
+
+        // The restarted server stays up for the next text
+        let second = dir.join("second.wav");
+        synthesizer
+            .synthesize_to("How are you?", Path::new("voice.onnx"), &second)
+            .unwrap();
+        assert!(second.exists());
+        assert!(synthesizer.piper_server.lock().unwrap().is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn persistent_piper_that_exits_is_reported_with_its_stderr() {
+        let (piper, dir) = fake_piper("exit", "echo 'voice file is corrupt' >&2\nexit 1\n");
+        let mut config = test_config(piper, dir.clone());
+        config.persistent = true;
+        let synthesizer = synthesizer(config);
+
+        let error = synthesizer
+            .synthesize_to("Hello", Path::new("voice.onnx"), &dir.join("out.wav"))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("voice file is corrupt"), "{}", error);
+        assert!(synthesizer.piper_server.lock().unwrap().is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn lookahead_limits_the_chunks_synthesized_ahead_of_playback() {
+        let (piper, dir) = fake_piper(
+            "lookahead",
+            "while [ $# -gt 0 ]; do\n\
+               if [ \"$1\" = --output_file ]; then out=\"$2\"; fi\n\
+               shift\n\
+             done\n\
+             cat > /dev/null\n\
+             printf 'RIFF' > \"$out\"\n",
+        );
+        for lookahead in 1..=3 {
+            let synthesizer = synthesizer(test_config(piper.clone(), dir.clone()));
+            let chunks: Vec<String> = (0..6).map(|i| format!("Chunk {}.", i)).collect();
+            let paths: Vec<PathBuf> = (0..6)
+                .map(|i| dir.join(format!("ahead{}_{}.wav", lookahead, i)))
+                .collect();
+            let written = {
+                let paths = paths.clone();
+                move || paths.iter().filter(|path| path.exists()).count()
+            };
+            let (ready, playing) = mpsc::sync_channel(lookahead_bound(lookahead));
+            let run = thread::spawn(move || {
+                synthesizer.run(&chunks, &paths, Path::new("voice.onnx"), ready)
+            });
+
+            // The player took the first chunk and is still playing it
+            playing.recv().unwrap();
+            let waited = Instant::now();
+            while written() < 1 + lookahead && waited.elapsed() < Duration::from_secs(5) {
+                thread::sleep(Duration::from_millis(20));
+            }
+            thread::sleep(Duration::from_millis(300));
+            assert_eq!(written(), 1 + lookahead, "lookahead {}", lookahead);
+
+            drop(playing);
+            run.join().unwrap();
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_piper_run_is_retried_into_a_fresh_file() {
+        let (piper, dir) = fake_piper(
+            "retry",
+            "marker=\"$(dirname \"$0\")/failed_once\"\n\
+             if [ ! -e \"$marker\" ]; then touch \"$marker\"; echo 'file is locked' >&2; exit 1; fi\n\
+             while [ $# -gt 0 ]; do\n\
+               if [ \"$1\" = --output_file ]; then out=\"$2\"; fi\n\
+               shift\n\
+             done\n\
+             cat > /dev/null\n\
+             printf 'RIFF' > \"$out\"\n",
+        );
+        let mut config = test_config(piper, dir.clone());
+        config.retries = 1;
+        config.retry_backoff = Duration::from_millis(10);
+        let retrying = synthesizer(config);
+
+        let path = dir.join("chunk.wav");
+        let written = retrying
+            .synthesize_retrying("Hello", Path::new("voice.onnx"), &path)
+            .unwrap();
+        assert_eq!(written, dir.join("chunk_retry1.wav"));
+        assert!(written.exists());
+        assert!(!path.exists());
+
+        // Out of retries, the last error is returned
+        let error = synthesizer(test_config(dir.join("no_piper"), dir.clone()))
+            .synthesize_retrying("Hello", Path::new("voice.onnx"), &path)
+            .unwrap_err();
+        assert!(error.downcast_ref::<Cancelled>().is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejected_length_scale_is_retried_without_it() {
+        let (piper, dir) = fake_piper(
+            "length_scale",
+            "echo run >> \"$0.runs\"\n\
+             while [ $# -gt 0 ]; do\n\
+               if [ \"$1\" = --length-scale ]; then\n\
+                 echo 'error: unrecognized arguments: --length-scale' >&2\n\
+                 exit 2\n\
+               fi\n\
+               if [ \"$1\" = --output_file ]; then out=\"$2\"; fi\n\
+               shift\n\
+             done\n\
+             cat > /dev/null\n\
+             printf 'RIFF' > \"$out\"\n",
+        );
+        let mut config = test_config(piper, dir.clone());
+        config.speed = 1.5;
+        let synthesizer = synthesizer(config);
+        let runs = || {
+            std::fs::read_to_string(dir.join("piper.runs"))
+                .unwrap()
+                .lines()
+                .count()
+        };
+
+        let first = dir.join("first.wav");
+        synthesizer
+            .synthesize_to("Hello", Path::new("voice.onnx"), &first)
+            .unwrap();
+        assert!(first.exists());
+        assert_eq!(runs(), 2);
+        assert!(!synthesizer.length_scale_supported.load(Ordering::Relaxed));
+
+        // The rejection is remembered, so the next text goes without the flag right away
+        synthesizer
+            .synthesize_to("Again", Path::new("voice.onnx"), &dir.join("second.wav"))
+            .unwrap();
+        assert_eq!(runs(), 3);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn length_scale_rejections_are_recognized() {
+        assert!(mentions_length_scale_rejection(
+            "error: unrecognized arguments: --length-scale 1.5"
+        ));
+        assert!(mentions_length_scale_rejection(
+            "Unknown option length_scale"
+        ));
+        assert!(!mentions_length_scale_rejection("Unknown voice 'xx'"));
+        assert!(!mentions_length_scale_rejection("--length-scale 1.5"));
+    }
+
+    #[test]
+    fn mono_speech_is_played_on_the_mapped_channels() {
+        use rodio::buffer::SamplesBuffer;
+        use rodio::Source;
+
+        let map = ChannelMap::parse("4", Some("2,3")).unwrap();
+        assert_eq!(map.volumes(), vec![0.0, 1.0, 1.0, 0.0]);
+
+        let mono = SamplesBuffer::new(1, 22050, vec![100i16, -200]);
+        let played = ChannelVolume::new(mono, map.volumes());
+        assert_eq!(played.channels(), 4);
+        assert_eq!(
+            played.collect::<Vec<i16>>(),
+            vec![0, 100, 100, 0, 0, -200, -200, 0]
+        );
+    }
+
+    #[test]
+    fn voice_sample_rate_is_read_from_the_model_json() {
+        let dir = env::temp_dir().join(format!("voice_rate_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let model = dir.join("en_US-lessac-medium.onnx");
+        std::fs::write(
+            dir.join("en_US-lessac-medium.onnx.json"),
+            r#"{"audio": {"sample_rate": 16000, "quality": "medium"}, "num_speakers": 1}"#,
+        )
+        .unwrap();
+        assert_eq!(voice_sample_rate(&model), Some(16000));
+        assert_eq!(voice_sample_rate(&dir.join("missing.onnx")), None);
+        assert!(parse_voice_sample_rate(r#"{"audio": {"sample_rate": 0}}"#).is_err());
+
+        // Piper's output claiming 22050 Hz is relabelled with the voice's rate
+        let wav = dir.join("out.wav");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&wav, spec).unwrap();
+        for sample in [0i16, 1000, -1000] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        assert_eq!(set_sample_rate(&wav, 16000).unwrap(), Some(22050));
+        assert_eq!(set_sample_rate(&wav, 16000).unwrap(), None);
+        let mut reader = hound::WavReader::open(&wav).unwrap();
+        assert_eq!(reader.spec().sample_rate, 16000);
+        let samples: Vec<i16> = reader.samples().map(|s| s.unwrap()).collect();
+        assert_eq!(samples, vec![0, 1000, -1000]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn word_timings_increase_in_proportion_to_word_length() {
+        let timings = word_timings("I  am talking", Duration::from_secs(2));
+        let words: Vec<&str> = timings.iter().map(|(_, word)| *word).collect();
+        assert_eq!(words, vec!["I", "am", "talking"]);
+        // Weights 2, 3 and 8 out of 13
+        assert_eq!(timings[0].0, Duration::ZERO);
+        assert_eq!(timings[1].0, Duration::from_secs(2).mul_f64(2.0 / 13.0));
+        assert_eq!(timings[2].0, Duration::from_secs(2).mul_f64(5.0 / 13.0));
+        assert!(timings.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(word_timings("   ", Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn paragraph_boundaries_get_the_configured_gap() {
+        let text = "First sentence. Second one.\n\nNext paragraph.\n  \nLast.";
+        let chunks = split_chunks(text, true);
+        let texts: Vec<&str> = chunks.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(
+            texts,
+            ["First sentence.", "Second one.", "Next paragraph.", "Last."]
+        );
+
+        let gap = Duration::from_millis(600);
+        let pauses: Vec<Duration> = chunks
+            .iter()
+            .enumerate()
+            .map(|(index, (_, paragraph_start))| pause_before(index, *paragraph_start, gap))
+            .collect();
+        assert_eq!(pauses, [Duration::ZERO, Duration::ZERO, gap, gap]);
+
+        // Without sentence splitting each paragraph is one chunk
+        let paragraphs = split_chunks(text, false);
+        assert_eq!(paragraphs.len(), 3);
+        assert!(paragraphs.iter().all(|(_, start)| *start));
+    }
+}
@@ -0,0 +1,19 @@
+//! Desktop notifications (toasts on Windows), for when the console isn't in view
+
+use tracing::warn;
+
+/// Show a desktop notification without waiting for it; failures are only logged
+pub fn show(summary: &str, body: &str) {
+    let summary = summary.to_string();
+    let body = body.to_string();
+    std::thread::spawn(move || {
+        let result = notify_rust::Notification::new()
+            .appname("Local TTS Tool")
+            .summary(&summary)
+            .body(&body)
+            .show();
+        if let Err(e) = result {
+            warn!("Failed to show a notification: {}", e);
+        }
+    });
+}
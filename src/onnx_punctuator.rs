@@ -0,0 +1,187 @@
+//! Punctuation restoration with a token-classification ONNX model run through `ort`
+//! Only built with the `punctuate-onnx` feature.
+
+use crate::punctuate::Punctuator;
+use anyhow::{anyhow, Result};
+use ort::session::Session;
+use ort::value::Tensor;
+use std::path::Path;
+use tokenizers::Tokenizer;
+
+/// A punctuation model that labels each token with the mark that follows it
+///
+/// The model takes `input_ids` and `attention_mask` and returns one row of logits per
+/// token; the label of a word's last token decides the mark written after the word.
+pub struct OnnxPunctuator {
+    session: Session,
+    tokenizer: Tokenizer,
+    /// Mark for each output label, None for "no punctuation"
+    marks: Vec<Option<&'static str>>,
+}
+
+impl OnnxPunctuator {
+    /// Load `model` with its HuggingFace `tokenizer` (tokenizer.json); `labels` names the
+    /// model's output classes in order, e.g. O, COMMA, PERIOD, QUESTION
+    pub fn load(model: &Path, tokenizer: &Path, labels: &[String]) -> Result<Self> {
+        let marks = labels
+            .iter()
+            .map(|label| {
+                label_mark(label).ok_or_else(|| anyhow!("Unknown punctuation label '{}'", label))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let session = Session::builder()
+            .and_then(|builder| builder.commit_from_file(model))
+            .map_err(|e| {
+                anyhow!(
+                    "Failed to load punctuation model '{}': {}",
+                    model.display(),
+                    e
+                )
+            })?;
+        let tokenizer = Tokenizer::from_file(tokenizer)
+            .map_err(|e| anyhow!("Failed to load tokenizer '{}': {}", tokenizer.display(), e))?;
+        Ok(Self {
+            session,
+            tokenizer,
+            marks,
+        })
+    }
+}
+
+impl Punctuator for OnnxPunctuator {
+    fn punctuate(&self, text: &str) -> Result<String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words.is_empty() {
+            return Ok(text.to_string());
+        }
+        let encoding = self
+            .tokenizer
+            .encode(&words[..], true)
+            .map_err(|e| anyhow!("Failed to tokenize: {}", e))?;
+        let shape = [1, encoding.len()];
+        let ids: Vec<i64> = encoding.get_ids().iter().map(|&id| id as i64).collect();
+        let mask: Vec<i64> = encoding
+            .get_attention_mask()
+            .iter()
+            .map(|&mask| mask as i64)
+            .collect();
+
+        let inputs = ort::inputs![
+            "input_ids" => Tensor::from_array((shape, ids))?,
+            "attention_mask" => Tensor::from_array((shape, mask))?,
+        ]?;
+        let outputs = self.session.run(inputs)?;
+        let (_, logits) = outputs[0].try_extract_raw_tensor::<f32>()?;
+
+        let token_labels = argmax_rows(logits, self.marks.len());
+        let labels = word_labels(encoding.get_word_ids(), &token_labels, words.len());
+        let marks: Vec<Option<&str>> = labels
+            .iter()
+            .map(|label| label.and_then(|label| self.marks.get(label).copied().flatten()))
+            .collect();
+        Ok(apply_marks(&words, &marks))
+    }
+}
+
+/// Mark written for a model label; None if the label isn't known
+fn label_mark(label: &str) -> Option<Option<&'static str>> {
+    Some(match label.trim().to_uppercase().as_str() {
+        "O" | "0" | "NONE" | "" => None,
+        "COMMA" | "," => Some(","),
+        "PERIOD" | "." => Some("."),
+        "QUESTION" | "?" => Some("?"),
+        "EXCLAMATION" | "!" => Some("!"),
+        "COLON" | ":" => Some(":"),
+        "SEMICOLON" | ";" => Some(";"),
+        _ => return None,
+    })
+}
+
+/// Index of the largest value in each row of `classes` logits
+fn argmax_rows(logits: &[f32], classes: usize) -> Vec<usize> {
+    logits
+        .chunks_exact(classes.max(1))
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .fold((0, f32::NEG_INFINITY), |best, (index, &value)| {
+                    if value > best.1 {
+                        (index, value)
+                    } else {
+                        best
+                    }
+                })
+                .0
+        })
+        .collect()
+}
+
+/// Label of each of `words` words: that of its last token
+/// Special tokens (no word id) are skipped; words without tokens get None.
+fn word_labels(
+    word_ids: &[Option<u32>],
+    token_labels: &[usize],
+    words: usize,
+) -> Vec<Option<usize>> {
+    let mut labels = vec![None; words];
+    for (word_id, &label) in word_ids.iter().zip(token_labels) {
+        if let Some(label_slot) = word_id.and_then(|id| labels.get_mut(id as usize)) {
+            *label_slot = Some(label);
+        }
+    }
+    labels
+}
+
+/// Join `words` with single spaces, writing each word's mark after it unless the word
+/// already ends in punctuation
+fn apply_marks(words: &[&str], marks: &[Option<&str>]) -> String {
+    let mut result = String::new();
+    for (index, word) in words.iter().enumerate() {
+        if index > 0 {
+            result.push(' ');
+        }
+        result.push_str(word);
+        let punctuated = word.ends_with(|c: char| c.is_ascii_punctuation());
+        if let (Some(Some(mark)), false) = (marks.get(index), punctuated) {
+            result.push_str(mark);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_map_to_marks() {
+        assert_eq!(label_mark("O"), Some(None));
+        assert_eq!(label_mark("comma"), Some(Some(",")));
+        assert_eq!(label_mark("QUESTION"), Some(Some("?")));
+        assert_eq!(label_mark("ELLIPSIS"), None);
+    }
+
+    #[test]
+    fn each_word_takes_the_label_of_its_last_token() {
+        // [CLS] hel lo world [SEP]
+        let word_ids = [None, Some(0), Some(0), Some(1), None];
+        let token_labels = [2, 0, 1, 2, 0];
+        assert_eq!(
+            word_labels(&word_ids, &token_labels, 2),
+            vec![Some(1), Some(2)]
+        );
+    }
+
+    #[test]
+    fn argmax_picks_the_largest_logit_per_token() {
+        let logits = [0.1, 2.0, -1.0, 3.0, 0.0, 0.5];
+        assert_eq!(argmax_rows(&logits, 3), vec![1, 0]);
+    }
+
+    #[test]
+    fn marks_follow_their_words() {
+        let words = ["hello", "world", "how", "are", "you", "ok."];
+        let marks = [None, Some("."), None, None, Some("?"), Some(".")];
+        assert_eq!(apply_marks(&words, &marks), "hello world. how are you? ok.");
+    }
+}
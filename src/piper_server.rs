@@ -0,0 +1,178 @@
+//! A Piper process kept running between utterances (PIPER_PERSISTENT)
+
+use crate::process::Cancelled;
+use anyhow::{anyhow, Result};
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often a waiting `synthesize` checks whether to give up
+const POLL: Duration = Duration::from_millis(20);
+
+/// Most of Piper's stderr kept for error messages
+const STDERR_TAIL: usize = 2000;
+
+/// How long a Piper that closed its output gets to finish exiting
+const EXIT_WAIT: Duration = Duration::from_secs(1);
+
+/// One `piper --json-input` process, loaded once and fed an utterance at a time
+///
+/// Each utterance is a JSON line on stdin naming the file to write; Piper prints the
+/// path of every file it finished on stdout. Dropping the server kills the process.
+pub struct PiperServer {
+    /// Program and arguments it was started with; another voice or speed needs a new one
+    command_line: Vec<OsString>,
+    child: Child,
+    stdin: ChildStdin,
+    finished: Receiver<String>,
+    stderr: Arc<Mutex<String>>,
+    stderr_reader: Option<JoinHandle<()>>,
+}
+
+impl PiperServer {
+    /// Start `command`, which must include `--json-input`
+    pub fn spawn(mut command: Command) -> Result<Self> {
+        let command_line = command_line(&command);
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start Piper: {}", e))?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("Piper has no stdin"))?;
+
+        let (sender, finished) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            thread::spawn(move || {
+                for line in BufReader::new(stdout).lines().map_while(|line| line.ok()) {
+                    if !line.trim().is_empty() && sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        let stderr = Arc::new(Mutex::new(String::new()));
+        let stderr_reader = child.stderr.take().map(|mut pipe| {
+            let stderr = stderr.clone();
+            thread::spawn(move || {
+                let mut buffer = [0u8; 1024];
+                while let Ok(read) = pipe.read(&mut buffer) {
+                    if read == 0 {
+                        break;
+                    }
+                    let mut tail = stderr.lock().unwrap_or_else(|e| e.into_inner());
+                    tail.push_str(&String::from_utf8_lossy(&buffer[..read]));
+                    if tail.len() > STDERR_TAIL {
+                        let cut = tail.len() - STDERR_TAIL;
+                        let cut = (cut..tail.len())
+                            .find(|&i| tail.is_char_boundary(i))
+                            .unwrap_or(tail.len());
+                        tail.drain(..cut);
+                    }
+                }
+            })
+        });
+
+        Ok(Self {
+            command_line,
+            child,
+            stdin,
+            finished,
+            stderr,
+            stderr_reader,
+        })
+    }
+
+    /// Whether this server was started with exactly `command`'s program and arguments
+    pub fn runs(&self, command: &Command) -> bool {
+        self.command_line == command_line(command)
+    }
+
+    /// What Piper has written to stderr recently
+    fn stderr(&self) -> String {
+        self.stderr
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .trim()
+            .to_string()
+    }
+
+    /// Have Piper write `text` to `output_path` and wait until it reports the file done
+    /// Gives up with `Cancelled` once `should_stop` returns true; the server is then in an
+    /// unknown state and must be dropped, as after any other error.
+    pub fn synthesize(
+        &mut self,
+        text: &str,
+        output_path: &Path,
+        should_stop: &dyn Fn() -> bool,
+    ) -> Result<()> {
+        // Drop anything a previous, abandoned utterance printed
+        while self.finished.try_recv().is_ok() {}
+
+        let request = serde_json::json!({
+            "text": text,
+            "output_file": output_path,
+        });
+        writeln!(self.stdin, "{}", request)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| self.exited(&format!("could not be sent the text ({})", e)))?;
+
+        loop {
+            match self.finished.recv_timeout(POLL) {
+                Ok(_) if output_path.exists() => return Ok(()),
+                Ok(line) => {
+                    return Err(anyhow!(
+                        "Piper reported '{}' but '{}' wasn't written",
+                        line.trim(),
+                        output_path.display()
+                    ))
+                }
+                Err(RecvTimeoutError::Timeout) if should_stop() => return Err(Cancelled.into()),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Err(self.exited("stopped")),
+            }
+        }
+    }
+
+    /// Error for a Piper that went away, with its exit status and stderr
+    fn exited(&mut self, what: &str) -> anyhow::Error {
+        let waited = Instant::now();
+        let status = loop {
+            match self.child.try_wait() {
+                Ok(None) if waited.elapsed() < EXIT_WAIT => thread::sleep(POLL),
+                result => break result.ok().flatten(),
+            }
+        };
+        let Some(status) = status else {
+            return anyhow!("Piper {}: stderr='{}'", what, self.stderr());
+        };
+        // Piper has exited and closed stderr, so the reader finishes with all of it
+        if let Some(reader) = self.stderr_reader.take() {
+            let _ = reader.join();
+        }
+        anyhow!("Piper {} ({}): stderr='{}'", what, status, self.stderr())
+    }
+}
+
+impl Drop for PiperServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Program and arguments of `command`
+fn command_line(command: &Command) -> Vec<OsString> {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_os_string())
+        .collect()
+}
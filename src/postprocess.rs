@@ -0,0 +1,166 @@
+use crate::code_mode;
+use crate::config::Profile;
+use crate::punctuate::Punctuator;
+use crate::voice_commands;
+use tracing::warn;
+
+/// Apply the active profile's text clean-ups to a transcription
+/// `punctuator` runs after spoken commands, before sentence capitalization.
+/// Code-mode profiles only get the code transforms.
+pub fn apply(text: &str, profile: &Profile, punctuator: &dyn Punctuator) -> String {
+    if profile.code_mode {
+        return code_mode::apply(text, &profile.code_symbols);
+    }
+    let mut text = text.to_string();
+    if profile.spoken_commands {
+        text = voice_commands::apply(
+            &text,
+            profile.language.as_deref(),
+            profile.command_prefix.as_deref(),
+        );
+    }
+    match punctuator.punctuate(&text) {
+        Ok(punctuated) => text = punctuated,
+        Err(e) => warn!("Punctuation restore failed, keeping the text as-is: {}", e),
+    }
+    if profile.auto_punctuate {
+        text = capitalize_sentences(&text);
+        // A dictation that was only commands (e.g. "new line") shouldn't grow a period
+        if text.chars().any(char::is_alphanumeric) {
+            text = add_final_period(&text);
+        }
+    }
+    text
+}
+
+/// Uppercase the first letter of the text and of every sentence after . ! ? or a line break
+pub fn capitalize_sentences(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut at_sentence_start = true;
+
+    for c in text.chars() {
+        if at_sentence_start && c.is_alphabetic() {
+            result.extend(c.to_uppercase());
+            at_sentence_start = false;
+        } else {
+            result.push(c);
+            if matches!(c, '.' | '!' | '?' | '\n') {
+                at_sentence_start = true;
+            } else if !c.is_whitespace() && !is_opening_mark(c) {
+                // Something like a digit started the sentence; leave the rest alone
+                at_sentence_start = false;
+            }
+        }
+    }
+    result
+}
+
+/// Append a period unless the text is empty, already ends in punctuation,
+/// or ends with a line break (e.g. from a spoken "new line")
+pub fn add_final_period(text: &str) -> String {
+    if text.ends_with('\n') {
+        return text.to_string();
+    }
+    let trimmed = text.trim_end();
+    match trimmed.chars().last() {
+        None => String::new(),
+        Some(c) if c.is_ascii_punctuation() || matches!(c, '…' | '»' | '”' | '’') => {
+            trimmed.to_string()
+        }
+        Some(_) => format!("{}.", trimmed),
+    }
+}
+
+/// Quotes and brackets that may precede the first word of a sentence
+fn is_opening_mark(c: char) -> bool {
+    matches!(c, '"' | '\'' | '(' | '[' | '«' | '“' | '‘' | '¿' | '¡')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Result};
+    use std::cell::RefCell;
+
+    /// Records what it was given and answers with a fixed text
+    struct MockPunctuator {
+        seen: RefCell<Vec<String>>,
+        answer: Result<String, String>,
+    }
+
+    impl MockPunctuator {
+        fn new(answer: Result<&str, &str>) -> Self {
+            Self {
+                seen: RefCell::new(Vec::new()),
+                answer: answer.map(str::to_string).map_err(str::to_string),
+            }
+        }
+    }
+
+    impl Punctuator for MockPunctuator {
+        fn punctuate(&self, text: &str) -> Result<String> {
+            self.seen.borrow_mut().push(text.to_string());
+            self.answer.clone().map_err(|e| anyhow!(e))
+        }
+    }
+
+    fn profile() -> Profile {
+        Profile {
+            name: "default".to_string(),
+            auto_punctuate: true,
+            spoken_commands: true,
+            command_prefix: None,
+            language: Some("en".to_string()),
+            code_mode: false,
+            code_symbols: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn punctuator_output_is_used_after_spoken_commands() {
+        let punctuator = MockPunctuator::new(Ok("hello, how are you?\n"));
+        let text = apply("hello how are you new line", &profile(), &punctuator);
+        assert_eq!(*punctuator.seen.borrow(), vec!["hello how are you\n"]);
+        assert_eq!(text, "Hello, how are you?\n");
+    }
+
+    #[test]
+    fn failing_punctuator_keeps_the_text() {
+        let punctuator = MockPunctuator::new(Err("model crashed"));
+        let text = apply("hello there", &profile(), &punctuator);
+        assert_eq!(punctuator.seen.borrow().len(), 1);
+        assert_eq!(text, "Hello there.");
+    }
+
+    #[test]
+    fn final_period_is_added_only_when_missing() {
+        assert_eq!(add_final_period("See you soon"), "See you soon.");
+        assert_eq!(add_final_period("See you soon  "), "See you soon.");
+        assert_eq!(add_final_period("See you soon."), "See you soon.");
+        assert_eq!(add_final_period("Really?"), "Really?");
+        assert_eq!(add_final_period("Wait for it…"), "Wait for it…");
+        assert_eq!(add_final_period("He said “go”"), "He said “go”");
+        assert_eq!(add_final_period("First line\n"), "First line\n");
+        assert_eq!(add_final_period("   "), "");
+    }
+
+    #[test]
+    fn sentences_are_capitalized_after_end_marks() {
+        assert_eq!(
+            capitalize_sentences("hi. how are you? \"fine\"!\nbye"),
+            "Hi. How are you? \"Fine\"!\nBye"
+        );
+        assert_eq!(capitalize_sentences("3 apples. ok"), "3 apples. Ok");
+    }
+
+    #[test]
+    fn code_mode_skips_the_punctuator() {
+        let punctuator = MockPunctuator::new(Ok("unused"));
+        let profile = Profile {
+            code_mode: true,
+            ..profile()
+        };
+        apply("let x equals one", &profile, &punctuator);
+        assert!(punctuator.seen.borrow().is_empty());
+    }
+}
@@ -0,0 +1,107 @@
+//! Power source detection, used to pick a lighter whisper model on battery
+
+use std::path::Path;
+
+/// Where the machine currently draws power from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    /// No battery, or the OS didn't say
+    Unknown,
+}
+
+/// Model to use for `source`: `battery_model` on battery (if set), `ac_model` otherwise
+pub fn model_for<'a>(
+    source: PowerSource,
+    ac_model: &'a Path,
+    battery_model: Option<&'a Path>,
+) -> &'a Path {
+    match (source, battery_model) {
+        (PowerSource::Battery, Some(battery_model)) => battery_model,
+        _ => ac_model,
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn power_source() -> PowerSource {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return PowerSource::Unknown;
+    }
+    match status.ACLineStatus {
+        0 => PowerSource::Battery,
+        1 => PowerSource::Ac,
+        _ => PowerSource::Unknown,
+    }
+}
+
+/// Read /sys/class/power_supply: on AC if any mains supply is online
+#[cfg(target_os = "linux")]
+pub fn power_source() -> PowerSource {
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else {
+        return PowerSource::Unknown;
+    };
+    let mut has_battery = false;
+    let mut has_mains = false;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let read = |name: &str| std::fs::read_to_string(path.join(name)).unwrap_or_default();
+        match read("type").trim() {
+            "Mains" | "USB" => {
+                has_mains = true;
+                if read("online").trim() == "1" {
+                    return PowerSource::Ac;
+                }
+            }
+            "Battery" => has_battery = true,
+            _ => {}
+        }
+    }
+    if has_battery && has_mains {
+        PowerSource::Battery
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+/// Ask `pmset` which source is in use
+#[cfg(target_os = "macos")]
+pub fn power_source() -> PowerSource {
+    let Ok(output) = std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+    else {
+        return PowerSource::Unknown;
+    };
+    let text = String::from_utf8_lossy(&output.stdout);
+    if text.contains("'Battery Power'") {
+        PowerSource::Battery
+    } else if text.contains("'AC Power'") {
+        PowerSource::Ac
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+pub fn power_source() -> PowerSource {
+    PowerSource::Unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_model_is_used_only_on_battery() {
+        let large = Path::new("models/ggml-large-v3.bin");
+        let base = Path::new("models/ggml-base.bin");
+        assert_eq!(model_for(PowerSource::Battery, large, Some(base)), base);
+        assert_eq!(model_for(PowerSource::Ac, large, Some(base)), large);
+        assert_eq!(model_for(PowerSource::Unknown, large, Some(base)), large);
+        assert_eq!(model_for(PowerSource::Battery, large, None), large);
+    }
+}
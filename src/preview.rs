@@ -0,0 +1,101 @@
+/// What the user chose to do with a previewed transcription
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewAction {
+    /// Paste the text as-is
+    Accept,
+    /// Throw the text away
+    Skip,
+    /// Throw the text away and start a new recording
+    ReRecord,
+}
+
+/// A transcription held back from pasting until the user decides what to do with it
+#[derive(Default)]
+pub struct Preview {
+    pending: Option<String>,
+}
+
+impl Preview {
+    /// Hold `text` and print it prominently, followed by `choices` (the keys to press)
+    /// A previous pending text, if any, is replaced
+    pub fn show(&mut self, text: String, choices: &str) {
+        println!("\n==================== PREVIEW ====================");
+        println!("{}", text);
+        println!("=================================================");
+        println!("{}", choices);
+        self.pending = Some(text);
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Text waiting for a decision, if any
+    pub fn pending_text(&self) -> Option<&str> {
+        self.pending.as_deref()
+    }
+
+    /// Apply the user's choice, returning the text to paste (only for `Accept`)
+    /// Does nothing and returns None when no preview is pending
+    pub fn resolve(&mut self, action: PreviewAction) -> Option<String> {
+        let text = self.pending.take()?;
+        match action {
+            PreviewAction::Accept => Some(text),
+            PreviewAction::Skip => {
+                println!("Preview skipped.");
+                None
+            }
+            PreviewAction::ReRecord => {
+                println!("Preview discarded, re-recording...");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending(text: &str) -> Preview {
+        let mut preview = Preview::default();
+        preview.show(text.to_string(), "[F9] paste   [Esc] skip   [F8] re-record");
+        preview
+    }
+
+    #[test]
+    fn accept_pastes_the_pending_text() {
+        let mut preview = pending("Hello there.");
+        assert!(preview.is_pending());
+        assert_eq!(preview.pending_text(), Some("Hello there."));
+        assert_eq!(
+            preview.resolve(PreviewAction::Accept),
+            Some("Hello there.".to_string())
+        );
+        assert!(!preview.is_pending());
+    }
+
+    #[test]
+    fn skip_and_re_record_drop_the_text() {
+        for action in [PreviewAction::Skip, PreviewAction::ReRecord] {
+            let mut preview = pending("Hello there.");
+            assert_eq!(preview.resolve(action), None);
+            assert!(!preview.is_pending());
+        }
+    }
+
+    #[test]
+    fn nothing_is_resolved_without_a_preview() {
+        let mut preview = Preview::default();
+        assert_eq!(preview.resolve(PreviewAction::Accept), None);
+
+        // A newer transcription replaces the one still waiting
+        let mut preview = pending("First.");
+        preview.show("Second.".to_string(), "");
+        assert_eq!(
+            preview.resolve(PreviewAction::Accept),
+            Some("Second.".to_string())
+        );
+        assert_eq!(preview.resolve(PreviewAction::Accept), None);
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::Result;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How often a running child is checked for exit or cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// A child process was killed because the caller asked to cancel it
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Run `command` to completion like `Command::output`, polling `should_cancel` while it runs
+/// If it returns true the child is killed and reaped, and a `Cancelled` error is returned.
+pub fn run_cancellable(command: &mut Command, should_cancel: &dyn Fn() -> bool) -> Result<Output> {
+    run_with_input(command, None, should_cancel)
+}
+
+/// Like `run_cancellable`, but feeds `input` (if any) to the child's stdin and then closes it
+pub fn run_with_input(
+    command: &mut Command,
+    input: Option<&[u8]>,
+    should_cancel: &dyn Fn() -> bool,
+) -> Result<Output> {
+    let stdin = if input.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    };
+    let mut child = command
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Write on a helper thread so a child that doesn't read its input can still be cancelled
+    if let (Some(input), Some(mut pipe)) = (input, child.stdin.take()) {
+        let input = input.to_vec();
+        std::thread::spawn(move || {
+            let _ = pipe.write_all(&input);
+        });
+    }
+
+    // Drain the pipes on helper threads so a chatty child can't stall on a full pipe
+    let stdout_reader = spawn_reader(child.stdout.take());
+    let stderr_reader = spawn_reader(child.stderr.take());
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if should_cancel() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Cancelled.into());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout_reader.join().unwrap_or_default(),
+        stderr: stderr_reader.join().unwrap_or_default(),
+    })
+}
+
+fn spawn_reader<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+/// Flags whose value may hold user content, hidden in logged commands unless asked for
+const SENSITIVE_FLAGS: &[&str] = &["--prompt"];
+
+/// Format `command` the way it could be typed into a shell, for debug logs
+/// Relative paths that exist are shown as absolute paths. The values of flags such as
+/// `--prompt` are replaced with `<redacted>` unless `show_sensitive` is set.
+pub fn format_command(command: &Command, show_sensitive: bool) -> String {
+    let mut parts = vec![quote_arg(&absolute_if_exists(command.get_program()))];
+    let mut redact_next = false;
+    for arg in command.get_args() {
+        if std::mem::take(&mut redact_next) {
+            parts.push("<redacted>".to_string());
+            continue;
+        }
+        let text = absolute_if_exists(arg);
+        redact_next = !show_sensitive && SENSITIVE_FLAGS.contains(&text.as_str());
+        parts.push(quote_arg(&text));
+    }
+    parts.join(" ")
+}
+
+/// `arg` as text, made absolute if it names an existing relative path
+fn absolute_if_exists(arg: &OsStr) -> String {
+    let path = Path::new(arg);
+    if path.is_relative() && path.exists() {
+        if let Ok(current_dir) = std::env::current_dir() {
+            return current_dir.join(path).display().to_string();
+        }
+    }
+    arg.to_string_lossy().into_owned()
+}
+
+/// Wrap an argument in double quotes if a shell would otherwise split or misread it
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.chars().any(|c| c.is_whitespace() || c == '"' || c == '\'') {
+        return arg.to_string();
+    }
+    format!("\"{}\"", arg.replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[cfg(unix)]
+    #[test]
+    fn cancelled_child_is_killed_and_its_output_dropped() {
+        let started = Instant::now();
+        let mut command = Command::new("sh");
+        command.args(["-c", "sleep 10; echo too late"]);
+        let result = run_cancellable(&mut command, &|| {
+            started.elapsed() > Duration::from_millis(100)
+        });
+        assert!(result.unwrap_err().is::<Cancelled>());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finished_child_delivers_its_output_and_input() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "cat; echo done >&2"]);
+        let output = run_with_input(&mut command, Some(b"hello"), &|| false).unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hello");
+        assert_eq!(output.stderr, b"done\n");
+    }
+
+    #[test]
+    fn commands_are_logged_as_shell_lines() {
+        let mut command = Command::new("whisper-cli");
+        command.args(["-m", "models/missing.bin", "--prompt", "Dear Ann,"]);
+        command.args(["-f", "a b.wav"]);
+        assert_eq!(
+            format_command(&command, false),
+            "whisper-cli -m models/missing.bin --prompt <redacted> -f \"a b.wav\""
+        );
+        assert_eq!(
+            format_command(&command, true),
+            "whisper-cli -m models/missing.bin --prompt \"Dear Ann,\" -f \"a b.wav\""
+        );
+
+        // Existing relative paths are shown absolute
+        let mut command = Command::new("piper");
+        command.arg("src");
+        let src = std::env::current_dir().unwrap().join("src");
+        assert_eq!(
+            format_command(&command, false),
+            format!("piper {}", quote_arg(&src.display().to_string()))
+        );
+    }
+
+    #[test]
+    fn only_args_a_shell_would_split_are_quoted() {
+        assert_eq!(quote_arg("--threads"), "--threads");
+        assert_eq!(quote_arg(""), "\"\"");
+        assert_eq!(quote_arg("it's"), "\"it's\"");
+        assert_eq!(quote_arg("say \"hi\""), "\"say \\\"hi\\\"\"");
+    }
+}
@@ -0,0 +1,161 @@
+use crate::config::AppConfig;
+#[cfg(feature = "punctuate-onnx")]
+use crate::onnx_punctuator::OnnxPunctuator;
+use crate::process::{run_with_input, Cancelled};
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Kill a punctuation command that hasn't answered after this long
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Restores punctuation in a transcription that came back without any
+pub trait Punctuator {
+    fn punctuate(&self, text: &str) -> Result<String>;
+}
+
+/// Leaves the text unchanged (the default)
+pub struct NoPunctuation;
+
+impl Punctuator for NoPunctuation {
+    fn punctuate(&self, text: &str) -> Result<String> {
+        Ok(text.to_string())
+    }
+}
+
+/// Runs an external punctuation model (e.g. a small ONNX model behind a script)
+/// The text goes in on stdin and the punctuated text is read back from stdout.
+pub struct CommandPunctuator {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandPunctuator {
+    pub fn new(program: String, args: Vec<String>) -> Self {
+        Self { program, args }
+    }
+}
+
+impl Punctuator for CommandPunctuator {
+    fn punctuate(&self, text: &str) -> Result<String> {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+
+        #[cfg(target_os = "windows")]
+        {
+            use std::os::windows::process::CommandExt;
+            command.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        }
+
+        let started = Instant::now();
+        let output = run_with_input(&mut command, Some(text.as_bytes()), &|| {
+            started.elapsed() > COMMAND_TIMEOUT
+        })
+        .map_err(|e| match e.downcast_ref::<Cancelled>() {
+            Some(_) => anyhow!(
+                "'{}' gave no answer within {}s",
+                self.program,
+                COMMAND_TIMEOUT.as_secs()
+            ),
+            None => anyhow!("Failed to run '{}': {}", self.program, e),
+        })?;
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "'{}' failed (exit code {:?}): {}",
+                self.program,
+                output.status.code(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let punctuated = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if punctuated.is_empty() && !text.trim().is_empty() {
+            return Err(anyhow!("'{}' returned no text", self.program));
+        }
+        Ok(punctuated)
+    }
+}
+
+/// Build the punctuator selected by PUNCTUATOR_MODEL, or else PUNCTUATOR_COMMAND
+/// (program followed by its arguments); a model that can't be loaded is reported
+/// and the command is used instead.
+pub fn from_config(config: &AppConfig) -> Box<dyn Punctuator> {
+    if let Some(model) = &config.punctuator_model {
+        match onnx_punctuator(config, model) {
+            Ok(punctuator) => return punctuator,
+            Err(e) => warn!("Not using PUNCTUATOR_MODEL: {}", e),
+        }
+    }
+    from_command(&config.punctuator_command)
+}
+
+fn from_command(command: &[String]) -> Box<dyn Punctuator> {
+    match command.split_first() {
+        Some((program, args)) => Box::new(CommandPunctuator::new(program.clone(), args.to_vec())),
+        None => Box::new(NoPunctuation),
+    }
+}
+
+#[cfg(feature = "punctuate-onnx")]
+fn onnx_punctuator(config: &AppConfig, model: &Path) -> Result<Box<dyn Punctuator>> {
+    let tokenizer = match &config.punctuator_tokenizer {
+        Some(tokenizer) => tokenizer.clone(),
+        None => model.with_file_name("tokenizer.json"),
+    };
+    let punctuator = OnnxPunctuator::load(model, &tokenizer, &config.punctuator_labels)?;
+    Ok(Box::new(punctuator))
+}
+
+#[cfg(not(feature = "punctuate-onnx"))]
+fn onnx_punctuator(_config: &AppConfig, _model: &Path) -> Result<Box<dyn Punctuator>> {
+    Err(anyhow!("this build lacks the 'punctuate-onnx' feature"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_punctuation_leaves_the_text() {
+        assert_eq!(
+            NoPunctuation.punctuate("hello there").unwrap(),
+            "hello there"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_output_replaces_the_text() {
+        let punctuator = CommandPunctuator::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "sed 's/ there/, there./'".to_string()],
+        );
+        assert_eq!(
+            punctuator.punctuate("hello there").unwrap(),
+            "hello, there."
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_or_silent_command_is_an_error() {
+        let failing = CommandPunctuator::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "exit 2".to_string()],
+        );
+        assert!(failing.punctuate("hello").is_err());
+        let silent = CommandPunctuator::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "cat >/dev/null".to_string()],
+        );
+        assert!(silent.punctuate("hello").is_err());
+    }
+
+    #[test]
+    fn missing_program_is_an_error() {
+        let punctuator = CommandPunctuator::new("no_such_punctuator".to_string(), Vec::new());
+        assert!(punctuator.punctuate("hello").is_err());
+    }
+}
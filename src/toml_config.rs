@@ -0,0 +1,338 @@
+use crate::config;
+use anyhow::{anyhow, Context, Result};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Name of the TOML configuration file, used instead of `tts_config.txt` when present
+pub const TOML_CONFIG_FILE: &str = "config.toml";
+
+/// Typed layout of `config.toml`
+///
+/// The common settings have their own sections; anything else can be given under
+/// `[settings]` with its `tts_config.txt` key name. Every value is checked when the file
+/// is read, and unknown fields or keys are rejected, so a typo or a bad value names the
+/// field instead of being ignored. Tables keep the order they were written in.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub piper: PiperConfig,
+    pub whisper: WhisperConfig,
+    pub audio: AudioConfig,
+    /// Action name -> key, as in `HOTKEY.<action>`
+    pub hotkeys: IndexMap<String, String>,
+    /// Profile name -> options, as in `PROFILE.<name>.<OPTION>`
+    pub profiles: IndexMap<String, IndexMap<String, toml::Value>>,
+    /// Any other `tts_config.txt` key
+    pub settings: IndexMap<String, toml::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PiperConfig {
+    pub path: Option<PathBuf>,
+    pub model: Option<PathBuf>,
+    /// Language code -> model, as in `PIPER_MODEL_<LANG>`
+    pub models: IndexMap<String, PathBuf>,
+    /// Voice name -> model, as in `PIPER_VOICE.<name>`
+    pub voices: IndexMap<String, PathBuf>,
+    pub speed: Option<f32>,
+    pub extra_args: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WhisperConfig {
+    pub model: Option<PathBuf>,
+    /// Model name -> file, as in `WHISPER_MODEL.<name>`
+    pub models: IndexMap<String, PathBuf>,
+    pub language: Option<String>,
+    pub backend: Option<String>,
+    pub gpu: Option<String>,
+    pub prompt: Option<String>,
+    pub extra_args: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioConfig {
+    pub device: Option<String>,
+    pub silence_threshold: Option<f32>,
+    pub start_delay_ms: Option<u64>,
+    pub start_retries: Option<u32>,
+    pub normalize: Option<String>,
+    pub trim_silence: Option<bool>,
+}
+
+impl Config {
+    /// Read and validate the TOML file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config: Config =
+            toml::from_str(&content).with_context(|| format!("Invalid {}", TOML_CONFIG_FILE))?;
+        config
+            .validate()
+            .with_context(|| format!("Invalid {}", TOML_CONFIG_FILE))?;
+        Ok(config)
+    }
+
+    /// Range checks serde can't express, naming the offending field
+    fn validate(&self) -> Result<()> {
+        if let Some(speed) = self.piper.speed {
+            if !speed.is_finite() || speed <= 0.0 {
+                return Err(anyhow!("piper.speed must be greater than 0, got {}", speed));
+            }
+        }
+        if let Some(threshold) = self.audio.silence_threshold {
+            if !(0.0..=1.0).contains(&threshold) {
+                return Err(anyhow!(
+                    "audio.silence_threshold must be between 0 and 1, got {}",
+                    threshold
+                ));
+            }
+        }
+        if self.whisper.language.as_deref() == Some("") {
+            return Err(anyhow!("whisper.language must not be empty (use \"auto\")"));
+        }
+        for (action, key) in &self.hotkeys {
+            if action.is_empty() {
+                return Err(anyhow!(
+                    "hotkeys has an empty action name (bound to '{}')",
+                    key
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The settings as `KEY=VALUE` pairs, as `tts_config.txt` would give them
+    /// Each one is checked as loading would apply it, naming the field if it is rejected.
+    pub fn into_pairs(self) -> Result<Vec<(String, String)>> {
+        let mut pairs = Vec::new();
+        let mut push = |field: &str, key: String, value: String| -> Result<()> {
+            match config::check_setting(&key, &value) {
+                Ok(true) => {
+                    pairs.push((key, value));
+                    Ok(())
+                }
+                Ok(false) => Err(anyhow!("{} is not a known setting", field)),
+                Err(e) => Err(anyhow!("{}: {}", field, e)),
+            }
+        };
+
+        let piper = self.piper;
+        if let Some(path) = piper.path {
+            push("piper.path", "PIPER_PATH".into(), path_value(&path))?;
+        }
+        if let Some(model) = piper.model {
+            push("piper.model", "PIPER_MODEL".into(), path_value(&model))?;
+        }
+        for (lang, model) in piper.models {
+            push(
+                &format!("piper.models.{}", lang),
+                format!("PIPER_MODEL_{}", lang.to_uppercase()),
+                path_value(&model),
+            )?;
+        }
+        for (name, model) in piper.voices {
+            push(
+                &format!("piper.voices.{}", name),
+                format!("PIPER_VOICE.{}", name),
+                path_value(&model),
+            )?;
+        }
+        if let Some(speed) = piper.speed {
+            push("piper.speed", "SPEED".into(), speed.to_string())?;
+        }
+        if let Some(args) = piper.extra_args {
+            push("piper.extra_args", "PIPER_EXTRA_ARGS".into(), args)?;
+        }
+
+        let whisper = self.whisper;
+        if let Some(model) = whisper.model {
+            push("whisper.model", "WHISPER_MODEL".into(), path_value(&model))?;
+        }
+        for (name, model) in whisper.models {
+            push(
+                &format!("whisper.models.{}", name),
+                format!("WHISPER_MODEL.{}", name),
+                path_value(&model),
+            )?;
+        }
+        if let Some(language) = whisper.language {
+            push("whisper.language", "LANGUAGE".into(), language)?;
+        }
+        if let Some(backend) = whisper.backend {
+            push("whisper.backend", "WHISPER_BACKEND".into(), backend)?;
+        }
+        if let Some(gpu) = whisper.gpu {
+            push("whisper.gpu", "WHISPER_GPU".into(), gpu)?;
+        }
+        if let Some(prompt) = whisper.prompt {
+            push("whisper.prompt", "WHISPER_PROMPT".into(), prompt)?;
+        }
+        if let Some(args) = whisper.extra_args {
+            push("whisper.extra_args", "WHISPER_EXTRA_ARGS".into(), args)?;
+        }
+
+        let audio = self.audio;
+        if let Some(device) = audio.device {
+            push("audio.device", "DEVICE".into(), device)?;
+        }
+        if let Some(threshold) = audio.silence_threshold {
+            push(
+                "audio.silence_threshold",
+                "SILENCE_THRESHOLD".into(),
+                threshold.to_string(),
+            )?;
+        }
+        if let Some(delay) = audio.start_delay_ms {
+            push(
+                "audio.start_delay_ms",
+                "AUDIO_START_DELAY_MS".into(),
+                delay.to_string(),
+            )?;
+        }
+        if let Some(retries) = audio.start_retries {
+            push(
+                "audio.start_retries",
+                "AUDIO_START_RETRIES".into(),
+                retries.to_string(),
+            )?;
+        }
+        if let Some(normalize) = audio.normalize {
+            push("audio.normalize", "NORMALIZE".into(), normalize)?;
+        }
+        if let Some(trim) = audio.trim_silence {
+            push(
+                "audio.trim_silence",
+                "TRIM_SILENCE".into(),
+                trim.to_string(),
+            )?;
+        }
+
+        for (action, key) in self.hotkeys {
+            push(
+                &format!("hotkeys.{}", action),
+                format!("HOTKEY.{}", action),
+                key,
+            )?;
+        }
+        for (name, options) in self.profiles {
+            for (option, value) in options {
+                let field = format!("profiles.{}.{}", name, option);
+                let value = scalar_value(&field, value)?;
+                push(
+                    &field,
+                    format!("PROFILE.{}.{}", name, option.to_uppercase()),
+                    value,
+                )?;
+            }
+        }
+        for (key, value) in self.settings {
+            let field = format!("settings.{}", key);
+            let value = scalar_value(&field, value)?;
+            push(&field, key.to_uppercase(), value)?;
+        }
+        Ok(pairs)
+    }
+}
+
+/// Read `config.toml` into `KEY=VALUE` pairs
+pub fn read_pairs(path: &Path) -> Result<Vec<(String, String)>> {
+    Config::load(path)?
+        .into_pairs()
+        .with_context(|| format!("Invalid {}", TOML_CONFIG_FILE))
+}
+
+fn path_value(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// A string, number or boolean as its config value; lists and tables are an error
+fn scalar_value(field: &str, value: toml::Value) -> Result<String> {
+    match value {
+        toml::Value::String(s) => Ok(s),
+        toml::Value::Integer(i) => Ok(i.to_string()),
+        toml::Value::Float(f) => Ok(f.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        other => Err(anyhow!(
+            "{} must be a string, number or boolean, got {}",
+            field,
+            other.type_str()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(toml: &str) -> Result<Vec<(String, String)>> {
+        let config: Config = toml::from_str(toml)?;
+        config.validate()?;
+        config.into_pairs()
+    }
+
+    #[test]
+    fn voices_keep_the_order_they_were_written_in() {
+        let pairs =
+            pairs("[piper.voices]\nzoe = \"zoe.onnx\"\namy = \"amy.onnx\"\nmax = \"max.onnx\"\n")
+                .unwrap();
+        let keys: Vec<&str> = pairs.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(
+            keys,
+            ["PIPER_VOICE.zoe", "PIPER_VOICE.amy", "PIPER_VOICE.max"]
+        );
+    }
+
+    #[test]
+    fn settings_are_checked_by_name_and_value() {
+        let pairs = pairs(
+            "[settings]\nrecord_mode = \"toggle\"\nPIPER_TIMEOUT_MS = 5000\nverbose = true\n",
+        )
+        .unwrap();
+        assert_eq!(
+            pairs,
+            [
+                ("RECORD_MODE".to_string(), "toggle".to_string()),
+                ("PIPER_TIMEOUT_MS".to_string(), "5000".to_string()),
+                ("VERBOSE".to_string(), "true".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_setting_names_the_field() {
+        let error = pairs("[settings]\nrecrod_mode = \"toggle\"\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "settings.recrod_mode is not a known setting"
+        );
+    }
+
+    #[test]
+    fn invalid_values_name_the_field() {
+        let error = pairs("[settings]\nPIPER_RETRIES = \"twice\"\n").unwrap_err();
+        assert!(
+            error.to_string().starts_with("settings.PIPER_RETRIES:"),
+            "{}",
+            error
+        );
+        let error = pairs("[whisper]\nbackend = \"gpu\"\n").unwrap_err();
+        assert!(
+            error.to_string().starts_with("whisper.backend:"),
+            "{}",
+            error
+        );
+        let error = pairs("[profiles.mail]\nauto_punctuate = \"maybe\"\n").unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("profiles.mail.auto_punctuate:"),
+            "{}",
+            error
+        );
+    }
+}
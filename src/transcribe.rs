@@ -1,15 +1,293 @@
-use anyhow::{anyhow, Result};
+use crate::audio::{self, WHISPER_SAMPLE_RATE};
+use crate::limiter::HeavyOpLimiter;
+use crate::process::{format_command, run_cancellable, Cancelled};
+#[cfg(feature = "whisper-rs")]
+use crate::whisper_lib::ResidentModel;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
 use std::env;
-use std::path::PathBuf;
-use std::process::Command;
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tracing::{debug, error, info, warn};
+
+/// How whisper is run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriberBackend {
+    /// Spawn whisper-cli for every recording (reloads the model each time)
+    Cli,
+    /// Keep the model loaded in-process with whisper-rs (needs the `whisper-rs` feature)
+    Library,
+}
+
+/// What whisper makes of the speech
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Task {
+    /// Text in the spoken language
+    Transcribe,
+    /// English text whatever the spoken language (`--translate`)
+    Translate,
+}
+
+/// Initial prompts given to whisper, picked by the language it is told to use
+#[derive(Debug, Clone, Default)]
+pub struct Prompts {
+    /// Used when the language is auto-detected, or forced without a prompt of its own
+    pub default: Option<String>,
+    /// (language code, prompt) pairs for forced languages
+    pub by_language: Vec<(String, String)>,
+}
+
+impl Prompts {
+    /// Prompt for a run with `language` forced (None = auto-detect)
+    /// A forced language uses its own prompt when there is one, so the prompt always
+    /// matches the language whisper transcribes in without a separate detection pass.
+    pub fn for_language(&self, language: Option<&str>) -> Option<&str> {
+        language
+            .and_then(|language| {
+                self.by_language
+                    .iter()
+                    .find(|(code, _)| code.eq_ignore_ascii_case(language))
+                    .map(|(_, prompt)| prompt.as_str())
+            })
+            .or(self.default.as_deref())
+    }
+}
+
+/// Outcomes of a successful whisper run that did not yield any text
+#[derive(Debug)]
+pub enum TranscribeError {
+    /// Whisper ran fine but the recording held no speech worth transcribing
+    NoSpeech,
+    /// Whisper exited successfully but neither stdout nor the sidecar txt had output
+    MissingOutput(PathBuf),
+    /// whisper-cli ran longer than the timeout and was killed
+    TimedOut(Duration),
+}
+
+impl fmt::Display for TranscribeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscribeError::NoSpeech => write!(f, "No speech detected"),
+            TranscribeError::MissingOutput(path) => write!(
+                f,
+                "Whisper reported success but produced no output (stdout empty, '{}' missing)",
+                path.display()
+            ),
+            TranscribeError::TimedOut(timeout) => write!(
+                f,
+                "Whisper didn't finish within {}s and was stopped (see WHISPER_TIMEOUT_SECONDS)",
+                timeout.as_secs()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TranscribeError {}
+
+/// Whether a recording of `duration` should be transcribed in chunks
+/// `threshold` None means never.
+pub fn needs_chunking(duration: Duration, threshold: Option<Duration>) -> bool {
+    threshold.is_some_and(|threshold| duration > threshold)
+}
+
+/// Words per second of `text` spoken over `duration`, counting whitespace-separated words
+pub fn words_per_second(text: &str, duration: Duration) -> f32 {
+    let seconds = duration.as_secs_f32();
+    if seconds <= 0.0 {
+        return 0.0;
+    }
+    text.split_whitespace().count() as f32 / seconds
+}
+
+/// Whether `text` is too fast to have been spoken in `duration`, which points to a
+/// whisper hallucination; `max_words_per_second` None means never
+pub fn is_implausible_rate(
+    text: &str,
+    duration: Duration,
+    max_words_per_second: Option<f32>,
+) -> bool {
+    max_words_per_second.is_some_and(|max| words_per_second(text, duration) > max)
+}
+
+/// Segments `transcribe_stream` holds before whisper's output stops being read
+const STREAM_BUFFER: usize = 32;
+
+/// A stretch of a transcription with its position in the audio
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub start: Duration,
+    pub end: Duration,
+    pub text: String,
+}
+
+/// The parts of whisper-cli's `--output-json` file that are used
+#[derive(Deserialize)]
+struct JsonOutput {
+    transcription: Vec<JsonSegment>,
+}
+
+#[derive(Deserialize)]
+struct JsonSegment {
+    /// Milliseconds from the start of the audio
+    offsets: JsonOffsets,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct JsonOffsets {
+    from: u64,
+    to: u64,
+}
+
+/// Raw segments from whisper-cli's JSON output
+fn parse_segments(json: &str) -> Result<Vec<Segment>> {
+    let output: JsonOutput =
+        serde_json::from_str(json).context("Failed to parse whisper's JSON output")?;
+    Ok(output
+        .transcription
+        .into_iter()
+        .map(|segment| Segment {
+            start: Duration::from_millis(segment.offsets.from),
+            end: Duration::from_millis(segment.offsets.to),
+            text: segment.text,
+        })
+        .collect())
+}
+
+/// Segment from one line of whisper-cli's stdout, e.g. "[00:00:01.500 --> 00:00:03.000]  Hello"
+/// None for other lines and for segments that are empty once cleaned.
+fn parse_segment_line(line: &str) -> Option<Segment> {
+    let (times, text) = line.trim_start().strip_prefix('[')?.split_once(']')?;
+    let (start, end) = times.split_once("-->")?;
+    let text = clean_transcription(text);
+    if text.is_empty() {
+        return None;
+    }
+    Some(Segment {
+        start: parse_timestamp(start.trim())?,
+        end: parse_timestamp(end.trim())?,
+        text,
+    })
+}
+
+/// Parse whisper's "HH:MM:SS.mmm" timestamps
+fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let mut parts = timestamp.splitn(3, ':');
+    let hours: u64 = parts.next()?.parse().ok()?;
+    let minutes: u64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return None;
+    }
+    Some(Duration::from_secs(hours * 3600 + minutes * 60) + Duration::from_secs_f64(seconds))
+}
+
+/// Clean each segment like a plain transcription, dropping those that end up empty
+/// (e.g. `[BLANK_AUDIO]`); `TranscribeError::NoSpeech` if none are left.
+fn clean_segments(segments: Vec<Segment>) -> Result<Vec<Segment>> {
+    let segments: Vec<Segment> = segments
+        .into_iter()
+        .filter_map(|segment| {
+            let text = clean_transcription(&segment.text);
+            (!text.is_empty()).then_some(Segment { text, ..segment })
+        })
+        .collect();
+    if segments.is_empty() {
+        return Err(TranscribeError::NoSpeech.into());
+    }
+    Ok(segments)
+}
+
+/// Text of a transcription and the language whisper detected for it
+#[derive(Debug)]
+pub struct Transcription {
+    pub text: String,
+    /// Only set when whisper auto-detected the language
+    pub language: Option<String>,
+}
 
 pub struct Transcriber {
-    executable_path: PathBuf,
+    /// None when whisper-cli wasn't found and the library backend is used instead
+    executable_path: Option<PathBuf>,
     model_path: PathBuf,
+    /// Model kept loaded by the library backend
+    #[cfg(feature = "whisper-rs")]
+    resident: Option<ResidentModel>,
+    // Cleared for the session once whisper rejects --output-txt (minimal builds lack it)
+    output_txt_supported: AtomicBool,
+    extra_args: Vec<String>,
+    /// Passed as `-t`, and to the library backend
+    threads: usize,
+    /// whisper-cli is killed after running this long, None = wait forever
+    timeout: Option<Duration>,
+    prompts: Prompts,
+    verbose: bool,
+    log_sensitive: bool,
+    strip_markup: bool,
+    limiter: Arc<HeavyOpLimiter>,
+}
+
+/// Whisper's thread count when WHISPER_THREADS is unset: one per logical CPU
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism().map_or(4, |n| n.get())
+}
+
+/// Return a copy of `model_path` inside `cache_dir`, refreshing it if missing or stale
+///
+/// whisper-cli reads the whole model from disk on every run, so keeping a copy on a
+/// fast local disk (instead of a network share or synced folder) shortens each load.
+/// The copy is refreshed whenever its size differs or the source is newer.
+pub fn cached_model_path(model_path: &Path, cache_dir: &Path) -> Result<PathBuf> {
+    let file_name = model_path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid model path '{}'", model_path.display()))?;
+    let cached_path = cache_dir.join(file_name);
+
+    let source = std::fs::metadata(model_path)?;
+    let is_fresh = match std::fs::metadata(&cached_path) {
+        Ok(cached) => {
+            cached.len() == source.len()
+                && match (cached.modified(), source.modified()) {
+                    (Ok(cached_time), Ok(source_time)) => cached_time >= source_time,
+                    _ => false,
+                }
+        }
+        Err(_) => false,
+    };
+
+    if !is_fresh {
+        info!(
+            "Caching model '{}' in '{}'...",
+            model_path.display(),
+            cache_dir.display()
+        );
+        std::fs::create_dir_all(cache_dir)?;
+        // Copy under a temporary name so an interrupted copy is never mistaken for the model
+        let partial_path = cache_dir.join(format!("{}.partial", file_name.to_string_lossy()));
+        std::fs::copy(model_path, &partial_path)?;
+        std::fs::rename(&partial_path, &cached_path)?;
+    }
+
+    Ok(cached_path)
 }
 
 impl Transcriber {
-    pub fn new(model_path: &str) -> Result<Self> {
+    /// `use_gpu` and `mmap` only matter for the library backend; `use_gpu` only in builds
+    /// with a GPU feature
+    pub fn new(
+        model_path: &Path,
+        backend: TranscriberBackend,
+        use_gpu: bool,
+        mmap: bool,
+    ) -> Result<Self> {
         let current_dir = env::current_dir()?;
 
         // We look for 'whisper-cli.exe', 'whisper.exe', or 'main.exe' (deprecated)
@@ -24,67 +302,970 @@ impl Transcriber {
             }
         }
 
-        let executable_path = executable_path.ok_or_else(|| {
-            anyhow!("Whisper executable not found. Please download 'whisper-cli.exe' from whisper.cpp releases and place it in the project root.")
-        })?;
-
         let model_full_path = current_dir.join(model_path);
 
+        #[cfg(feature = "whisper-rs")]
+        let resident = match backend {
+            TranscriberBackend::Library => {
+                Some(ResidentModel::load(&model_full_path, use_gpu, mmap)?)
+            }
+            TranscriberBackend::Cli => None,
+        };
+        #[cfg(not(feature = "whisper-rs"))]
+        let _ = (use_gpu, mmap);
+        #[cfg(not(feature = "whisper-rs"))]
+        if backend == TranscriberBackend::Library {
+            warn!("WHISPER_BACKEND=library needs a build with the 'whisper-rs' feature, using whisper-cli.");
+        }
+
+        let uses_cli = backend == TranscriberBackend::Cli || cfg!(not(feature = "whisper-rs"));
+        if uses_cli && executable_path.is_none() {
+            return Err(anyhow!("Whisper executable not found. Please download 'whisper-cli.exe' from whisper.cpp releases and place it in the project root."));
+        }
+
         Ok(Self {
             executable_path,
             model_path: model_full_path,
+            #[cfg(feature = "whisper-rs")]
+            resident,
+            output_txt_supported: AtomicBool::new(true),
+            extra_args: Vec::new(),
+            threads: default_threads(),
+            timeout: None,
+            prompts: Prompts::default(),
+            verbose: false,
+            log_sensitive: false,
+            strip_markup: true,
+            limiter: Arc::new(HeavyOpLimiter::new(0)),
+        })
+    }
+
+    pub fn model_path(&self) -> &Path {
+        &self.model_path
+    }
+
+    /// The whisper-cli binary, if one was found
+    pub fn executable_path(&self) -> Option<&Path> {
+        self.executable_path.as_deref()
+    }
+
+    /// Use another model from the next transcription on
+    /// The library backend loads it right away and keeps the old one if that fails.
+    pub fn set_model(&mut self, model_path: PathBuf) {
+        #[cfg(feature = "whisper-rs")]
+        if let Some(resident) = self.resident.as_mut() {
+            match resident.reload(&model_path) {
+                Ok(model) => *resident = model,
+                Err(e) => {
+                    warn!("Keeping model '{}': {}", resident.path().display(), e);
+                    return;
+                }
+            }
+        }
+        self.model_path = model_path;
+    }
+
+    /// Arguments appended verbatim after the managed ones (WHISPER_EXTRA_ARGS)
+    pub fn set_extra_args(&mut self, args: Vec<String>) {
+        self.extra_args = args;
+    }
+
+    /// CPU threads whisper may use (WHISPER_THREADS), None = one per logical CPU
+    pub fn set_threads(&mut self, threads: Option<usize>) {
+        self.threads = threads.unwrap_or_else(default_threads);
+        #[cfg(feature = "whisper-rs")]
+        if let Some(resident) = self.resident.as_mut() {
+            resident.set_threads(self.threads);
+        }
+    }
+
+    /// Longest a whisper-cli run may take (WHISPER_TIMEOUT_SECONDS), None = no limit
+    /// The library backend can't be interrupted, so it isn't limited.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Initial prompts per language (WHISPER_PROMPT, WHISPER_PROMPT.<language>)
+    pub fn set_prompts(&mut self, prompts: Prompts) {
+        self.prompts = prompts;
+    }
+
+    /// Drop log lines, timestamps, special tokens and colour codes from whisper's stdout,
+    /// so verbose/debug flags in WHISPER_EXTRA_ARGS don't end up in the pasted text
+    pub fn set_strip_markup(&mut self, strip_markup: bool) {
+        self.strip_markup = strip_markup;
+    }
+
+    /// Log the full whisper command before each run
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    /// Share a limit on concurrent whisper/Piper runs (MAX_HEAVY_OPS)
+    pub fn set_limiter(&mut self, limiter: Arc<HeavyOpLimiter>) {
+        self.limiter = limiter;
+    }
+
+    /// Show prompt text in logged commands instead of `<redacted>`
+    pub fn set_log_sensitive(&mut self, log_sensitive: bool) {
+        self.log_sensitive = log_sensitive;
+    }
+
+    /// Transcribe a WAV file, polling `should_cancel` while whisper runs
+    /// `language` forces whisper's language (e.g. "en"); None lets it auto-detect.
+    /// `language` is always the spoken one, also when `task` translates it to English.
+    /// Cancelling kills whisper and returns a `process::Cancelled` error.
+    pub fn transcribe(
+        &self,
+        audio_path: &Path,
+        language: Option<&str>,
+        task: Task,
+        should_cancel: &dyn Fn() -> bool,
+    ) -> Result<Transcription> {
+        let current_dir = env::current_dir()?;
+        let audio_path = current_dir.join(audio_path);
+
+        #[cfg(feature = "whisper-rs")]
+        if self.resident.is_some() {
+            let samples = audio::load_samples(&audio_path)?;
+            return self.transcribe_samples(&samples, language, task, should_cancel);
+        }
+
+        let use_output_txt = self.output_txt_supported.load(Ordering::Relaxed);
+        let output_flag = use_output_txt.then_some("--output-txt");
+        let mut output =
+            self.run_whisper(&audio_path, output_flag, language, task, should_cancel)?;
+
+        if !output.status.success() && use_output_txt && rejects_output_txt(&output) {
+            warn!("This whisper build doesn't support --output-txt, reading stdout only.");
+            self.output_txt_supported.store(false, Ordering::Relaxed);
+            output = self.run_whisper(&audio_path, None, language, task, should_cancel)?;
+        }
+        check_status(&output)?;
+
+        let mut raw_output = String::from_utf8_lossy(&output.stdout).to_string();
+        if self.strip_markup {
+            raw_output = extract_transcript(&raw_output);
+        }
+
+        // --output-txt also writes '<audio>.txt' next to the input; read it as a fallback
+        let sidecar_path = sidecar_path(&audio_path, "txt");
+        let sidecar = std::fs::read_to_string(&sidecar_path).ok();
+        let _ = std::fs::remove_file(&sidecar_path);
+
+        let text = interpret_output(&raw_output, sidecar.as_deref(), &sidecar_path)?;
+        Ok(Transcription {
+            text,
+            language: detected_language(&String::from_utf8_lossy(&output.stderr)),
         })
     }
 
-    pub fn transcribe(&self, audio_filename: &str) -> Result<String> {
+    /// Transcribe a WAV file into timed segments, using whisper-cli's `--output-json`
+    /// Whisper writes the JSON next to the input ('<audio>.json'); it is read and deleted.
+    /// If it isn't there, stdout is tried in case this build prints the JSON instead.
+    pub fn transcribe_segments(
+        &self,
+        audio_path: &Path,
+        language: Option<&str>,
+        should_cancel: &dyn Fn() -> bool,
+    ) -> Result<Vec<Segment>> {
         let current_dir = env::current_dir()?;
-        let audio_path = current_dir.join(audio_filename);
+        let audio_path = current_dir.join(audio_path);
 
-        let output = Command::new(&self.executable_path)
+        #[cfg(feature = "whisper-rs")]
+        if let Some(resident) = &self.resident {
+            let samples = audio::load_samples(&audio_path)?;
+            if should_cancel() {
+                return Err(Cancelled.into());
+            }
+            let _permit = self.limiter.acquire("transcription");
+            let (segments, _) = resident.transcribe_segments(
+                &samples,
+                language,
+                self.prompts.for_language(language),
+                Task::Transcribe,
+            )?;
+            return clean_segments(segments);
+        }
+
+        let output = self.run_whisper(
+            &audio_path,
+            Some("--output-json"),
+            language,
+            Task::Transcribe,
+            should_cancel,
+        )?;
+        check_status(&output)?;
+
+        let json_path = sidecar_path(&audio_path, "json");
+        let json = match std::fs::read_to_string(&json_path) {
+            Ok(json) => {
+                let _ = std::fs::remove_file(&json_path);
+                json
+            }
+            Err(_) => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                if !stdout.trim_start().starts_with('{') {
+                    return Err(TranscribeError::MissingOutput(json_path).into());
+                }
+                stdout.into_owned()
+            }
+        };
+        clean_segments(parse_segments(&json)?)
+    }
+
+    /// Transcribe a WAV file into segments delivered as whisper prints them
+    /// whisper-cli's stdout is read line by line on its own thread, so each "[start --> end] text"
+    /// line becomes available while the rest of the file is still being processed. Dropping the
+    /// stream stops whisper. The library backend yields all its segments at the end of the run.
+    /// A failure to run whisper, or whisper failing partway, ends the stream with an error.
+    /// `transcribe_segments` is the blocking counterpart, which reads whisper's JSON output.
+    pub fn transcribe_stream(
+        &self,
+        audio_path: &Path,
+        language: Option<&str>,
+    ) -> Result<impl Stream<Item = Result<Segment>>> {
+        let current_dir = env::current_dir()?;
+        let audio_path = current_dir.join(audio_path);
+
+        #[cfg(feature = "whisper-rs")]
+        if self.resident.is_some() {
+            let segments = self.transcribe_segments(&audio_path, language, &|| false)?;
+            let (sender, receiver) = mpsc::channel(segments.len().max(1));
+            for segment in segments {
+                let _ = sender.try_send(Ok(segment));
+            }
+            return Ok(ReceiverStream::new(receiver));
+        }
+
+        let mut command =
+            self.whisper_command(&audio_path, None, true, language, Task::Transcribe)?;
+        command.stdout(Stdio::piped()).stderr(Stdio::null());
+        let limiter = Arc::clone(&self.limiter);
+        let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+        std::thread::spawn(move || {
+            let _permit = limiter.acquire("transcription");
+            let mut child = match command.spawn() {
+                Ok(child) => child,
+                Err(e) => {
+                    let _ = sender
+                        .blocking_send(Err(anyhow!("Failed to execute whisper process: {}", e)));
+                    return;
+                }
+            };
+            if let Some(stdout) = child.stdout.take() {
+                for line in BufReader::new(stdout).lines().map_while(|line| line.ok()) {
+                    let Some(segment) = parse_segment_line(&line) else {
+                        continue;
+                    };
+                    if sender.blocking_send(Ok(segment)).is_err() {
+                        // Nobody is listening anymore
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return;
+                    }
+                }
+            }
+            let failure = match child.wait() {
+                Ok(status) if !status.success() => Some(anyhow!(
+                    "Whisper failed while streaming segments ({})",
+                    status
+                )),
+                Ok(_) => None,
+                Err(e) => Some(anyhow!("Failed to wait for whisper: {}", e)),
+            };
+            if let Some(e) = failure {
+                let _ = sender.blocking_send(Err(e));
+            }
+        });
+        Ok(ReceiverStream::new(receiver))
+    }
+
+    /// Transcribe 16 kHz mono samples with the model kept loaded by the library backend
+    /// The run can't be interrupted once started, so `should_cancel` is only checked before it.
+    #[cfg(feature = "whisper-rs")]
+    pub fn transcribe_samples(
+        &self,
+        samples: &[f32],
+        language: Option<&str>,
+        task: Task,
+        should_cancel: &dyn Fn() -> bool,
+    ) -> Result<Transcription> {
+        let resident = self
+            .resident
+            .as_ref()
+            .ok_or_else(|| anyhow!("The whisper model is not loaded in-process"))?;
+        if should_cancel() {
+            return Err(Cancelled.into());
+        }
+        let (raw_output, detected) = {
+            let _permit = self.limiter.acquire("transcription");
+            resident.transcribe(samples, language, self.prompt(language, task), task)?
+        };
+
+        // Same artifact cleanup as whisper-cli's output
+        let text = clean_transcription(&raw_output);
+        if text.is_empty() {
+            return Err(TranscribeError::NoSpeech.into());
+        }
+        Ok(Transcription {
+            text,
+            language: detected,
+        })
+    }
+
+    /// Transcribe a long WAV file piece by piece and join the texts
+    /// The file is cut into chunks of at most `chunk_length` at quiet spots; chunks without
+    /// speech are left out. Once whisper detects a language, later chunks keep it.
+    pub fn transcribe_chunked(
+        &self,
+        audio_path: &Path,
+        chunk_length: Duration,
+        language: Option<&str>,
+        task: Task,
+        should_cancel: &dyn Fn() -> bool,
+    ) -> Result<Transcription> {
+        let samples = audio::load_samples(audio_path)?;
+        let bounds = audio::chunk_bounds(&samples, WHISPER_SAMPLE_RATE, chunk_length);
+        let stem = audio_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut texts = Vec::new();
+        let mut language = language.map(str::to_string);
+        let mut detected = None;
+        for (index, &(start, end)) in bounds.iter().enumerate() {
+            info!("Transcribing part {}/{}...", index + 1, bounds.len());
+            let chunk_path = audio_path.with_file_name(format!("{}_chunk{}.wav", stem, index));
+            let result = self.transcribe_chunk(
+                &samples[start..end],
+                &chunk_path,
+                language.as_deref(),
+                task,
+                should_cancel,
+            );
+            match result {
+                Ok(transcription) => {
+                    if language.is_none() {
+                        language = transcription.language.clone();
+                        detected = transcription.language;
+                    }
+                    texts.push(transcription.text);
+                }
+                Err(e) if matches!(e.downcast_ref(), Some(TranscribeError::NoSpeech)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        if texts.is_empty() {
+            return Err(TranscribeError::NoSpeech.into());
+        }
+        Ok(Transcription {
+            text: texts.join(" "),
+            language: detected,
+        })
+    }
+
+    /// Transcribe one chunk, through a temporary WAV at `chunk_path` for whisper-cli
+    fn transcribe_chunk(
+        &self,
+        samples: &[f32],
+        chunk_path: &Path,
+        language: Option<&str>,
+        task: Task,
+        should_cancel: &dyn Fn() -> bool,
+    ) -> Result<Transcription> {
+        #[cfg(feature = "whisper-rs")]
+        if self.resident.is_some() {
+            return self.transcribe_samples(samples, language, task, should_cancel);
+        }
+        audio::save_to_file(samples, WHISPER_SAMPLE_RATE, chunk_path)?;
+        let result = self.transcribe(chunk_path, language, task, should_cancel);
+        let _ = std::fs::remove_file(chunk_path);
+        result
+    }
+
+    /// Initial prompt for a run: the spoken language's when transcribing, none when
+    /// translating, since a prompt in the spoken language would pull the output back into it
+    fn prompt(&self, language: Option<&str>, task: Task) -> Option<&str> {
+        match task {
+            Task::Transcribe => self.prompts.for_language(language),
+            Task::Translate => None,
+        }
+    }
+
+    /// Run whisper-cli on `audio_path`, adding `output_flag` (e.g. "--output-txt") if given
+    fn run_whisper(
+        &self,
+        audio_path: &Path,
+        output_flag: Option<&str>,
+        language: Option<&str>,
+        task: Task,
+        should_cancel: &dyn Fn() -> bool,
+    ) -> Result<Output> {
+        // Segment timestamps are only wanted in the JSON output
+        let timestamps = output_flag == Some("--output-json");
+        let mut command =
+            self.whisper_command(audio_path, output_flag, timestamps, language, task)?;
+        let _permit = self.limiter.acquire("transcription");
+        // A hung whisper (e.g. on corrupt audio) is killed like a cancelled one
+        let started = Instant::now();
+        let timed_out = || {
+            self.timeout
+                .is_some_and(|timeout| started.elapsed() > timeout)
+        };
+        let result = run_cancellable(&mut command, &|| should_cancel() || timed_out());
+        if let (Err(e), Some(timeout)) = (&result, self.timeout) {
+            if e.is::<Cancelled>() && timed_out() {
+                return Err(TranscribeError::TimedOut(timeout).into());
+            }
+        }
+        result.context("Failed to execute whisper process")
+    }
+
+    /// whisper-cli command line for `audio_path`; `timestamps` keeps the "[start --> end]" prefixes
+    fn whisper_command(
+        &self,
+        audio_path: &Path,
+        output_flag: Option<&str>,
+        timestamps: bool,
+        language: Option<&str>,
+        task: Task,
+    ) -> Result<Command> {
+        let executable_path = self
+            .executable_path
+            .as_ref()
+            .ok_or_else(|| anyhow!("Whisper executable not found"))?;
+        let mut command = Command::new(executable_path);
+        command
             .arg("-m")
             .arg(&self.model_path)
             .arg("-f")
-            .arg(&audio_path)
-            .arg("--output-txt")
-            .arg("-nt") // No timestamps in output
-            .arg("-l")
-            .arg("auto") // Auto-detect language
-            // .arg("--prompt")
-            // .arg("Multilingual transcription. Transcrição multilíngue. English and Portuguese text. Texto em inglês e português.")
-            .output()
-            .map_err(|e| anyhow!("Failed to execute whisper process: {}", e))?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            eprintln!("Whisper stdout: {}", stdout);
-            eprintln!("Whisper stderr: {}", stderr);
-            return Err(anyhow!(
-                "Whisper process execution failed (exit code: {:?})",
-                output.status.code()
-            ));
-        }
-
-        let raw_output = String::from_utf8_lossy(&output.stdout).to_string();
-
-        // Cleanup common artifacts and Whisper hallucinations
-        let clean_text = raw_output
-            .trim()
-            .replace("[BLANK_AUDIO]", "")
-            .replace("[MÚSICA]", "")
-            .replace("[MÚSICA DE FUNDO]", "")
-            // Whisper hallucinations (echoes of the initial prompt)
-            .replace("Multilingual transcription.", "")
-            .replace("Transcrição multilíngue.", "")
-            .replace("English and Portuguese text.", "")
-            .replace("Texto em inglês e português.", "")
-            .replace("Text in english.", "")
-            .replace("Text in English.", "")
-            .replace("Texto em inglês.", "")
-            .replace("Texto em português.", "")
-            .trim()
-            .to_string();
-
-        Ok(clean_text)
+            .arg(audio_path);
+        if let Some(flag) = output_flag {
+            command.arg(flag);
+        }
+        if !timestamps {
+            command.arg("-nt"); // No timestamps in output
+        }
+        command.arg("-t").arg(self.threads.to_string());
+        command.arg("-l").arg(language.unwrap_or("auto")); // Auto-detect unless a language is forced or pinned
+        if task == Task::Translate {
+            command.arg("--translate");
+        }
+        if let Some(prompt) = self.prompt(language, task) {
+            command.arg("--prompt").arg(prompt);
+        }
+        command.args(&self.extra_args);
+
+        if self.verbose {
+            debug!(
+                "Whisper command: {}",
+                format_command(&command, self.log_sensitive)
+            );
+        }
+        Ok(command)
+    }
+}
+
+/// Whether a failed whisper run complained about the --output-txt flag itself
+fn rejects_output_txt(output: &Output) -> bool {
+    let stderr = String::from_utf8_lossy(&output.stderr).to_lowercase();
+    let stdout = String::from_utf8_lossy(&output.stdout).to_lowercase();
+    [stderr, stdout].iter().any(|text| {
+        text.contains("--output-txt")
+            && (text.contains("unknown argument") || text.contains("unrecognized"))
+    })
+}
+
+/// Language code from whisper's "auto-detected language: xx (p = ...)" log line
+fn detected_language(stderr: &str) -> Option<String> {
+    stderr.lines().find_map(|line| {
+        let (_, rest) = line.split_once("auto-detected language:")?;
+        rest.split_whitespace().next().map(str::to_string)
+    })
+}
+
+/// Path of the file whisper writes for `--output-txt`/`--output-json` ('<audio file>.<extension>')
+fn sidecar_path(audio_path: &Path, extension: &str) -> PathBuf {
+    let mut path = audio_path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// Error out (printing whisper's output) if the run failed
+fn check_status(output: &Output) -> Result<()> {
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    error!("Whisper stdout: {}", stdout);
+    error!("Whisper stderr: {}", stderr);
+    Err(anyhow!(
+        "Whisper process execution failed (exit code: {:?})",
+        output.status.code()
+    ))
+}
+
+/// Turn a successful whisper run's output into text
+/// Stdout is preferred; the sidecar txt is only consulted when stdout is empty.
+/// Returns `TranscribeError::NoSpeech` when output exists but cleans up to nothing,
+/// and `TranscribeError::MissingOutput` when there is no output to read at all.
+fn interpret_output(stdout: &str, sidecar: Option<&str>, sidecar_path: &Path) -> Result<String> {
+    let raw_output = if !stdout.trim().is_empty() {
+        stdout
+    } else {
+        match sidecar {
+            Some(text) => text,
+            None => return Err(TranscribeError::MissingOutput(sidecar_path.to_path_buf()).into()),
+        }
+    };
+
+    let clean_text = clean_transcription(raw_output);
+    if clean_text.is_empty() {
+        return Err(TranscribeError::NoSpeech.into());
+    }
+    Ok(clean_text)
+}
+
+/// Keep only the transcription text from whisper's stdout
+/// Verbose and debug runs mix in log lines, `[00:00.000 --> 00:02.000]` timestamps,
+/// special tokens like `[_BEG_]` or `<|en|>` and ANSI colours (`-pc`); all are dropped.
+fn extract_transcript(stdout: &str) -> String {
+    stdout
+        .lines()
+        .map(strip_ansi)
+        .filter(|line| !is_log_line(line))
+        .map(|line| strip_tokens(strip_timestamp(&line)))
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether a stdout line is whisper.cpp/ggml diagnostics rather than transcription
+fn is_log_line(line: &str) -> bool {
+    const LOG_PREFIXES: [&str; 8] = [
+        "whisper_",
+        "ggml_",
+        "ggml-",
+        "main:",
+        "system_info:",
+        "output_txt:",
+        "load_backend:",
+        "read_audio_data",
+    ];
+    let line = line.trim_start();
+    LOG_PREFIXES.iter().any(|prefix| line.starts_with(prefix)) || line.contains("progress =")
+}
+
+/// Remove a leading `[start --> end]` segment timestamp
+fn strip_timestamp(line: &str) -> &str {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('[') {
+        if let Some(end) = trimmed.find(']') {
+            if trimmed[..end].contains("-->") {
+                return &trimmed[end + 1..];
+            }
+        }
+    }
+    line
+}
+
+/// Remove special tokens (`[_BEG_]`, `[_TT_150]`, `<|en|>`) printed by --print-special
+fn strip_tokens(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+    loop {
+        let next = [("[_", "]"), ("<|", "|>")]
+            .iter()
+            .filter_map(|(open, close)| rest.find(open).map(|start| (start, *open, *close)))
+            .min_by_key(|(start, _, _)| *start);
+        let Some((start, open, close)) = next else {
+            result.push_str(rest);
+            return result;
+        };
+        result.push_str(&rest[..start]);
+        match rest[start + open.len()..].find(close) {
+            Some(end) => rest = &rest[start + open.len() + end + close.len()..],
+            None => {
+                result.push_str(&rest[start..]);
+                return result;
+            }
+        }
+    }
+}
+
+/// Remove ANSI escape sequences (whisper colours tokens by confidence with -pc)
+fn strip_ansi(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip "ESC [ params letter"
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Cleanup common artifacts and Whisper hallucinations
+fn clean_transcription(raw_output: &str) -> String {
+    raw_output
+        .trim()
+        .replace("[BLANK_AUDIO]", "")
+        .replace("[MÚSICA]", "")
+        .replace("[MÚSICA DE FUNDO]", "")
+        // Whisper hallucinations (echoes of the initial prompt)
+        .replace("Multilingual transcription.", "")
+        .replace("Transcrição multilíngue.", "")
+        .replace("English and Portuguese text.", "")
+        .replace("Texto em inglês e português.", "")
+        .replace("Text in english.", "")
+        .replace("Text in English.", "")
+        .replace("Texto em inglês.", "")
+        .replace("Texto em português.", "")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_no_speech(result: &Result<String>) -> bool {
+        matches!(
+            result
+                .as_ref()
+                .unwrap_err()
+                .downcast_ref::<TranscribeError>(),
+            Some(TranscribeError::NoSpeech)
+        )
+    }
+
+    #[test]
+    fn empty_output_from_a_successful_run_is_no_speech() {
+        let sidecar_path = Path::new("input.wav.txt");
+        assert!(is_no_speech(&interpret_output("", Some(""), sidecar_path)));
+        assert!(is_no_speech(&interpret_output(
+            "\n",
+            Some(" [BLANK_AUDIO]\n"),
+            sidecar_path
+        )));
+    }
+
+    #[test]
+    fn missing_sidecar_with_empty_stdout_is_missing_output() {
+        let sidecar_path = Path::new("input.wav.txt");
+        let error = interpret_output("  \n", None, sidecar_path).unwrap_err();
+        match error.downcast_ref::<TranscribeError>() {
+            Some(TranscribeError::MissingOutput(path)) => assert_eq!(path, sidecar_path),
+            other => panic!("expected MissingOutput, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sidecar_is_used_when_stdout_is_empty() {
+        let text = interpret_output("", Some(" Hello world.\n"), Path::new("input.wav.txt"));
+        assert_eq!(text.unwrap(), "Hello world.");
+    }
+
+    #[test]
+    fn stdout_wins_over_the_sidecar() {
+        let text = interpret_output(" From stdout.", Some("From the file."), Path::new("x.txt"));
+        assert_eq!(text.unwrap(), "From stdout.");
+    }
+
+    #[test]
+    fn verbose_output_is_reduced_to_the_transcript() {
+        let stdout = "whisper_init_from_file_with_params_no_state: loading model from 'ggml-base.bin'\n\
+            system_info: n_threads = 4 / 8 | AVX = 1 |\n\
+            main: processing 'input.wav' (32000 samples, 2.0 sec), 4 threads, lang = en\n\
+            \n\
+            [00:00:00.000 --> 00:00:01.200]  [_BEG_] Hello[_TT_60] there.<|en|>\n\
+            [00:00:01.200 --> 00:00:02.000]   \u{1b}[38;5;160m How\u{1b}[0m\u{1b}[38;5;82m are you?\u{1b}[0m\n\
+            whisper_print_timings:     load time =    45.12 ms\n";
+        assert_eq!(extract_transcript(stdout), "Hello there.\nHow are you?");
+    }
+
+    #[test]
+    fn plain_output_is_kept() {
+        assert_eq!(extract_transcript(" Hello there.\n"), "Hello there.");
+        // Brackets that aren't timestamps or tokens are the speaker's
+        assert_eq!(
+            extract_transcript("[laughs] <b>ok</b>"),
+            "[laughs] <b>ok</b>"
+        );
+        assert_eq!(strip_tokens("a [_unclosed"), "a [_unclosed");
+    }
+
+    #[test]
+    fn only_recordings_past_the_threshold_are_chunked() {
+        let threshold = Some(Duration::from_secs(60));
+        assert!(!needs_chunking(Duration::from_secs(30), threshold));
+        assert!(!needs_chunking(Duration::from_secs(60), threshold));
+        assert!(needs_chunking(Duration::from_secs(61), threshold));
+        assert!(!needs_chunking(Duration::from_secs(3600), None));
+    }
+
+    #[test]
+    fn words_per_second_counts_words_over_the_duration() {
+        let rate = words_per_second("one two three four", Duration::from_secs(2));
+        assert_eq!(rate, 2.0);
+        assert_eq!(words_per_second("anything", Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn too_many_words_for_the_audio_is_implausible() {
+        let long = "word ".repeat(30);
+        let max = Some(8.0);
+        assert!(is_implausible_rate(&long, Duration::from_secs(1), max));
+        assert!(!is_implausible_rate(&long, Duration::from_secs(10), max));
+        assert!(!is_implausible_rate(&long, Duration::from_secs(1), None));
+    }
+
+    /// A whisper-cli transcriber running `executable`
+    fn transcriber_with(executable: PathBuf) -> Transcriber {
+        Transcriber {
+            model_path: executable.with_file_name("model.bin"),
+            executable_path: Some(executable),
+            #[cfg(feature = "whisper-rs")]
+            resident: None,
+            output_txt_supported: AtomicBool::new(true),
+            extra_args: Vec::new(),
+            threads: 1,
+            timeout: None,
+            prompts: Prompts::default(),
+            verbose: false,
+            log_sensitive: false,
+            strip_markup: true,
+            limiter: Arc::new(HeavyOpLimiter::new(0)),
+        }
+    }
+
+    #[test]
+    fn forced_language_gets_its_own_prompt() {
+        let mut transcriber = transcriber_with(PathBuf::from("whisper-cli"));
+        transcriber.prompts = Prompts {
+            default: Some("Hello.".to_string()),
+            by_language: vec![("pt".to_string(), "Olá, tudo bem?".to_string())],
+        };
+        let args = |language, task| -> Vec<String> {
+            let command = transcriber
+                .whisper_command(Path::new("in.wav"), None, false, language, task)
+                .unwrap();
+            command
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect()
+        };
+        let has = |args: &[String], flag: &str, value: &str| {
+            args.windows(2)
+                .any(|pair| pair[0] == flag && pair[1] == value)
+        };
+
+        let forced = args(Some("PT"), Task::Transcribe);
+        assert!(has(&forced, "-l", "PT"));
+        assert!(has(&forced, "--prompt", "Olá, tudo bem?"));
+        let detected = args(None, Task::Transcribe);
+        assert!(has(&detected, "-l", "auto"));
+        assert!(has(&detected, "--prompt", "Hello."));
+        let other = args(Some("de"), Task::Transcribe);
+        assert!(has(&other, "--prompt", "Hello."));
+        let translated = args(Some("pt"), Task::Translate);
+        assert!(!translated.iter().any(|arg| arg == "--prompt"));
+    }
+
+    #[test]
+    fn extra_args_are_appended_in_order_after_the_managed_ones() {
+        let mut transcriber = transcriber_with(PathBuf::from("whisper-cli"));
+        transcriber.extra_args = crate::config::split_args("-bs 5 --prompt 'Hi there'").unwrap();
+        let command = transcriber
+            .whisper_command(
+                Path::new("in.wav"),
+                None,
+                false,
+                Some("en"),
+                Task::Translate,
+            )
+            .unwrap();
+        let args: Vec<String> = command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            args[args.len() - 5..],
+            ["--translate", "-bs", "5", "--prompt", "Hi there"]
+        );
+    }
+
+    /// A transcriber whose whisper-cli is the shell `script`, written to its own directory
+    #[cfg(unix)]
+    fn fake_whisper(name: &str, script: &str) -> (Transcriber, PathBuf) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join(format!("fake_whisper_{}_{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let executable = dir.join("whisper-cli");
+        std::fs::write(&executable, format!("#!/bin/sh\n{}", script)).unwrap();
+        std::fs::set_permissions(&executable, std::fs::Permissions::from_mode(0o755)).unwrap();
+        (transcriber_with(executable), dir)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn segments_are_read_from_the_json_next_to_the_audio() {
+        let (transcriber, dir) = fake_whisper(
+            "segments",
+            "while [ $# -gt 0 ]; do\n\
+               if [ \"$1\" = -f ]; then audio=\"$2\"; fi\n\
+               shift\n\
+             done\n\
+             cat > \"$audio.json\" <<'EOF'\n\
+             {\"transcription\": [\n\
+               {\"offsets\": {\"from\": 0, \"to\": 1500}, \"text\": \" Hello there.\"},\n\
+               {\"offsets\": {\"from\": 1500, \"to\": 2000}, \"text\": \" [BLANK_AUDIO]\"},\n\
+               {\"offsets\": {\"from\": 2000, \"to\": 4250}, \"text\": \" How are you?\"}\n\
+             ]}\n\
+             EOF\n",
+        );
+        let audio_path = dir.join("input.wav");
+        let segments = transcriber
+            .transcribe_segments(&audio_path, None, &|| false)
+            .unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                Segment {
+                    start: Duration::ZERO,
+                    end: Duration::from_millis(1500),
+                    text: "Hello there.".to_string(),
+                },
+                Segment {
+                    start: Duration::from_millis(2000),
+                    end: Duration::from_millis(4250),
+                    text: "How are you?".to_string(),
+                },
+            ]
+        );
+        assert!(!sidecar_path(&audio_path, "json").exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unknown_output_txt_flag_is_retried_without_it() {
+        let (transcriber, dir) = fake_whisper(
+            "no_output_txt",
+            "echo run >> \"$0.runs\"\n\
+             for arg in \"$@\"; do\n\
+               if [ \"$arg\" = --output-txt ]; then\n\
+                 echo 'error: unknown argument: --output-txt' >&2\n\
+                 exit 1\n\
+               fi\n\
+             done\n\
+             echo '[00:00:00.000 --> 00:00:01.000]   Hello there.'\n",
+        );
+        let audio_path = dir.join("input.wav");
+        let runs = || {
+            std::fs::read_to_string(dir.join("whisper-cli.runs"))
+                .unwrap()
+                .lines()
+                .count()
+        };
+
+        let transcription = transcriber
+            .transcribe(&audio_path, None, Task::Transcribe, &|| false)
+            .unwrap();
+        assert_eq!(transcription.text, "Hello there.");
+        assert_eq!(runs(), 2);
+
+        // The unsupported flag is remembered, so later runs go straight to stdout
+        transcriber
+            .transcribe(&audio_path, None, Task::Transcribe, &|| false)
+            .unwrap();
+        assert_eq!(runs(), 3);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stream_yields_segments_as_whisper_prints_them() {
+        use tokio_stream::StreamExt;
+
+        let (transcriber, dir) = fake_whisper(
+            "stream",
+            "echo '[00:00:00.000 --> 00:00:01.500]   Hello there.'\n\
+             sleep 0.1\n\
+             echo 'whisper_print_timings: total time = 1.0 ms'\n\
+             echo '[00:00:01.500 --> 00:00:02.000]  [BLANK_AUDIO]'\n\
+             sleep 0.1\n\
+             echo '[00:00:02.000 --> 00:00:04.250]  How are you?'\n",
+        );
+        let stream = transcriber
+            .transcribe_stream(&dir.join("input.wav"), None)
+            .unwrap();
+        let segments: Vec<Segment> = stream.map(|segment| segment.unwrap()).collect().await;
+        assert_eq!(
+            segments,
+            vec![
+                Segment {
+                    start: Duration::ZERO,
+                    end: Duration::from_millis(1500),
+                    text: "Hello there.".to_string(),
+                },
+                Segment {
+                    start: Duration::from_millis(2000),
+                    end: Duration::from_millis(4250),
+                    text: "How are you?".to_string(),
+                },
+            ]
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stream_ends_with_an_error_when_whisper_fails() {
+        use tokio_stream::StreamExt;
+
+        let (transcriber, dir) = fake_whisper(
+            "stream_fails",
+            "echo '[00:00:00.000 --> 00:00:01.000]  Hello.'\nexit 3\n",
+        );
+        let mut stream = Box::pin(
+            transcriber
+                .transcribe_stream(&dir.join("input.wav"), None)
+                .unwrap(),
+        );
+        assert_eq!(stream.next().await.unwrap().unwrap().text, "Hello.");
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn stream_reports_a_whisper_that_cannot_be_started() {
+        use tokio_stream::StreamExt;
+
+        let transcriber = transcriber_with(env::temp_dir().join("no_such_whisper_cli"));
+        let mut stream = Box::pin(
+            transcriber
+                .transcribe_stream(Path::new("input.wav"), None)
+                .unwrap(),
+        );
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.is_none());
     }
 }
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use std::sync::mpsc::Sender;
+
+/// Commands the tray menu sends to the hotkey loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayCommand {
+    /// Start a recording, or stop and transcribe the one running
+    Record,
+    /// Stop reading aloud
+    StopSpeaking,
+    /// Pause or resume hotkey handling
+    ToggleEnabled,
+    /// Re-read the config file, like F11
+    ReloadConfig,
+    Quit,
+}
+
+/// Tray menu entries in display order, with the command each one sends
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub const MENU: [(&str, TrayCommand); 5] = [
+    ("Start/stop recording", TrayCommand::Record),
+    ("Stop speaking", TrayCommand::StopSpeaking),
+    ("Enable/disable hotkeys", TrayCommand::ToggleEnabled),
+    ("Reload config", TrayCommand::ReloadConfig),
+    ("Quit", TrayCommand::Quit),
+];
+
+/// What the tool is doing, shown at the top of the tray menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayState {
+    Idle,
+    Recording,
+    Transcribing,
+    Speaking,
+}
+
+impl TrayState {
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    fn label(self) -> &'static str {
+        match self {
+            TrayState::Idle => "Idle",
+            TrayState::Recording => "Recording...",
+            TrayState::Transcribing => "Transcribing...",
+            TrayState::Speaking => "Speaking...",
+        }
+    }
+}
+
+/// Command sent by the menu entry labelled `label`
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub fn command_for(label: &str) -> Option<TrayCommand> {
+    MENU.iter()
+        .find(|(entry, _)| *entry == label)
+        .map(|(_, command)| *command)
+}
+
+/// The tray icon, kept as long as it should be shown
+#[cfg(target_os = "windows")]
+pub struct Tray {
+    item: tray_item::TrayItem,
+    state_label: u32,
+    state: TrayState,
+}
+
+#[cfg(target_os = "windows")]
+impl Tray {
+    /// Show `state` in the menu; cheap to call repeatedly with the same state
+    pub fn set_state(&mut self, state: TrayState) {
+        if state == self.state {
+            return;
+        }
+        self.state = state;
+        if let Err(e) = self
+            .item
+            .inner_mut()
+            .set_label(state.label(), self.state_label)
+        {
+            tracing::warn!("Failed to update the tray menu: {}", e);
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub struct Tray;
+
+#[cfg(not(target_os = "windows"))]
+impl Tray {
+    pub fn set_state(&mut self, _state: TrayState) {}
+}
+
+/// Show the tray icon and hide the console window; menu clicks are sent to `commands`
+/// The icon stays as long as the returned handle is kept alive.
+#[cfg(target_os = "windows")]
+pub fn spawn(commands: Sender<TrayCommand>) -> Result<Tray> {
+    use tray_item::{IconSource, TrayItem};
+    use windows_sys::Win32::System::Console::GetConsoleWindow;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        LoadIconW, ShowWindow, IDI_APPLICATION, SW_HIDE,
+    };
+
+    // Stock application icon, so no icon resource has to be compiled in
+    let icon = unsafe { LoadIconW(0, IDI_APPLICATION) };
+    let mut tray = TrayItem::new("Local TTS Tool", IconSource::RawIcon(icon))?;
+    let state_label = tray
+        .inner_mut()
+        .add_label_with_id(TrayState::Idle.label())?;
+
+    for (label, _) in MENU {
+        let commands = commands.clone();
+        tray.add_menu_item(label, move || {
+            if let Some(command) = command_for(label) {
+                let _ = commands.send(command);
+            }
+        })?;
+    }
+
+    unsafe {
+        let console = GetConsoleWindow();
+        if console != 0 {
+            ShowWindow(console, SW_HIDE);
+        }
+    }
+    Ok(Tray {
+        item: tray,
+        state_label,
+        state: TrayState::Idle,
+    })
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn spawn(_commands: Sender<TrayCommand>) -> Result<Tray> {
+    Err(anyhow::anyhow!("Tray mode is only supported on Windows"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu_entries_send_their_commands() {
+        assert_eq!(
+            command_for("Start/stop recording"),
+            Some(TrayCommand::Record)
+        );
+        assert_eq!(
+            command_for("Reload config"),
+            Some(TrayCommand::ReloadConfig)
+        );
+        assert_eq!(command_for("Quit"), Some(TrayCommand::Quit));
+        assert_eq!(command_for("Settings"), None);
+        // Every entry has a label of its own, so no click is sent as another command
+        for (label, command) in MENU {
+            assert_eq!(command_for(label), Some(command));
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+/// Time constant of the smoothed level, so short dips between words don't count as silence
+const SMOOTHING: Duration = Duration::from_millis(200);
+
+/// Speech has to be this many times louder than the noise floor (besides the threshold)
+const NOISE_MARGIN: f32 = 2.0;
+
+/// Tracks how long the input has been silent, fed block by block from the input callback
+///
+/// The level is a rolling RMS over roughly `SMOOTHING`. Silence only starts counting once
+/// speech was heard, so the time before the user starts talking never ends a recording
+/// (and that audio is kept). On quiet microphones whose noise floor sits near the
+/// threshold, the lowest level seen so far is taken as the noise floor and speech has
+/// to be `NOISE_MARGIN` times louder than it.
+pub struct SilenceDetector {
+    threshold: f32,
+    mean_square: Option<f32>,
+    noise_floor: Option<f32>,
+    heard_speech: bool,
+    silent_frames: u64,
+}
+
+impl SilenceDetector {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            mean_square: None,
+            noise_floor: None,
+            heard_speech: false,
+            silent_frames: 0,
+        }
+    }
+
+    /// Input RMS (0-1) below which the microphone counts as silent
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    /// Feed one block of interleaved samples
+    pub fn update(&mut self, block: &[f32], channels: u16, sample_rate: u32) {
+        if block.is_empty() {
+            return;
+        }
+        let frames = (block.len() / channels.max(1) as usize) as u64;
+        let block_mean_square = block.iter().map(|s| s * s).sum::<f32>() / block.len() as f32;
+        let block_length = frames as f32 / sample_rate.max(1) as f32;
+        let alpha = 1.0 - (-block_length / SMOOTHING.as_secs_f32()).exp();
+        let mean_square = match self.mean_square {
+            Some(previous) => previous + alpha * (block_mean_square - previous),
+            None => block_mean_square,
+        };
+        self.mean_square = Some(mean_square);
+
+        let rms = mean_square.sqrt();
+        let floor = self.noise_floor.map_or(rms, |floor| floor.min(rms));
+        self.noise_floor = Some(floor);
+
+        if rms >= self.threshold.max(floor * NOISE_MARGIN) {
+            self.heard_speech = true;
+            self.silent_frames = 0;
+        } else if self.heard_speech {
+            self.silent_frames += frames;
+        }
+    }
+
+    /// Frames of silence since speech was last heard (0 before any speech)
+    pub fn silent_frames(&self) -> u64 {
+        self.silent_frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `blocks` 20 ms blocks at `level` to `detector`, 16 kHz mono
+    fn feed(detector: &mut SilenceDetector, level: f32, blocks: usize) {
+        let block = vec![level; 320];
+        for _ in 0..blocks {
+            detector.update(&block, 1, 16000);
+        }
+    }
+
+    #[test]
+    fn silence_counts_only_after_speech() {
+        let mut detector = SilenceDetector::new(0.01);
+        feed(&mut detector, 0.0, 50);
+        assert_eq!(detector.silent_frames(), 0);
+
+        feed(&mut detector, 0.3, 25);
+        assert_eq!(detector.silent_frames(), 0);
+
+        // The smoothed level takes a moment to fall below the threshold
+        feed(&mut detector, 0.0, 100);
+        let silent = detector.silent_frames();
+        assert!(silent > 0 && silent < 100 * 320, "{}", silent);
+
+        feed(&mut detector, 0.3, 1);
+        assert_eq!(detector.silent_frames(), 0);
+    }
+
+    #[test]
+    fn short_dip_between_words_is_not_silence() {
+        let mut detector = SilenceDetector::new(0.01);
+        feed(&mut detector, 0.3, 25);
+        feed(&mut detector, 0.0, 5);
+        assert_eq!(detector.silent_frames(), 0);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::narrate::language_code;
+use std::cmp::Reverse;
+use whatlang::detect;
+
+/// Spoken punctuation/formatting commands for a language, as (phrase, replacement)
+/// Phrases are lowercase; longer phrases are always tried before shorter ones,
+/// so "ponto e vírgula" wins over "ponto".
+fn command_map(lang: &str) -> &'static [(&'static str, &'static str)] {
+    match lang {
+        "en" => &[
+            ("new paragraph", "\n\n"),
+            ("new line", "\n"),
+            ("full stop", "."),
+            ("period", "."),
+            ("comma", ","),
+            ("question mark", "?"),
+            ("exclamation mark", "!"),
+            ("exclamation point", "!"),
+            ("colon", ":"),
+            ("semicolon", ";"),
+        ],
+        "pt" => &[
+            ("novo parágrafo", "\n\n"),
+            ("nova linha", "\n"),
+            ("ponto final", "."),
+            ("ponto de interrogação", "?"),
+            ("ponto de exclamação", "!"),
+            ("ponto e vírgula", ";"),
+            ("dois pontos", ":"),
+            ("vírgula", ","),
+            ("ponto", "."),
+        ],
+        _ => &[],
+    }
+}
+
+/// Languages that have a command map
+const COMMAND_LANGUAGES: [&str; 2] = ["en", "pt"];
+
+/// Replace spoken commands in `text` using the map for `language`
+/// When `language` is None it is detected from the text; if detection isn't reliable
+/// (common for short dictations) every available map is applied in turn.
+///
+/// A command only counts in a command position: at the end of the dictation (possibly
+/// after other commands, as in "thanks period new line"), or anywhere right after
+/// `prefix` (e.g. "hello command comma world" with the prefix "command"). Elsewhere
+/// "period" or "ponto" is an ordinary word. Spacing between other words is kept.
+pub fn apply(text: &str, language: Option<&str>, prefix: Option<&str>) -> String {
+    let detected = detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| language_code(info.lang()));
+
+    match language.or(detected) {
+        Some(lang) => apply_map(text, command_map(lang), prefix),
+        None => COMMAND_LANGUAGES
+            .iter()
+            .fold(text.to_string(), |text, lang| {
+                apply_map(&text, command_map(lang), prefix)
+            }),
+    }
+}
+
+/// A word of the dictation, or a command phrase found there
+struct Token<'a> {
+    /// Byte range in the text, including the prefix of a prefixed command
+    start: usize,
+    end: usize,
+    /// Replacement for a command, None for a plain word
+    command: Option<&'a str>,
+    prefixed: bool,
+}
+
+fn apply_map(text: &str, map: &[(&str, &str)], prefix: Option<&str>) -> String {
+    if map.is_empty() {
+        return text.to_string();
+    }
+
+    let mut commands: Vec<(Vec<&str>, &str)> = map
+        .iter()
+        .map(|(phrase, replacement)| (phrase.split_whitespace().collect(), *replacement))
+        .collect();
+    commands.sort_by_key(|command| Reverse(command.0.len()));
+    let prefix: Vec<String> = prefix
+        .map(|prefix| prefix.split_whitespace().map(normalize).collect())
+        .unwrap_or_default();
+
+    let words = word_spans(text);
+    let phrase_at = |i: usize| {
+        commands.iter().find_map(|(phrase, replacement)| {
+            let matches = i + phrase.len() <= words.len()
+                && phrase
+                    .iter()
+                    .zip(&words[i..])
+                    .all(|(expected, &(start, end))| normalize(&text[start..end]) == *expected);
+            matches.then_some((phrase.len(), *replacement))
+        })
+    };
+
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let prefixed = !prefix.is_empty()
+            && i + prefix.len() <= words.len()
+            && prefix
+                .iter()
+                .zip(&words[i..])
+                .all(|(expected, &(start, end))| normalize(&text[start..end]) == *expected);
+        if prefixed {
+            if let Some((len, replacement)) = phrase_at(i + prefix.len()) {
+                let last = i + prefix.len() + len - 1;
+                tokens.push(Token {
+                    start: words[i].0,
+                    end: words[last].1,
+                    command: Some(replacement),
+                    prefixed: true,
+                });
+                i = last + 1;
+                continue;
+            }
+        }
+        if let Some((len, replacement)) = phrase_at(i) {
+            tokens.push(Token {
+                start: words[i].0,
+                end: words[i + len - 1].1,
+                command: Some(replacement),
+                prefixed: false,
+            });
+            i += len;
+            continue;
+        }
+        tokens.push(Token {
+            start: words[i].0,
+            end: words[i].1,
+            command: None,
+            prefixed: false,
+        });
+        i += 1;
+    }
+
+    // The commands closing the dictation apply without the prefix
+    let trailing = tokens
+        .iter()
+        .rev()
+        .take_while(|token| token.command.is_some())
+        .count();
+    let trailing_from = tokens.len() - trailing;
+
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        let Some(replacement) = token.command else {
+            continue;
+        };
+        if !token.prefixed && index < trailing_from {
+            continue;
+        }
+        // Punctuation and line breaks attach to the text before them
+        let before = text[copied..token.start].trim_end();
+        if replacement.trim().is_empty() {
+            result.push_str(before);
+        } else {
+            result.push_str(strip_punctuation(before));
+        }
+        result.push_str(replacement);
+        copied = token.end;
+        if replacement.ends_with('\n') {
+            copied += text[copied..].len() - text[copied..].trim_start().len();
+        }
+    }
+    result.push_str(&text[copied..]);
+    result
+}
+
+/// Byte ranges of the whitespace-separated words of `text`
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(word_start)) => {
+                spans.push((word_start, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(word_start) = start {
+        spans.push((word_start, text.len()));
+    }
+    spans
+}
+
+/// `text` without a trailing spoken end command `phrase` (e.g. "end dictation"), or None
+/// if it doesn't end with it
+/// Case and the punctuation whisper puts around words are ignored, so "Thanks. End dictation."
+/// gives "Thanks.".
+pub fn strip_end_command(text: &str, phrase: &str) -> Option<String> {
+    let expected: Vec<String> = phrase.split_whitespace().map(normalize).collect();
+    if expected.is_empty() {
+        return None;
+    }
+    let mut rest = text.trim_end();
+    for expected in expected.iter().rev() {
+        let start = rest
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        if rest.is_empty() || normalize(&rest[start..]) != *expected {
+            return None;
+        }
+        rest = rest[..start].trim_end();
+    }
+    Some(rest.trim_end_matches([',', ';', ':']).to_string())
+}
+
+/// Lowercase a word and strip the punctuation whisper tends to put around it
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// `text` without whisper's own punctuation at its end, which a spoken punctuation command
+/// replaces ("Hello, comma, world" should become "Hello, world", not "Hello,, world")
+fn strip_punctuation(text: &str) -> &str {
+    text.trim_end_matches([',', '.', ';', ':', '!', '?'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_commands_are_replaced() {
+        assert_eq!(
+            apply("See you tomorrow period", Some("en"), None),
+            "See you tomorrow."
+        );
+        assert_eq!(
+            apply("Thanks, period. New line.", Some("en"), None),
+            "Thanks.\n"
+        );
+        assert_eq!(
+            apply("Até amanhã ponto final", Some("pt"), None),
+            "Até amanhã."
+        );
+    }
+
+    #[test]
+    fn commands_in_the_middle_are_plain_words() {
+        assert_eq!(
+            apply("The trial period  ended today", Some("en"), None),
+            "The trial period  ended today"
+        );
+        assert_eq!(
+            apply("O ponto de ônibus fica ali", Some("pt"), None),
+            "O ponto de ônibus fica ali"
+        );
+    }
+
+    #[test]
+    fn prefix_marks_commands_anywhere() {
+        assert_eq!(
+            apply(
+                "Hello, command comma, world command period",
+                Some("en"),
+                Some("command")
+            ),
+            "Hello, world."
+        );
+        assert_eq!(
+            apply("First command new line second", Some("en"), Some("command")),
+            "First\nsecond"
+        );
+        // Without a command after it, the prefix is an ordinary word
+        assert_eq!(
+            apply("Run the command again", Some("en"), Some("command")),
+            "Run the command again"
+        );
+    }
+
+    #[test]
+    fn map_follows_the_language() {
+        assert_eq!(apply("Tudo bem vírgula", Some("pt"), None), "Tudo bem,");
+        assert_eq!(
+            apply("All good vírgula", Some("en"), None),
+            "All good vírgula"
+        );
+        assert_eq!(apply("All good comma", Some("pt"), None), "All good comma");
+        assert_eq!(apply("Hi there comma", Some("de"), None), "Hi there comma");
+    }
+
+    #[test]
+    fn longer_phrases_win() {
+        assert_eq!(
+            apply("Isso mesmo ponto e vírgula", Some("pt"), None),
+            "Isso mesmo;"
+        );
+        assert_eq!(
+            apply("Será ponto de interrogação", Some("pt"), None),
+            "Será?"
+        );
+    }
+
+    #[test]
+    fn spacing_between_words_is_kept() {
+        assert_eq!(
+            apply("Line one\nline  two new paragraph", Some("en"), None),
+            "Line one\nline  two\n\n"
+        );
+    }
+
+    #[test]
+    fn end_command_is_stripped() {
+        assert_eq!(
+            strip_end_command("Thanks. End dictation.", "end dictation"),
+            Some("Thanks.".to_string())
+        );
+        assert_eq!(
+            strip_end_command("Thanks, send it", "send it"),
+            Some("Thanks".to_string())
+        );
+        assert_eq!(strip_end_command("Send it later", "send it"), None);
+        assert_eq!(strip_end_command("Thanks", ""), None);
+    }
+}
@@ -0,0 +1,252 @@
+//! In-process whisper through whisper-rs, keeping the model loaded between recordings
+//! Only built with the `whisper-rs` feature (needs LLVM/clang to compile whisper.cpp).
+
+use crate::transcribe::{default_threads, Segment, Task};
+use anyhow::{anyhow, Context, Result};
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+/// GPU backend compiled in, if any
+const GPU_BACKEND: Option<&str> = if cfg!(feature = "whisper-cuda") {
+    Some("CUDA")
+} else if cfg!(feature = "whisper-metal") {
+    Some("Metal")
+} else {
+    None
+};
+
+/// A whisper model loaded once and reused for every transcription
+pub struct ResidentModel {
+    path: PathBuf,
+    context: WhisperContext,
+    /// Whether the GPU should be tried, kept for reloading another model
+    use_gpu: bool,
+    /// Whether model files are memory-mapped, kept for reloading another model
+    mmap: bool,
+    /// CPU threads whisper may use
+    threads: usize,
+}
+
+impl ResidentModel {
+    /// Load the model on the GPU if `use_gpu` and this build has a GPU backend, otherwise
+    /// (or if that fails) on the CPU
+    /// With `mmap` the file is memory-mapped and whisper builds the context from the mapping
+    /// instead of reading the file itself; the mapping is released once the model is loaded.
+    pub fn load(path: &Path, use_gpu: bool, mmap: bool) -> Result<Self> {
+        info!("Loading whisper model '{}'...", path.display());
+        let model = ModelFile::open(path, mmap)?;
+        let gpu = GPU_BACKEND.filter(|_| use_gpu);
+        let (context, on_gpu) = with_cpu_fallback(gpu.is_some(), |gpu| {
+            let mut params = WhisperContextParameters::default();
+            params.use_gpu(gpu);
+            model
+                .create_context(params)
+                .map_err(|e| anyhow!("Failed to load whisper model '{}': {}", path.display(), e))
+        })?;
+        match gpu.filter(|_| on_gpu) {
+            Some(name) => info!("Whisper is running on the GPU ({})", name),
+            None => info!("Whisper is running on the CPU"),
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            context,
+            use_gpu,
+            mmap,
+            threads: default_threads(),
+        })
+    }
+
+    /// Load another model with the same GPU, mmap and thread settings
+    pub fn reload(&self, path: &Path) -> Result<Self> {
+        let mut model = Self::load(path, self.use_gpu, self.mmap)?;
+        model.threads = self.threads;
+        Ok(model)
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads;
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Raw text of 16 kHz mono `samples` and the language whisper detected
+    /// `language` forces the spoken language (e.g. "en"); None lets whisper auto-detect.
+    /// `Task::Translate` gives English text instead.
+    pub fn transcribe(
+        &self,
+        samples: &[f32],
+        language: Option<&str>,
+        prompt: Option<&str>,
+        task: Task,
+    ) -> Result<(String, Option<String>)> {
+        let (segments, detected) = self.transcribe_segments(samples, language, prompt, task)?;
+        let text = segments.into_iter().map(|segment| segment.text).collect();
+        Ok((text, detected))
+    }
+
+    /// Like `transcribe`, but keeps whisper's segments with their timestamps (raw text)
+    pub fn transcribe_segments(
+        &self,
+        samples: &[f32],
+        language: Option<&str>,
+        prompt: Option<&str>,
+        task: Task,
+    ) -> Result<(Vec<Segment>, Option<String>)> {
+        let mut state = self
+            .context
+            .create_state()
+            .map_err(|e| anyhow!("Failed to create whisper state: {}", e))?;
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(language.unwrap_or("auto")));
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_special(false);
+        params.set_print_timestamps(false);
+        params.set_translate(task == Task::Translate);
+        params.set_n_threads(self.threads.try_into().unwrap_or(i32::MAX));
+        if let Some(prompt) = prompt {
+            params.set_initial_prompt(prompt);
+        }
+
+        state
+            .full(params, samples)
+            .map_err(|e| anyhow!("Whisper failed: {}", e))?;
+
+        let count = state
+            .full_n_segments()
+            .map_err(|e| anyhow!("Failed to read whisper output: {}", e))?;
+        let mut segments = Vec::new();
+        for segment in 0..count {
+            let read_error = |e| anyhow!("Failed to read whisper output: {}", e);
+            let text = state.full_get_segment_text(segment).map_err(read_error)?;
+            // Timestamps are in centiseconds
+            let start = state.full_get_segment_t0(segment).map_err(read_error)?;
+            let end = state.full_get_segment_t1(segment).map_err(read_error)?;
+            segments.push(Segment {
+                start: Duration::from_millis(start.max(0) as u64 * 10),
+                end: Duration::from_millis(end.max(0) as u64 * 10),
+                text,
+            });
+        }
+
+        let detected = match language {
+            Some(_) => None,
+            None => state
+                .full_lang_id_from_state()
+                .ok()
+                .and_then(whisper_rs::get_lang_str)
+                .map(str::to_string),
+        };
+        Ok((segments, detected))
+    }
+}
+
+/// A model file opened for building a whisper context
+enum ModelFile {
+    /// Memory-mapped (WHISPER_MMAP)
+    Mapped(Mmap),
+    /// Read by whisper itself from this path
+    Path(String),
+}
+
+impl ModelFile {
+    fn open(path: &Path, mmap: bool) -> Result<Self> {
+        if !mmap {
+            let path = path
+                .to_str()
+                .ok_or_else(|| anyhow!("Model path '{}' is not valid UTF-8", path.display()))?;
+            return Ok(ModelFile::Path(path.to_string()));
+        }
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open whisper model '{}'", path.display()))?;
+        // Safety: the model isn't expected to change while it loads; a concurrent
+        // MODEL_CACHE_DIR refresh writes a new file and renames it over this one
+        let mapping = unsafe { Mmap::map(&file) }
+            .with_context(|| format!("Failed to map whisper model '{}'", path.display()))?;
+        Ok(ModelFile::Mapped(mapping))
+    }
+
+    fn create_context(
+        &self,
+        params: WhisperContextParameters,
+    ) -> Result<WhisperContext, whisper_rs::WhisperError> {
+        match self {
+            ModelFile::Mapped(mapping) => {
+                WhisperContext::new_from_buffer_with_params(mapping, params)
+            }
+            ModelFile::Path(path) => WhisperContext::new_with_params(path, params),
+        }
+    }
+}
+
+/// Run `create(true)` for the GPU if `try_gpu`, falling back to `create(false)` when it fails
+/// Also returns whether the GPU one succeeded.
+fn with_cpu_fallback<T>(try_gpu: bool, create: impl Fn(bool) -> Result<T>) -> Result<(T, bool)> {
+    if try_gpu {
+        match create(true) {
+            Ok(value) => return Ok((value, true)),
+            Err(e) => warn!("GPU initialization failed, using the CPU: {}", e),
+        }
+    }
+    Ok((create(false)?, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn model_is_memory_mapped_when_configured() {
+        let path = std::env::temp_dir().join(format!("mmap_test_{}.bin", std::process::id()));
+        std::fs::write(&path, b"ggml model bytes").unwrap();
+
+        let mapped = ModelFile::open(&path, true);
+        let read = ModelFile::open(&path, false);
+        let _ = std::fs::remove_file(&path);
+
+        match mapped.unwrap() {
+            ModelFile::Mapped(mapping) => assert_eq!(&mapping[..], b"ggml model bytes"),
+            ModelFile::Path(_) => panic!("expected a memory-mapped model"),
+        }
+        match read.unwrap() {
+            ModelFile::Path(read_path) => assert_eq!(Path::new(&read_path), path),
+            ModelFile::Mapped(_) => panic!("expected the model to be left to whisper"),
+        }
+    }
+
+    #[test]
+    fn missing_model_fails_to_map() {
+        let path = std::env::temp_dir().join("no_such_whisper_model.bin");
+        assert!(ModelFile::open(&path, true).is_err());
+    }
+
+    #[test]
+    fn failed_gpu_falls_back_to_the_cpu() {
+        let attempts = std::cell::RefCell::new(Vec::new());
+        let created = with_cpu_fallback(true, |gpu| {
+            attempts.borrow_mut().push(gpu);
+            if gpu {
+                Err(anyhow!("no CUDA device"))
+            } else {
+                Ok("cpu context")
+            }
+        });
+        assert_eq!(created.unwrap(), ("cpu context", false));
+        assert_eq!(attempts.into_inner(), vec![true, false]);
+    }
+
+    #[test]
+    fn working_gpu_is_used_and_cpu_only_skips_it() {
+        assert_eq!(with_cpu_fallback(true, Ok).unwrap(), (true, true));
+        assert_eq!(with_cpu_fallback(false, Ok).unwrap(), (false, false));
+        let failed = with_cpu_fallback(true, |_| Err::<(), _>(anyhow!("bad model")));
+        assert!(failed.is_err());
+    }
+}
@@ -0,0 +1,113 @@
+//! Runs whisper on a background thread, so the hotkey loop keeps polling while it works
+
+use crate::transcribe::{self, Task, Transcriber, Transcription};
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// One recording to transcribe
+pub struct Job {
+    /// The recording, already prepared for whisper
+    pub audio_path: PathBuf,
+    pub duration: Duration,
+    /// Language to force, None = auto-detect
+    pub language: Option<String>,
+    pub task: Task,
+    /// Recordings longer than this are transcribed in parts, None = never
+    pub chunk_threshold: Option<Duration>,
+    pub chunk_length: Duration,
+}
+
+/// At most one transcription at a time, its result collected with `poll`
+pub struct TranscriptionWorker {
+    /// Shared with the HTTP server, whose requests wait for the lock
+    transcriber: Arc<Mutex<Transcriber>>,
+    running: Option<Running>,
+}
+
+struct Running {
+    cancel: Arc<AtomicBool>,
+    result: Receiver<Result<Transcription>>,
+}
+
+impl TranscriptionWorker {
+    pub fn new(transcriber: Arc<Mutex<Transcriber>>) -> Self {
+        Self {
+            transcriber,
+            running: None,
+        }
+    }
+
+    /// Whether a transcription was started and its result not yet collected
+    pub fn is_busy(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Transcribe `job` on a new thread; an error if one is still running
+    pub fn start(&mut self, job: Job) -> Result<()> {
+        if self.is_busy() {
+            return Err(anyhow!("A transcription is already running"));
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, result) = mpsc::channel();
+        let transcriber = self.transcriber.clone();
+        let should_cancel = cancel.clone();
+        std::thread::Builder::new()
+            .name("transcription".to_string())
+            .spawn(move || {
+                let should_cancel = || should_cancel.load(Ordering::Relaxed);
+                let transcriber = transcriber.lock().unwrap_or_else(|e| e.into_inner());
+                let language = job.language.as_deref();
+                let result = if transcribe::needs_chunking(job.duration, job.chunk_threshold) {
+                    transcriber.transcribe_chunked(
+                        &job.audio_path,
+                        job.chunk_length,
+                        language,
+                        job.task,
+                        &should_cancel,
+                    )
+                } else {
+                    transcriber.transcribe(&job.audio_path, language, job.task, &should_cancel)
+                };
+                let _ = sender.send(result);
+            })?;
+        self.running = Some(Running { cancel, result });
+        Ok(())
+    }
+
+    /// Ask the running transcription to stop; it then finishes with `process::Cancelled`
+    pub fn cancel(&self) {
+        if let Some(running) = &self.running {
+            running.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Cancel the running transcription and wait up to `timeout` for it to end
+    /// The library backend can't be interrupted, so it may still be running afterwards.
+    pub fn shut_down(&mut self, timeout: Duration) {
+        self.cancel();
+        let deadline = Instant::now() + timeout;
+        while self.is_busy() && Instant::now() < deadline {
+            if self.poll().is_none() {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        }
+    }
+
+    /// The result of the running transcription once it finished, None while it runs
+    pub fn poll(&mut self) -> Option<Result<Transcription>> {
+        let running = self.running.as_ref()?;
+        let result = match running.result.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => {
+                Err(anyhow!("The transcription thread stopped unexpectedly"))
+            }
+        };
+        self.running = None;
+        Some(result)
+    }
+}